@@ -8,7 +8,7 @@ use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Command;
 
-use super::BuildSystem;
+use super::{BuildOptions, BuildSystem};
 use crate::deps::ResolvedDependency;
 use crate::scan::ProjectSources;
 use crate::util::pretty::*;
@@ -19,14 +19,21 @@ use crate::util::pretty::*;
 /// Executes the standard configuration and build process.
 pub struct AutotoolsBuildSystem {
     root: String,
+    options: BuildOptions,
 }
 
 impl AutotoolsBuildSystem {
-    pub fn new(root: &str) -> Self {
+    pub fn new(root: &str, options: BuildOptions) -> Self {
         Self {
             root: root.to_string(),
+            options,
         }
     }
+
+    /// Records the settings `./configure` ran with
+    fn stamp(&self) -> std::path::PathBuf {
+        self.options.cache_dir.join("autotools-options")
+    }
 }
 
 impl BuildSystem for AutotoolsBuildSystem {
@@ -38,7 +45,7 @@ impl BuildSystem for AutotoolsBuildSystem {
         root.join("configure").exists() || root.join("configure.ac").exists()
     }
 
-    fn configure(&self, _sources: &ProjectSources, deps: &[ResolvedDependency]) -> Result<()> {
+    fn configure(&self, _sources: &ProjectSources, _deps: &[ResolvedDependency]) -> Result<()> {
         print_build("Configuring Autotools...");
 
         // Run autoreconf if configure doesn't exist
@@ -57,27 +64,23 @@ impl BuildSystem for AutotoolsBuildSystem {
 
         let mut cmd = Command::new("./configure");
         cmd.current_dir(&self.root);
-
-        // Add dependency paths
-        for dep in deps {
-            for inc_path in &dep.include_paths {
-                cmd.env("CPPFLAGS", format!("-I{}", inc_path.display()));
-            }
-            for lib_path in &dep.lib_paths {
-                cmd.env("LDFLAGS", format!("-L{}", lib_path.display()));
-            }
-        }
-
-        let output = cmd.output().with_context(|| "Failed to run ./configure")?;
-
-        if !output.status.success() {
-            print_error(&format!(
-                "Configure failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        // CFLAGS, LDFLAGS and LIBS carry porters.toml's settings and the
+        // dependencies' include and library directories
+        self.options.apply_env(&mut cmd);
+
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to run ./configure")?;
+
+        if !status.success() {
+            print_error("Configure failed");
             return Err(anyhow::anyhow!("Configure failed"));
         }
 
+        std::fs::create_dir_all(&self.options.cache_dir)?;
+        std::fs::write(self.stamp(), self.options.fingerprint())?;
         print_success("Configure successful");
         Ok(())
     }
@@ -88,22 +91,27 @@ impl BuildSystem for AutotoolsBuildSystem {
         deps: &[ResolvedDependency],
         args: &[String],
     ) -> Result<()> {
-        // Configure first if needed
-        if !Path::new(&self.root).join("Makefile").exists() {
+        // Configure first if needed, or again when the build settings changed,
+        // as the Makefile keeps the flags configure was run with
+        if !Path::new(&self.root).join("Makefile").exists()
+            || std::fs::read_to_string(self.stamp()).ok() != Some(self.options.fingerprint())
+        {
             self.configure(sources, deps)?;
         }
 
         let mut cmd = Command::new("make");
         cmd.current_dir(&self.root);
         cmd.args(args);
+        self.options.apply_env(&mut cmd);
 
-        let output = cmd.output().with_context(|| "Failed to run make")?;
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to run make")?;
 
-        if !output.status.success() {
-            print_error(&format!(
-                "Make failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        if !status.success() {
+            print_error("Make failed");
             return Err(anyhow::anyhow!("Make failed"));
         }
 
@@ -126,10 +134,15 @@ impl BuildSystem for AutotoolsBuildSystem {
         let mut cmd = Command::new("make");
         cmd.current_dir(&self.root);
         cmd.arg("check");
+        self.options.apply_env(&mut cmd);
 
-        let output = cmd.output().with_context(|| "Failed to run make check")?;
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to run make check")?;
 
-        if !output.status.success() {
+        if !status.success() {
             print_error("Tests failed");
             return Err(anyhow::anyhow!("Tests failed"));
         }
@@ -8,7 +8,7 @@ use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Command;
 
-use super::BuildSystem;
+use super::{BuildOptions, BuildSystem};
 use crate::deps::ResolvedDependency;
 use crate::scan::ProjectSources;
 use crate::util::pretty::*;
@@ -19,14 +19,66 @@ use crate::util::pretty::*;
 /// and scalable builds for large codebases.
 pub struct BazelBuildSystem {
     root: String,
+    options: BuildOptions,
 }
 
 impl BazelBuildSystem {
-    pub fn new(root: &str) -> Self {
+    pub fn new(root: &str, options: BuildOptions) -> Self {
         Self {
             root: root.to_string(),
+            options,
         }
     }
+
+    /// Command line flags carrying the profile and porters.toml's compiler
+    /// and linker settings
+    fn bazel_flags(&self) -> Vec<String> {
+        let options = &self.options;
+        let profile = &options.profile;
+
+        // `opt` builds with -O2 and `dbg` with -g; anything else is added
+        let mut flags = vec![format!(
+            "--compilation_mode={}",
+            if profile.optimized() { "opt" } else { "dbg" }
+        )];
+        let mut copts = options.preprocessor_flags();
+        if profile.optimized() {
+            copts.push(format!("-O{}", profile.opt_level.as_str()));
+            if profile.debug {
+                copts.push("-g".to_string());
+            }
+        }
+        if profile.lto {
+            copts.push("-flto".to_string());
+        }
+        copts.extend(profile.sanitizer_compile_flags());
+        flags.extend(copts.iter().map(|flag| format!("--copt={}", flag)));
+        flags.extend(
+            options
+                .flags
+                .cflags
+                .iter()
+                .chain(&profile.flags.cflags)
+                .map(|f| format!("--conlyopt={}", f)),
+        );
+        flags.extend(
+            options
+                .flags
+                .cxxflags
+                .iter()
+                .chain(&profile.flags.cxxflags)
+                .map(|f| format!("--cxxopt={}", f)),
+        );
+        flags.extend(
+            options
+                .link_flags()
+                .iter()
+                .chain(&profile.link_flags())
+                .chain(&options.link_libraries())
+                .map(|flag| format!("--linkopt={}", flag)),
+        );
+        flags
+    }
 }
 
 impl BuildSystem for BazelBuildSystem {
@@ -56,6 +108,7 @@ impl BuildSystem for BazelBuildSystem {
         let mut cmd = Command::new("bazel");
         cmd.current_dir(&self.root);
         cmd.arg("build");
+        cmd.args(self.bazel_flags());
 
         // Default target if none specified
         if args.is_empty() {
@@ -64,15 +117,14 @@ impl BuildSystem for BazelBuildSystem {
             cmd.args(args);
         }
 
-        let output = cmd
-            .output()
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
             .with_context(|| "Failed to run bazel. Is Bazel installed?")?;
 
-        if !output.status.success() {
-            print_error(&format!(
-                "Bazel build failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        if !status.success() {
+            print_error("Bazel build failed");
             return Err(anyhow::anyhow!("Bazel build failed"));
         }
 
@@ -93,9 +145,9 @@ impl BuildSystem for BazelBuildSystem {
 
         cmd.args(args);
 
-        let output = cmd.output().with_context(|| "Failed to run bazel run")?;
+        let status = cmd.status().with_context(|| "Failed to run bazel run")?;
 
-        if !output.status.success() {
+        if !status.success() {
             print_error("Bazel run failed");
             return Err(anyhow::anyhow!("Bazel run failed"));
         }
@@ -107,11 +159,16 @@ impl BuildSystem for BazelBuildSystem {
         let mut cmd = Command::new("bazel");
         cmd.current_dir(&self.root);
         cmd.arg("test");
+        cmd.args(self.bazel_flags());
         cmd.arg("//...");
 
-        let output = cmd.output().with_context(|| "Failed to run bazel test")?;
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to run bazel test")?;
 
-        if !output.status.success() {
+        if !status.success() {
             print_error("Tests failed");
             return Err(anyhow::anyhow!("Tests failed"));
         }
@@ -8,7 +8,7 @@ use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Command;
 
-use super::BuildSystem;
+use super::{BuildOptions, BuildSystem};
 use crate::deps::ResolvedDependency;
 use crate::scan::ProjectSources;
 use crate::util::pretty::*;
@@ -19,12 +19,14 @@ use crate::util::pretty::*;
 /// and distributed execution capabilities.
 pub struct Buck2BuildSystem {
     root: String,
+    options: BuildOptions,
 }
 
 impl Buck2BuildSystem {
-    pub fn new(root: &str) -> Self {
+    pub fn new(root: &str, options: BuildOptions) -> Self {
         Self {
             root: root.to_string(),
+            options,
         }
     }
 }
@@ -52,6 +54,7 @@ impl BuildSystem for Buck2BuildSystem {
         let mut cmd = Command::new("buck2");
         cmd.current_dir(&self.root);
         cmd.arg("build");
+        self.options.apply_env(&mut cmd);
 
         if args.is_empty() {
             cmd.arg("//...");
@@ -59,15 +62,14 @@ impl BuildSystem for Buck2BuildSystem {
             cmd.args(args);
         }
 
-        let output = cmd
-            .output()
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
             .with_context(|| "Failed to run buck2. Is Buck2 installed?")?;
 
-        if !output.status.success() {
-            print_error(&format!(
-                "Buck2 build failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        if !status.success() {
+            print_error("Buck2 build failed");
             return Err(anyhow::anyhow!("Buck2 build failed"));
         }
 
@@ -88,9 +90,9 @@ impl BuildSystem for Buck2BuildSystem {
 
         cmd.args(args);
 
-        let output = cmd.output().with_context(|| "Failed to run buck2 run")?;
+        let status = cmd.status().with_context(|| "Failed to run buck2 run")?;
 
-        if !output.status.success() {
+        if !status.success() {
             print_error("Buck2 run failed");
             return Err(anyhow::anyhow!("Buck2 run failed"));
         }
@@ -102,11 +104,16 @@ impl BuildSystem for Buck2BuildSystem {
         let mut cmd = Command::new("buck2");
         cmd.current_dir(&self.root);
         cmd.arg("test");
+        self.options.apply_env(&mut cmd);
         cmd.arg("//...");
 
-        let output = cmd.output().with_context(|| "Failed to run buck2 test")?;
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to run buck2 test")?;
 
-        if !output.status.success() {
+        if !status.success() {
             print_error("Tests failed");
             return Err(anyhow::anyhow!("Tests failed"));
         }
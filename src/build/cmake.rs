@@ -8,7 +8,7 @@ use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Command;
 
-use super::BuildSystem;
+use super::{BuildOptions, BuildSystem};
 use crate::deps::ResolvedDependency;
 use crate::scan::ProjectSources;
 use crate::util::pretty::*;
@@ -19,14 +19,127 @@ use crate::util::pretty::*;
 /// Generates native build files and invokes the appropriate build tool.
 pub struct CMakeBuildSystem {
     root: String,
+    options: BuildOptions,
 }
 
 impl CMakeBuildSystem {
-    pub fn new(root: &str) -> Self {
+    pub fn new(root: &str, options: BuildOptions) -> Self {
         Self {
             root: root.to_string(),
+            options,
         }
     }
+
+    /// Check whether the configured build used a different feature set or
+    /// build settings, or predates the dependency script
+    fn needs_configure(&self) -> bool {
+        let cache_file = self.options.profile_dir().join("CMakeCache.txt");
+        let Ok(cache) = std::fs::read_to_string(cache_file) else {
+            return true;
+        };
+        let cached = |name: &str| {
+            cache
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+                .and_then(|entry| entry.split_once('='))
+                .map(|(_, value)| value.to_string())
+                .unwrap_or_default()
+        };
+
+        cached("PORTERS_FEATURES") != self.options.features.join(";")
+            || !cached("CMAKE_PROJECT_INCLUDE").ends_with(DEPENDENCY_SCRIPT)
+            || self
+                .options
+                .cmake_cache()
+                .iter()
+                .any(|(name, value)| cached(name) != *value)
+    }
+}
+
+/// Script included after the project's `project()` call (`CMAKE_PROJECT_INCLUDE`)
+const DEPENDENCY_SCRIPT: &str = "porters-dependencies.cmake";
+
+/// CMake code that finds the dependencies Porters built from source
+///
+/// Each package's `find_package` name and targets come from its registry
+/// entry. `PORTERS_DEPENDENCY_TARGETS` lists the usual target of each one,
+/// so a project can link them all or name targets such as `fmt::fmt`.
+///
+/// The script also applies porters.toml's flags, include directories and
+/// libraries, passed as `PORTERS_*` cache variables, to every target.
+pub fn dependency_script(deps: &[ResolvedDependency]) -> String {
+    let built: Vec<_> = deps
+        .iter()
+        .filter_map(|dep| {
+            let build = dep.build.as_ref()?;
+            Some((dep, build, build.prefix.as_ref()?))
+        })
+        .collect();
+    let cmake_path = |path: &Path| path.display().to_string().replace('\\', "/");
+
+    let mut script = String::from(
+        "# Generated by Porters: dependencies built from source\ninclude_guard(GLOBAL)\n",
+    );
+    if !built.is_empty() {
+        script.push_str("\nlist(PREPEND CMAKE_PREFIX_PATH\n");
+        for (_, _, prefix) in &built {
+            script.push_str(&format!("    \"{}\"\n", cmake_path(prefix)));
+        }
+        script.push_str(")\n");
+    }
+
+    let mut targets = Vec::new();
+    for (dep, build, _) in &built {
+        let Some(cmake) = &build.cmake else {
+            continue;
+        };
+        let package = cmake.find_package.as_deref().unwrap_or(&dep.name);
+        if cmake.components.is_empty() {
+            script.push_str(&format!("find_package({} REQUIRED)\n", package));
+        } else {
+            script.push_str(&format!(
+                "find_package({} REQUIRED COMPONENTS {})\n",
+                package,
+                cmake.components.join(" ")
+            ));
+        }
+        targets.extend(cmake.targets.first().cloned());
+    }
+
+    script.push_str(&format!(
+        "\nset(PORTERS_DEPENDENCY_TARGETS {})\n",
+        targets.join(" ")
+    ));
+    script.push_str(BUILD_SETTINGS);
+    script
+}
+
+/// Applies the `PORTERS_*` build settings from porters.toml
+const BUILD_SETTINGS: &str = r#"
+# porters.toml [build] flags, include directories and libraries
+add_compile_options(
+    "$<$<COMPILE_LANGUAGE:C>:${PORTERS_C_FLAGS}>"
+    "$<$<COMPILE_LANGUAGE:CXX>:${PORTERS_CXX_FLAGS}>"
+)
+add_link_options(${PORTERS_LINK_FLAGS})
+link_libraries(${PORTERS_LINK_LIBRARIES})
+"#;
+
+/// Write the dependency script into `build_dir`, leaving it alone when
+/// unchanged so CMake doesn't reconfigure
+pub(crate) fn write_dependency_script(
+    build_dir: &Path,
+    deps: &[ResolvedDependency],
+) -> Result<std::path::PathBuf> {
+    std::fs::create_dir_all(build_dir)?;
+    let path = build_dir.join(DEPENDENCY_SCRIPT);
+
+    let script = dependency_script(deps);
+    if std::fs::read_to_string(&path).ok().as_deref() != Some(script.as_str()) {
+        std::fs::write(&path, script)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(path)
 }
 
 impl BuildSystem for CMakeBuildSystem {
@@ -42,7 +155,7 @@ impl BuildSystem for CMakeBuildSystem {
         print_build("Configuring CMake...");
 
         let mut cmd = Command::new("cmake");
-        cmd.arg("-B").arg("build");
+        cmd.arg("-B").arg(self.options.profile_dir());
         cmd.arg("-S").arg(&self.root);
 
         // Add dependency include paths
@@ -52,13 +165,23 @@ impl BuildSystem for CMakeBuildSystem {
             }
         }
 
-        let output = cmd.output().with_context(|| "Failed to run cmake")?;
+        // Find the dependencies built from source after `project()`
+        let script = write_dependency_script(&self.options.profile_dir(), deps)?;
+        cmd.arg(format!("-DCMAKE_PROJECT_INCLUDE={}", script.display()));
 
-        if !output.status.success() {
-            print_error(&format!(
-                "CMake configuration failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        // Feature options, dropping ones left in the cache by earlier builds
+        cmd.arg("-UPORTERS_*FEATURE_*");
+        cmd.args(self.options.cmake_options());
+        self.options.apply_feature_env(&mut cmd);
+
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to run cmake")?;
+
+        if !status.success() {
+            print_error("CMake configuration failed");
             return Err(anyhow::anyhow!("CMake configuration failed"));
         }
 
@@ -71,40 +194,43 @@ impl BuildSystem for CMakeBuildSystem {
         deps: &[ResolvedDependency],
         args: &[String],
     ) -> Result<()> {
-        // Configure first if needed
-        if !Path::new("build").exists() {
+        // Configure first if needed, or again when the active features changed
+        if !self.options.profile_dir().exists() || self.needs_configure() {
             self.configure(sources, deps)?;
+        } else {
+            // CMake reconfigures by itself when the script changes
+            write_dependency_script(&self.options.profile_dir(), deps)?;
         }
 
         print_build("Building with CMake...");
 
         let mut cmd = Command::new("cmake");
-        cmd.arg("--build").arg("build");
+        cmd.arg("--build").arg(self.options.profile_dir());
+        // Multi-config generators pick the build type when building
+        cmd.arg("--config")
+            .arg(self.options.profile.cmake_build_type());
 
         for arg in args {
             cmd.arg(arg);
         }
 
-        let output = cmd
-            .output()
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
             .with_context(|| "Failed to run cmake --build")?;
 
-        if !output.status.success() {
-            print_error(&format!(
-                "Build failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        if !status.success() {
+            print_error("Build failed");
             return Err(anyhow::anyhow!("Build failed"));
         }
 
-        print!("{}", String::from_utf8_lossy(&output.stdout));
-
         Ok(())
     }
 
     fn run(&self, args: &[String]) -> Result<()> {
         // Find executable in build directory
-        let build_dir = Path::new("build");
+        let build_dir = self.options.profile_dir();
 
         // Helper function to search directory recursively for executables
         fn find_executable(dir: &Path, depth: usize) -> Option<std::fs::DirEntry> {
@@ -162,7 +288,7 @@ impl BuildSystem for CMakeBuildSystem {
             None
         }
 
-        let exe = find_executable(build_dir, 0);
+        let exe = find_executable(&build_dir, 0);
 
         if let Some(exe) = exe {
             print_info(&format!("🚀 Found executable: {}", exe.path().display()));
@@ -191,16 +317,20 @@ impl BuildSystem for CMakeBuildSystem {
 
         print_build("Running tests with CTest...");
 
-        let output = Command::new("ctest")
-            .arg("--test-dir")
-            .arg("build")
-            .arg("--output-on-failure")
-            .output()
+        let mut cmd = Command::new("ctest");
+        cmd.arg("--test-dir")
+            .arg(self.options.profile_dir())
+            .arg("-C")
+            .arg(self.options.profile.cmake_build_type())
+            .arg("--output-on-failure");
+
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
             .with_context(|| "Failed to run ctest")?;
 
-        print!("{}", String::from_utf8_lossy(&output.stdout));
-
-        if !output.status.success() {
+        if !status.success() {
             print_error("Tests failed");
             return Err(anyhow::anyhow!("Tests failed"));
         }
@@ -211,11 +341,8 @@ impl BuildSystem for CMakeBuildSystem {
     fn clean(&self) -> Result<()> {
         print_build("Cleaning CMake build...");
 
-        if Path::new("build").exists() {
-            std::fs::remove_dir_all("build").with_context(|| "Failed to remove build directory")?;
-        }
-
-        Ok(())
+        // Every profile's build
+        self.options.clean_build_dir(Path::new(&self.root))
     }
 }
 
@@ -8,7 +8,7 @@ use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Command;
 
-use super::BuildSystem;
+use super::{BuildOptions, BuildSystem};
 use crate::deps::ResolvedDependency;
 use crate::scan::ProjectSources;
 use crate::util::pretty::*;
@@ -19,12 +19,14 @@ use crate::util::pretty::*;
 /// using Conan-generated build files.
 pub struct ConanBuildSystem {
     root: String,
+    options: BuildOptions,
 }
 
 impl ConanBuildSystem {
-    pub fn new(root: &str) -> Self {
+    pub fn new(root: &str, options: BuildOptions) -> Self {
         Self {
             root: root.to_string(),
+            options,
         }
     }
 }
@@ -46,16 +48,19 @@ impl BuildSystem for ConanBuildSystem {
         cmd.arg("install");
         cmd.arg(".");
         cmd.arg("--build=missing");
-
-        let output = cmd
-            .output()
+        cmd.arg("-s").arg(format!(
+            "build_type={}",
+            self.options.profile.cmake_build_type()
+        ));
+
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
             .with_context(|| "Failed to run conan. Is Conan installed?")?;
 
-        if !output.status.success() {
-            print_error(&format!(
-                "Conan install failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        if !status.success() {
+            print_error("Conan install failed");
             return Err(anyhow::anyhow!("Conan install failed"));
         }
 
@@ -79,28 +84,37 @@ impl BuildSystem for ConanBuildSystem {
 
             let mut cmd = Command::new("cmake");
             cmd.current_dir(&self.root);
-            cmd.arg("-B").arg("build");
+            cmd.arg("-B").arg(self.options.profile_dir());
             cmd.arg("-S").arg(".");
+            cmd.args(self.options.cmake_options());
+            self.options.apply_feature_env(&mut cmd);
+            // Apply porters.toml's build settings and find the dependencies
+            let script = super::cmake::write_dependency_script(&self.options.profile_dir(), deps)?;
+            cmd.arg(format!("-DCMAKE_PROJECT_INCLUDE={}", script.display()));
             cmd.arg("-DCMAKE_TOOLCHAIN_FILE=conan_toolchain.cmake");
-            cmd.arg("-DCMAKE_BUILD_TYPE=Release");
 
-            cmd.output()
+            self.options
+                .diagnostics
+                .run(&mut cmd)
                 .with_context(|| "Failed to configure with CMake")?;
 
             let mut build_cmd = Command::new("cmake");
             build_cmd.current_dir(&self.root);
-            build_cmd.arg("--build").arg("build");
+            build_cmd.arg("--build").arg(self.options.profile_dir());
+            build_cmd
+                .arg("--config")
+                .arg(self.options.profile.cmake_build_type());
             build_cmd.args(args);
+            self.options.apply_feature_env(&mut build_cmd);
 
-            let output = build_cmd
-                .output()
+            let status = self
+                .options
+                .diagnostics
+                .run(&mut build_cmd)
                 .with_context(|| "Failed to build with CMake")?;
 
-            if !output.status.success() {
-                print_error(&format!(
-                    "Build failed:\n{}",
-                    String::from_utf8_lossy(&output.stderr)
-                ));
+            if !status.success() {
+                print_error("Build failed");
                 return Err(anyhow::anyhow!("Build failed"));
             }
         } else {
@@ -116,7 +130,11 @@ impl BuildSystem for ConanBuildSystem {
 
     fn run(&self, args: &[String]) -> Result<()> {
         print_warning("Conan is a package manager. Run the executable from the build directory.");
-        print_info(&format!("Try: ./build/bin/<executable> {}", args.join(" ")));
+        print_info(&format!(
+            "Try: {}/bin/<executable> {}",
+            self.options.profile_dir().display(),
+            args.join(" ")
+        ));
         Ok(())
     }
 
@@ -126,9 +144,13 @@ impl BuildSystem for ConanBuildSystem {
         cmd.current_dir(&self.root);
         cmd.arg("test");
 
-        let output = cmd.output().with_context(|| "Failed to run conan test")?;
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to run conan test")?;
 
-        if !output.status.success() {
+        if !status.success() {
             print_warning("Conan test not configured or failed");
         }
 
@@ -136,11 +158,16 @@ impl BuildSystem for ConanBuildSystem {
     }
 
     fn clean(&self) -> Result<()> {
+        // Every profile's build
+        self.options.clean_build_dir(Path::new(&self.root))?;
+
         // Remove Conan build artifacts
-        let paths_to_clean = vec!["build", "conan_cache", "conanbuildinfo.txt"];
+        let paths_to_clean = [
+            Path::new(&self.root).join("conan_cache"),
+            Path::new(&self.root).join("conanbuildinfo.txt"),
+        ];
 
-        for path in paths_to_clean {
-            let full_path = Path::new(&self.root).join(path);
+        for full_path in paths_to_clean {
             if full_path.exists() {
                 if full_path.is_dir() {
                     std::fs::remove_dir_all(&full_path).ok();
@@ -8,7 +8,7 @@ use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Command;
 
-use super::BuildSystem;
+use super::{BuildOptions, BuildSystem};
 use crate::config::CustomBuild;
 use crate::deps::ResolvedDependency;
 use crate::scan::ProjectSources;
@@ -21,13 +21,15 @@ use crate::util::pretty::*;
 pub struct CustomBuildSystem {
     root: String,
     config: CustomBuild,
+    options: BuildOptions,
 }
 
 impl CustomBuildSystem {
-    pub fn new(root: &str, config: CustomBuild) -> Self {
+    pub fn new(root: &str, config: CustomBuild, options: BuildOptions) -> Self {
         Self {
             root: root.to_string(),
             config,
+            options,
         }
     }
 
@@ -36,32 +38,29 @@ impl CustomBuildSystem {
 
         print_build(&format!("Executing: {}", cmd_str));
 
-        let output = if cfg!(windows) {
-            Command::new("powershell")
-                .arg("-Command")
-                .arg(&cmd_str)
-                .current_dir(&self.root)
-                .output()
+        let mut cmd = if cfg!(windows) {
+            let mut cmd = Command::new("powershell");
+            cmd.arg("-Command");
+            cmd
         } else {
-            Command::new("sh")
-                .arg("-c")
-                .arg(&cmd_str)
-                .current_dir(&self.root)
-                .output()
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c");
+            cmd
         };
+        cmd.arg(&cmd_str).current_dir(&self.root);
+        self.options.apply_env(&mut cmd);
 
-        let output = output.with_context(|| format!("Failed to execute: {}", cmd_str))?;
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| format!("Failed to execute: {}", cmd_str))?;
 
-        if !output.status.success() {
-            print_error(&format!(
-                "Command failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        if !status.success() {
+            print_error("Command failed");
             return Err(anyhow::anyhow!("Command failed"));
         }
 
-        print!("{}", String::from_utf8_lossy(&output.stdout));
-
         Ok(())
     }
 
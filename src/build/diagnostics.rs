@@ -0,0 +1,388 @@
+//! Build output streaming and compiler diagnostics
+//!
+//! Build tools run with their output streamed line by line as they produce
+//! it; the output of a parallel job is held until it exits, so each job's
+//! lines stay together. Lines holding a GCC, Clang or MSVC diagnostic are also recorded, so
+//! the build can end with a count of errors and warnings:
+//!
+//! ```text
+//! src/main.c:3:9: warning: unused variable 'x' [-Wunused-variable]
+//! C:\src\main.cpp(10,5): error C2065: 'x': undeclared identifier
+//! ```
+//!
+//! With `--message-format json` every diagnostic is written to stdout as a
+//! JSON object on its own line, and everything else goes to stderr.
+
+use serde::Serialize;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+
+use crate::util::pretty::*;
+
+/// How build output is reported
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MessageFormat {
+    /// Tool output as it is, with a summary at the end
+    #[default]
+    Human,
+    /// One JSON object per diagnostic on stdout
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+/// A compiler diagnostic
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<u32>,
+    pub severity: Severity,
+    pub message: String,
+    /// Warning option (`-Wunused-variable`) or MSVC code (`C2065`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+/// Messages written with `--message-format json`
+#[derive(Serialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+enum Message<'a> {
+    CompilerMessage(&'a Diagnostic),
+    BuildFinished {
+        success: bool,
+        errors: usize,
+        warnings: usize,
+    },
+}
+
+/// Where JSON messages are written, stdout unless a test captures them
+///
+/// Messages are written whole under the lock, so those of parallel jobs
+/// don't mix.
+#[derive(Clone)]
+struct Output(Arc<Mutex<dyn Write + Send>>);
+
+impl Default for Output {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(std::io::stdout())))
+    }
+}
+
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Output")
+    }
+}
+
+/// Streams build output and collects the diagnostics in it
+///
+/// Clones share the collected diagnostics, so the count covers every
+/// command a build ran.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    format: MessageFormat,
+    records: Arc<Mutex<Vec<Diagnostic>>>,
+    output: Output,
+}
+
+impl Diagnostics {
+    pub fn new(format: MessageFormat) -> Self {
+        Self {
+            format,
+            ..Self::default()
+        }
+    }
+
+    /// Diagnostics writing their JSON messages to `output`
+    #[cfg(test)]
+    pub fn with_output(format: MessageFormat, output: impl Write + Send + 'static) -> Self {
+        Self {
+            format,
+            output: Output(Arc::new(Mutex::new(output))),
+            ..Self::default()
+        }
+    }
+
+    /// Run `cmd`, streaming its stdout and stderr while it runs
+    pub fn run(&self, cmd: &mut Command) -> std::io::Result<ExitStatus> {
+        let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| read_lines(stdout, |line| self.line(line, false)));
+            scope.spawn(|| read_lines(stderr, |line| self.line(line, true)));
+        });
+        child.wait()
+    }
+
+    /// Run `cmd` as one of several parallel jobs
+    ///
+    /// Its output is read while it runs but reported once it exits, so the
+    /// lines of one job stay together.
+    pub fn run_job(&self, cmd: &mut Command) -> std::io::Result<ExitStatus> {
+        let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+
+        let output = Mutex::new(String::new());
+        let collect = |line: &str| {
+            let mut output = output.lock().unwrap();
+            output.push_str(line);
+            output.push('\n');
+        };
+        std::thread::scope(|scope| {
+            scope.spawn(|| read_lines(stdout, collect));
+            scope.spawn(|| read_lines(stderr, collect));
+        });
+        let status = child.wait();
+        self.report(&output.into_inner().unwrap());
+        status
+    }
+
+    /// Report output captured some other way, such as by a parallel compile
+    ///
+    /// The lines are printed together, even when other threads report at
+    /// the same time.
+    pub fn report(&self, output: &str) {
+        // Reentrant, so the lines below can still be written
+        let _stderr = std::io::stderr().lock();
+        for line in output.lines() {
+            self.line(line, true);
+        }
+    }
+
+    fn line(&self, line: &str, stderr: bool) {
+        let diagnostic = parse_line(line);
+        match self.format {
+            MessageFormat::Human if !stderr => println!("{}", line),
+            MessageFormat::Human => eprintln!("{}", line),
+            MessageFormat::Json => {
+                eprintln!("{}", line);
+                if let Some(diagnostic) = &diagnostic {
+                    self.emit(&Message::CompilerMessage(diagnostic));
+                }
+            }
+        }
+        if let Some(diagnostic) = diagnostic {
+            self.records.lock().unwrap().push(diagnostic);
+        }
+    }
+
+    /// Number of errors and warnings seen so far
+    pub fn counts(&self) -> (usize, usize) {
+        let records = self.records.lock().unwrap();
+        let count = |severity| records.iter().filter(|d| d.severity == severity).count();
+        (count(Severity::Error), count(Severity::Warning))
+    }
+
+    /// Print the error and warning counts once the build is done
+    pub fn finish(&self, success: bool) {
+        let (errors, warnings) = self.counts();
+        match self.format {
+            MessageFormat::Json => self.emit(&Message::BuildFinished {
+                success,
+                errors,
+                warnings,
+            }),
+            MessageFormat::Human if errors > 0 => print_error(&format!(
+                "{}, {}",
+                plural(errors, "error"),
+                plural(warnings, "warning")
+            )),
+            MessageFormat::Human if warnings > 0 => print_warning(&plural(warnings, "warning")),
+            MessageFormat::Human => {}
+        }
+    }
+
+    fn emit(&self, message: &Message) {
+        if let Ok(json) = serde_json::to_string(message) {
+            let mut output = self.output.0.lock().unwrap();
+            writeln!(output, "{}", json).ok();
+            output.flush().ok();
+        }
+    }
+}
+
+/// Call `f` with each line `reader` produces
+fn read_lines(reader: impl Read, mut f: impl FnMut(&str)) {
+    let mut reader = BufReader::new(reader);
+    let mut buf = Vec::new();
+    // Tools don't always write UTF-8
+    while reader
+        .read_until(b'\n', &mut buf)
+        .is_ok_and(|read| read > 0)
+    {
+        let line = String::from_utf8_lossy(&buf);
+        f(line.trim_end_matches(['\n', '\r']));
+        buf.clear();
+    }
+}
+
+fn plural(count: usize, word: &str) -> String {
+    if count == 1 {
+        format!("1 {}", word)
+    } else {
+        format!("{} {}s", count, word)
+    }
+}
+
+/// Parse a GCC, Clang or MSVC diagnostic line
+pub fn parse_line(line: &str) -> Option<Diagnostic> {
+    parse_gcc(line).or_else(|| parse_msvc(line))
+}
+
+/// `file:line:column: severity: message [-Wflag]`, the column being optional
+fn parse_gcc(line: &str) -> Option<Diagnostic> {
+    let (location, severity, message) = [
+        (": fatal error: ", Severity::Error),
+        (": error: ", Severity::Error),
+        (": warning: ", Severity::Warning),
+        (": note: ", Severity::Note),
+    ]
+    .into_iter()
+    .filter_map(|(marker, severity)| {
+        line.find(marker)
+            .map(|at| (&line[..at], severity, &line[at + marker.len()..]))
+    })
+    .min_by_key(|(location, _, _)| location.len())?;
+
+    let mut parts = location.rsplitn(3, ':');
+    let last = parts.next()?.parse().ok()?;
+    let (file, line, column) = match (parts.next(), parts.next()) {
+        (Some(line), Some(file)) if line.parse::<u32>().is_ok() => {
+            (file, line.parse().ok()?, Some(last))
+        }
+        _ => (location.rsplit_once(':')?.0, last, None),
+    };
+    if file.is_empty() {
+        return None;
+    }
+
+    let (message, code) = match message.rsplit_once(" [") {
+        Some((text, flag)) if flag.starts_with("-W") && flag.ends_with(']') => {
+            (text, Some(flag.trim_end_matches(']').to_string()))
+        }
+        _ => (message, None),
+    };
+    Some(Diagnostic {
+        file: file.to_string(),
+        line,
+        column,
+        severity,
+        message: message.to_string(),
+        code,
+    })
+}
+
+/// `file(line): severity C1234: message` or `file(line,column): ...`
+fn parse_msvc(line: &str) -> Option<Diagnostic> {
+    let (location, rest) = line.split_once("): ")?;
+    let (file, position) = location.rsplit_once('(')?;
+    let (line, column) = match position.split_once(',') {
+        Some((line, column)) => (line.parse().ok()?, Some(column.parse().ok()?)),
+        None => (position.parse().ok()?, None),
+    };
+
+    let (severity, rest) = [
+        ("fatal error ", Severity::Error),
+        ("error ", Severity::Error),
+        ("warning ", Severity::Warning),
+        ("note: ", Severity::Note),
+    ]
+    .into_iter()
+    .find_map(|(prefix, severity)| rest.strip_prefix(prefix).map(|rest| (severity, rest)))?;
+    let (code, message) = match rest.split_once(": ") {
+        Some((code, message)) if severity != Severity::Note => (Some(code.to_string()), message),
+        _ => (None, rest),
+    };
+
+    Some(Diagnostic {
+        file: file.trim().to_string(),
+        line,
+        column,
+        severity,
+        message: message.to_string(),
+        code,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(
+            parse_line("src/main.c:3:9: warning: unused variable 'x' [-Wunused-variable]"),
+            Some(Diagnostic {
+                file: "src/main.c".to_string(),
+                line: 3,
+                column: Some(9),
+                severity: Severity::Warning,
+                message: "unused variable 'x'".to_string(),
+                code: Some("-Wunused-variable".to_string()),
+            })
+        );
+
+        let fatal =
+            parse_line("C:\\src\\app.c:12:10: fatal error: foo.h: No such file or directory")
+                .unwrap();
+        assert_eq!(fatal.file, "C:\\src\\app.c");
+        assert_eq!((fatal.line, fatal.column), (12, Some(10)));
+        assert_eq!(fatal.severity, Severity::Error);
+        assert_eq!(fatal.message, "foo.h: No such file or directory");
+
+        let no_column = parse_line("main.cpp:7: error: expected ';'").unwrap();
+        assert_eq!((no_column.line, no_column.column), (7, None));
+
+        let msvc =
+            parse_line("C:\\src\\main.cpp(10,5): error C2065: 'x': undeclared identifier").unwrap();
+        assert_eq!(msvc.file, "C:\\src\\main.cpp");
+        assert_eq!((msvc.line, msvc.column), (10, Some(5)));
+        assert_eq!(msvc.code.as_deref(), Some("C2065"));
+        assert_eq!(msvc.message, "'x': undeclared identifier");
+
+        let msvc =
+            parse_line("util.h(4): warning C4996: 'strcpy': This function may be unsafe").unwrap();
+        assert_eq!(msvc.severity, Severity::Warning);
+        assert_eq!(msvc.column, None);
+
+        for line in [
+            "[2/4] Building C object CMakeFiles/app.dir/main.c.o",
+            "collect2: error: ld returned 1 exit status",
+            "make: *** [Makefile:3: all] Error 1",
+            "In file included from src/main.c:1:",
+        ] {
+            assert_eq!(parse_line(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_json_messages() {
+        let diagnostic = parse_line("a.c:1:2: error: oops").unwrap();
+        assert_eq!(
+            serde_json::to_string(&Message::CompilerMessage(&diagnostic)).unwrap(),
+            r#"{"reason":"compiler-message","file":"a.c","line":1,"column":2,"severity":"error","message":"oops"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&Message::BuildFinished {
+                success: false,
+                errors: 1,
+                warnings: 0
+            })
+            .unwrap(),
+            r#"{"reason":"build-finished","success":false,"errors":1,"warnings":0}"#
+        );
+    }
+}
@@ -7,7 +7,7 @@ use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Command;
 
-use super::BuildSystem;
+use super::{BuildOptions, BuildSystem};
 use crate::deps::ResolvedDependency;
 use crate::scan::ProjectSources;
 use crate::util::pretty::*;
@@ -18,12 +18,14 @@ use crate::util::pretty::*;
 /// Executes make targets for build, test, and clean operations.
 pub struct MakeBuildSystem {
     root: String,
+    options: BuildOptions,
 }
 
 impl MakeBuildSystem {
-    pub fn new(root: &str) -> Self {
+    pub fn new(root: &str, options: BuildOptions) -> Self {
         Self {
             root: root.to_string(),
+            options,
         }
     }
 }
@@ -55,22 +57,19 @@ impl BuildSystem for MakeBuildSystem {
         for arg in args {
             cmd.arg(arg);
         }
+        self.options.apply_env(&mut cmd);
 
-        let output = cmd
-            .current_dir(&self.root)
-            .output()
+        let status = self
+            .options
+            .diagnostics
+            .run(cmd.current_dir(&self.root))
             .with_context(|| "Failed to run make")?;
 
-        if !output.status.success() {
-            print_error(&format!(
-                "Build failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        if !status.success() {
+            print_error("Build failed");
             return Err(anyhow::anyhow!("Build failed"));
         }
 
-        print!("{}", String::from_utf8_lossy(&output.stdout));
-
         Ok(())
     }
 
@@ -97,15 +96,17 @@ impl BuildSystem for MakeBuildSystem {
     fn test(&self, _sources: &ProjectSources, _deps: &[ResolvedDependency]) -> Result<()> {
         print_build("Running tests with Make...");
 
-        let output = Command::new("make")
-            .arg("test")
-            .current_dir(&self.root)
-            .output()
-            .with_context(|| "Failed to run make test")?;
+        let mut cmd = Command::new("make");
+        cmd.arg("test").current_dir(&self.root);
+        self.options.apply_env(&mut cmd);
 
-        print!("{}", String::from_utf8_lossy(&output.stdout));
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to run make test")?;
 
-        if !output.status.success() {
+        if !status.success() {
             print_error("Tests failed");
             return Err(anyhow::anyhow!("Tests failed"));
         }
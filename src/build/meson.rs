@@ -8,7 +8,7 @@ use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Command;
 
-use super::BuildSystem;
+use super::{BuildOptions, BuildSystem};
 use crate::deps::ResolvedDependency;
 use crate::scan::ProjectSources;
 use crate::util::pretty::*;
@@ -19,14 +19,57 @@ use crate::util::pretty::*;
 /// using Meson and Ninja.
 pub struct MesonBuildSystem {
     root: String,
+    options: BuildOptions,
 }
 
 impl MesonBuildSystem {
-    pub fn new(root: &str) -> Self {
+    pub fn new(root: &str, options: BuildOptions) -> Self {
         Self {
             root: root.to_string(),
+            options,
         }
     }
+
+    /// Built-in options carrying the profile and porters.toml's compiler and
+    /// linker settings
+    pub(crate) fn meson_options(&self) -> Vec<String> {
+        let profile = &self.options.profile;
+        let sanitize = if profile.sanitize.is_empty() {
+            "none".to_string()
+        } else {
+            profile.sanitize.join(",")
+        };
+
+        let mut link_args = self.options.link_flags();
+        link_args.extend(self.options.link_libraries());
+        vec![
+            format!("-Doptimization={}", profile.meson_optimization()),
+            format!("-Ddebug={}", profile.debug),
+            format!("-Db_lto={}", profile.lto),
+            format!("-Db_sanitize={}", sanitize),
+            format!("-Dc_args={}", meson_array(&self.options.c_flags())),
+            format!("-Dcpp_args={}", meson_array(&self.options.cxx_flags())),
+            format!("-Dc_link_args={}", meson_array(&link_args)),
+            format!("-Dcpp_link_args={}", meson_array(&link_args)),
+        ]
+    }
+
+    /// Records the settings the build directory was configured with
+    fn stamp(&self) -> std::path::PathBuf {
+        self.options.profile_dir().join(STAMP)
+    }
+}
+
+/// File in the build directory holding the configured settings
+const STAMP: &str = "porters-options";
+
+/// A Meson array option value (`['-DX', '-Iinclude']`)
+fn meson_array(values: &[String]) -> String {
+    let quoted: Vec<String> = values
+        .iter()
+        .map(|value| format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")))
+        .collect();
+    format!("[{}]", quoted.join(", "))
 }
 
 impl BuildSystem for MesonBuildSystem {
@@ -41,21 +84,29 @@ impl BuildSystem for MesonBuildSystem {
     fn configure(&self, _sources: &ProjectSources, _deps: &[ResolvedDependency]) -> Result<()> {
         print_build("Configuring Meson...");
 
-        let output = Command::new("meson")
-            .arg("setup")
-            .arg("build")
-            .current_dir(&self.root)
-            .output()
+        // An existing build directory only needs its options updated
+        let mut cmd = Command::new("meson");
+        let build_dir = self.options.profile_dir();
+        if build_dir.exists() {
+            cmd.arg("configure").arg(&build_dir);
+        } else {
+            cmd.arg("setup").arg(&build_dir);
+        }
+        cmd.args(self.meson_options()).current_dir(&self.root);
+        self.options.apply_feature_env(&mut cmd);
+
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
             .with_context(|| "Failed to run meson setup")?;
 
-        if !output.status.success() {
-            print_error(&format!(
-                "Meson configuration failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        if !status.success() {
+            print_error("Meson configuration failed");
             return Err(anyhow::anyhow!("Meson configuration failed"));
         }
 
+        std::fs::write(self.stamp(), self.options.fingerprint())?;
         Ok(())
     }
 
@@ -65,7 +116,8 @@ impl BuildSystem for MesonBuildSystem {
         deps: &[ResolvedDependency],
         args: &[String],
     ) -> Result<()> {
-        if !Path::new("build").exists() {
+        // Configure first if needed, or again when the build settings changed
+        if std::fs::read_to_string(self.stamp()).ok() != Some(self.options.fingerprint()) {
             self.configure(sources, deps)?;
         }
 
@@ -73,34 +125,31 @@ impl BuildSystem for MesonBuildSystem {
 
         let mut cmd = Command::new("meson");
         cmd.arg("compile");
-        cmd.arg("-C").arg("build");
+        cmd.arg("-C").arg(self.options.profile_dir());
 
         for arg in args {
             cmd.arg(arg);
         }
+        self.options.apply_feature_env(&mut cmd);
 
-        let output = cmd
-            .current_dir(&self.root)
-            .output()
+        let status = self
+            .options
+            .diagnostics
+            .run(cmd.current_dir(&self.root))
             .with_context(|| "Failed to run meson compile")?;
 
-        if !output.status.success() {
-            print_error(&format!(
-                "Build failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        if !status.success() {
+            print_error("Build failed");
             return Err(anyhow::anyhow!("Build failed"));
         }
 
-        print!("{}", String::from_utf8_lossy(&output.stdout));
-
         Ok(())
     }
 
     fn run(&self, args: &[String]) -> Result<()> {
         let mut cmd = Command::new("meson");
         cmd.arg("devenv");
-        cmd.arg("-C").arg("build");
+        cmd.arg("-C").arg(self.options.profile_dir());
 
         for arg in args {
             cmd.arg(arg);
@@ -123,17 +172,19 @@ impl BuildSystem for MesonBuildSystem {
 
         print_build("Running tests with Meson...");
 
-        let output = Command::new("meson")
-            .arg("test")
+        let mut cmd = Command::new("meson");
+        cmd.arg("test")
             .arg("-C")
-            .arg("build")
-            .current_dir(&self.root)
-            .output()
-            .with_context(|| "Failed to run meson test")?;
+            .arg(self.options.profile_dir())
+            .current_dir(&self.root);
 
-        print!("{}", String::from_utf8_lossy(&output.stdout));
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to run meson test")?;
 
-        if !output.status.success() {
+        if !status.success() {
             print_error("Tests failed");
             return Err(anyhow::anyhow!("Tests failed"));
         }
@@ -144,10 +195,7 @@ impl BuildSystem for MesonBuildSystem {
     fn clean(&self) -> Result<()> {
         print_build("Cleaning Meson build...");
 
-        if Path::new("build").exists() {
-            std::fs::remove_dir_all("build").with_context(|| "Failed to remove build directory")?;
-        }
-
-        Ok(())
+        // Every profile's build
+        self.options.clean_build_dir(Path::new(&self.root))
     }
 }
@@ -3,9 +3,12 @@
 //! This module provides a unified trait-based interface for all supported
 //! build systems. It includes adapters for CMake, Make, Ninja, Meson, Bazel,
 //! XMake, and 10+ other build tools, enabling consistent build orchestration.
+//! Projects without a build file are compiled by Porters' own backend
+//! ([`native`]).
 
-use anyhow::{Result, anyhow};
-use std::path::Path;
+use anyhow::{Context, Result, anyhow, bail};
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
 
 pub mod autotools;
 pub mod bazel;
@@ -13,18 +16,414 @@ pub mod buck2;
 pub mod cmake;
 pub mod conan;
 pub mod custom;
+pub mod diagnostics;
 pub mod make;
 pub mod meson;
+pub mod native;
 pub mod ninja;
 pub mod premake;
+pub mod profile;
 pub mod qmake;
 pub mod scons;
 pub mod vcpkg;
 pub mod xmake;
 
-use crate::config::PortersConfig;
+use crate::config::{BuildConfig, BuildFlags, PortersConfig};
 use crate::deps::ResolvedDependency;
+use crate::deps::features::feature_define;
 use crate::scan::ProjectSources;
+use diagnostics::Diagnostics;
+use profile::Profile;
+
+/// Settings shared by every build system adapter
+#[derive(Debug, Clone)]
+pub struct BuildOptions {
+    /// Features activated for the project
+    pub features: Vec<String>,
+    /// Preprocessor defines for the project's and dependencies' features
+    pub defines: Vec<String>,
+    /// Compiler and linker flags from `[build.flags]`
+    pub flags: BuildFlags,
+    /// Include directories: `[build] include`, then the dependencies'
+    pub include_dirs: Vec<PathBuf>,
+    /// Library directories: `[build.linking] library_paths`, then the dependencies'
+    pub library_dirs: Vec<PathBuf>,
+    /// Libraries to link, by name (`m`) or path (`lib/libfoo.a`)
+    pub libraries: Vec<String>,
+    /// macOS frameworks to link
+    pub frameworks: Vec<String>,
+    /// Profile to build with
+    pub profile: Profile,
+    /// Every profile's name, to find their directories when cleaning
+    pub profiles: Vec<String>,
+    /// Directory builds go into, one subdirectory per profile
+    pub build_dir: PathBuf,
+    /// Directory for Porters' own intermediate files
+    pub cache_dir: PathBuf,
+    /// Streams tool output and collects compiler diagnostics
+    pub diagnostics: Diagnostics,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        Self {
+            features: vec![],
+            defines: vec![],
+            flags: BuildFlags::default(),
+            include_dirs: vec![],
+            library_dirs: vec![],
+            libraries: vec![],
+            frameworks: vec![],
+            profile: Profile::default(),
+            profiles: Profile::names(&Default::default()),
+            build_dir: PathBuf::from("build"),
+            cache_dir: PathBuf::from(".porters"),
+            diagnostics: Diagnostics::default(),
+        }
+    }
+}
+
+impl BuildOptions {
+    /// Collect the activated features of the project and its dependencies,
+    /// along with the dependencies' include and library directories
+    pub fn with_features(features: Vec<String>, deps: &[ResolvedDependency]) -> Self {
+        let mut defines: Vec<String> = features.iter().map(|f| feature_define(None, f)).collect();
+        for dep in deps {
+            defines.extend(
+                dep.features
+                    .iter()
+                    .map(|f| feature_define(Some(&dep.name), f)),
+            );
+        }
+
+        let mut options = Self {
+            features,
+            defines,
+            ..Self::default()
+        };
+        for dep in deps {
+            options.add_include_dirs(dep.include_paths.iter().cloned());
+            options.add_library_dirs(dep.lib_paths.iter().cloned());
+        }
+        options
+    }
+
+    /// Add porters.toml's flags, include directories and link settings,
+    /// resolving relative paths against the project `root`
+    ///
+    /// The project's own directories come before the dependencies'.
+    pub fn with_build_config(mut self, root: &Path, build: &BuildConfig) -> Self {
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+
+        self.flags = build.flags.clone();
+        // Absolute directories stay as they are, for out-of-tree builds
+        self.build_dir = root.join(build.build_dir.as_deref().unwrap_or("build"));
+        self.cache_dir = root.join(build.cache_dir.as_deref().unwrap_or(".porters"));
+        let include_dirs = std::mem::take(&mut self.include_dirs);
+        self.add_include_dirs(build.include.iter().map(|dir| root.join(dir)));
+        self.add_include_dirs(include_dirs);
+        let library_dirs = std::mem::take(&mut self.library_dirs);
+        self.add_library_dirs(build.linking.library_paths.iter().map(|dir| root.join(dir)));
+        self.add_library_dirs(library_dirs);
+
+        // Library files are linked by path, anything else by name
+        self.libraries = build
+            .linking
+            .libraries
+            .iter()
+            .map(|library| {
+                if library.contains(['/', '\\']) || Path::new(library).extension().is_some() {
+                    root.join(library).display().to_string()
+                } else {
+                    library.clone()
+                }
+            })
+            .collect();
+        self.frameworks = build.linking.frameworks.clone();
+        self
+    }
+
+    /// Build with `profile`
+    pub fn with_profile(mut self, profile: Profile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Stream output and collect diagnostics through `diagnostics`
+    pub fn with_diagnostics(mut self, diagnostics: Diagnostics) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+
+    /// Where the selected profile builds to (`build/debug`)
+    pub fn profile_dir(&self) -> PathBuf {
+        self.build_dir.join(&self.profile.name)
+    }
+
+    /// Remove every profile's build directory, also under each
+    /// cross-compilation target's directory
+    pub fn clean_build_dir(&self, root: &Path) -> Result<()> {
+        for target in crate::cross_compile::Target::all() {
+            self.remove_profile_dirs(&self.build_dir.join(target.triple()), root)?;
+        }
+        self.remove_profile_dirs(&self.build_dir, root)
+    }
+
+    /// Remove each profile's directory under `dir` (`build/debug`,
+    /// `build/release`, ...), then `dir` itself if nothing else is left in it
+    ///
+    /// `dir` comes from porters.toml, so it is refused when it is the
+    /// project `root` or one of its ancestors, and files Porters didn't put
+    /// there are kept.
+    pub fn remove_profile_dirs(&self, dir: &Path, root: &Path) -> Result<()> {
+        // Nothing was built there
+        let Ok(dir) = dir.canonicalize() else {
+            return Ok(());
+        };
+        let root = root
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve {}", root.display()))?;
+        if root.starts_with(&dir) {
+            bail!(
+                "Refusing to clean {}: it contains the project",
+                dir.display()
+            );
+        }
+
+        for profile in &self.profiles {
+            // A profile name is a single directory name
+            if !matches!(
+                Path::new(profile).components().collect::<Vec<_>>()[..],
+                [Component::Normal(_)]
+            ) {
+                continue;
+            }
+            let profile_dir = dir.join(profile);
+            if profile_dir.is_dir() {
+                std::fs::remove_dir_all(&profile_dir)
+                    .with_context(|| format!("Failed to remove {}", profile_dir.display()))?;
+            }
+        }
+        std::fs::remove_dir(&dir).ok();
+        Ok(())
+    }
+
+    fn add_include_dirs(&mut self, dirs: impl IntoIterator<Item = PathBuf>) {
+        for dir in dirs {
+            if !self.include_dirs.contains(&dir) {
+                self.include_dirs.push(dir);
+            }
+        }
+    }
+
+    fn add_library_dirs(&mut self, dirs: impl IntoIterator<Item = PathBuf>) {
+        for dir in dirs {
+            if !self.library_dirs.contains(&dir) {
+                self.library_dirs.push(dir);
+            }
+        }
+    }
+
+    /// Preprocessor flags: `-D` for feature, `[build.flags]` and profile
+    /// defines, then `-I` for the include directories
+    pub fn preprocessor_flags(&self) -> Vec<String> {
+        self.defines
+            .iter()
+            .chain(&self.flags.defines)
+            .chain(&self.profile.flags.defines)
+            .map(|define| format!("-D{}", define))
+            .chain(
+                self.include_dirs
+                    .iter()
+                    .map(|dir| format!("-I{}", dir.display())),
+            )
+            .collect()
+    }
+
+    /// Everything a C source is compiled with, except the profile's code
+    /// generation flags ([`Profile::compile_flags`])
+    pub fn c_flags(&self) -> Vec<String> {
+        let mut flags = self.preprocessor_flags();
+        flags.extend(self.flags.cflags.iter().cloned());
+        flags.extend(self.profile.flags.cflags.iter().cloned());
+        flags
+    }
+
+    /// Everything a C++ source is compiled with, except the profile's code
+    /// generation flags
+    pub fn cxx_flags(&self) -> Vec<String> {
+        let mut flags = self.preprocessor_flags();
+        flags.extend(self.flags.cxxflags.iter().cloned());
+        flags.extend(self.profile.flags.cxxflags.iter().cloned());
+        flags
+    }
+
+    /// Linker flags: `[build.flags]` and profile `ldflags`, then `-L` for
+    /// the library directories
+    pub fn link_flags(&self) -> Vec<String> {
+        self.flags
+            .ldflags
+            .iter()
+            .chain(&self.profile.flags.ldflags)
+            .cloned()
+            .chain(
+                self.library_dirs
+                    .iter()
+                    .map(|dir| format!("-L{}", dir.display())),
+            )
+            .collect()
+    }
+
+    /// Libraries to link: `-lname`, library files, and frameworks on macOS
+    pub fn link_libraries(&self) -> Vec<String> {
+        let mut libraries: Vec<String> = self
+            .libraries
+            .iter()
+            .map(|library| {
+                if Path::new(library).is_absolute() {
+                    library.clone()
+                } else {
+                    format!("-l{}", library)
+                }
+            })
+            .collect();
+        if cfg!(target_os = "macos") {
+            for framework in &self.frameworks {
+                libraries.extend(["-framework".to_string(), framework.clone()]);
+            }
+        }
+        libraries
+    }
+
+    /// CMake cache options for the activated features (`-DPORTERS_FEATURE_X=ON`),
+    /// the profile and the compiler and linker settings
+    ///
+    /// The profile maps to `CMAKE_BUILD_TYPE` and
+    /// `CMAKE_INTERPROCEDURAL_OPTIMIZATION`. The settings go into
+    /// `PORTERS_C_FLAGS`, `PORTERS_CXX_FLAGS`, `PORTERS_LINK_FLAGS` and
+    /// `PORTERS_LINK_LIBRARIES`, which the dependency script applies to every
+    /// target (see [`cmake::dependency_script`]).
+    pub fn cmake_options(&self) -> Vec<String> {
+        let mut options: Vec<String> = self.defines.iter().map(|d| format!("-D{}=ON", d)).collect();
+        options.push(format!("-DPORTERS_FEATURES={}", self.features.join(";")));
+        options.extend(
+            self.cmake_cache()
+                .into_iter()
+                .map(|(name, value)| format!("-D{}={}", name, value)),
+        );
+        options
+    }
+
+    /// Cache variables holding the profile and the compiler and linker
+    /// settings for CMake
+    pub fn cmake_cache(&self) -> Vec<(&'static str, String)> {
+        let mut libraries = self.libraries.clone();
+        if cfg!(target_os = "macos") {
+            libraries.extend(self.frameworks.iter().map(|f| format!("-framework {}", f)));
+        }
+        let sanitizers = self.profile.sanitizer_compile_flags();
+        let with_sanitizers = |mut flags: Vec<String>| {
+            flags.extend(sanitizers.iter().cloned());
+            flags.join(";")
+        };
+        let mut link_flags = self.link_flags();
+        link_flags.extend(self.profile.sanitizer_link_flags());
+
+        vec![
+            (
+                "CMAKE_BUILD_TYPE",
+                self.profile.cmake_build_type().to_string(),
+            ),
+            (
+                "CMAKE_INTERPROCEDURAL_OPTIMIZATION",
+                if self.profile.lto { "ON" } else { "OFF" }.to_string(),
+            ),
+            ("PORTERS_C_FLAGS", with_sanitizers(self.c_flags())),
+            ("PORTERS_CXX_FLAGS", with_sanitizers(self.cxx_flags())),
+            ("PORTERS_LINK_FLAGS", link_flags.join(";")),
+            ("PORTERS_LINK_LIBRARIES", libraries.join(";")),
+        ]
+    }
+
+    /// Every compiler and linker setting, to tell when a configured build is
+    /// out of date
+    pub fn fingerprint(&self) -> String {
+        [
+            self.profile.compile_flags(),
+            self.profile.link_flags(),
+            self.c_flags(),
+            self.cxx_flags(),
+            self.link_flags(),
+            self.link_libraries(),
+        ]
+        .iter()
+        .map(|flags| flags.join(" "))
+        .collect::<Vec<_>>()
+        .join("\n")
+    }
+
+    /// Expose the activated features to a build tool that gets the compiler
+    /// and linker settings some other way
+    pub fn apply_feature_env(&self, cmd: &mut Command) {
+        cmd.env("PORTERS_FEATURES", self.features.join(","));
+    }
+
+    /// Expose the activated features and porters.toml's build settings to a
+    /// build tool through the environment
+    ///
+    /// Sets `PORTERS_FEATURES` and appends the compiler and linker flags,
+    /// including the profile's `-O`, `-g`, `-flto` and `-fsanitize`, to the
+    /// standard `CFLAGS`, `CXXFLAGS`, `LDFLAGS` and `LIBS` variables,
+    /// which Make, Autotools and most generated build files pick up. Include
+    /// and library directories also go into `CPATH` and `LIBRARY_PATH`, which
+    /// GCC and Clang read even when a build file overrides `CFLAGS`.
+    pub fn apply_env(&self, cmd: &mut Command) {
+        self.apply_feature_env(cmd);
+
+        let codegen = self.profile.compile_flags();
+        for (var, flags) in [
+            ("CFLAGS", [self.c_flags(), codegen.clone()].concat()),
+            ("CXXFLAGS", [self.cxx_flags(), codegen].concat()),
+            (
+                "LDFLAGS",
+                [self.link_flags(), self.profile.link_flags()].concat(),
+            ),
+            ("LIBS", self.link_libraries()),
+        ] {
+            if !flags.is_empty() {
+                cmd.env(var, with_env_flags(var, &flags));
+            }
+        }
+
+        for (var, dirs) in [
+            ("CPATH", &self.include_dirs),
+            ("LIBRARY_PATH", &self.library_dirs),
+        ] {
+            if dirs.is_empty() {
+                continue;
+            }
+            let existing = std::env::var_os(var).unwrap_or_default();
+            let paths = dirs.iter().cloned().chain(
+                std::env::split_paths(&existing).filter(|path| !path.as_os_str().is_empty()),
+            );
+            if let Ok(value) = std::env::join_paths(paths) {
+                cmd.env(var, value);
+            }
+        }
+    }
+}
+
+/// `flags` appended to the value of the environment variable `var`
+fn with_env_flags(var: &str, flags: &[String]) -> String {
+    match std::env::var(var) {
+        Ok(existing) if !existing.trim().is_empty() => {
+            format!("{} {}", existing.trim(), flags.join(" "))
+                .trim_end()
+                .to_string()
+        }
+        _ => flags.join(" "),
+    }
+}
 
 /// Trait that all build system adapters must implement
 pub trait BuildSystem {
@@ -57,34 +456,50 @@ pub trait BuildSystem {
     fn clean(&self) -> Result<()>;
 }
 
-/// Detect which build system to use
+/// Detect which build system to use, building with the default profile
 pub fn detect_build_system(root: &str, config: &PortersConfig) -> Result<Box<dyn BuildSystem>> {
+    let profile = Profile::resolve(profile::DEFAULT_PROFILE, &config.profile)?;
+    detect_build_system_with(root, config, BuildOptions::default().with_profile(profile))
+}
+
+/// Detect which build system to use, configured with `options`
+pub fn detect_build_system_with(
+    root: &str,
+    config: &PortersConfig,
+    options: BuildOptions,
+) -> Result<Box<dyn BuildSystem>> {
     let root_path = Path::new(root);
+    let mut options = options.with_build_config(root_path, &config.build);
+    options.profiles = Profile::names(&config.profile);
 
     // Check if custom build is configured
     if let Some(ref custom_build) = config.build.custom {
         return Ok(Box::new(custom::CustomBuildSystem::new(
             root,
             custom_build.clone(),
+            options,
         )));
     }
 
     // Check if build system is explicitly specified
     if let Some(ref system) = config.build.system {
         return match system.as_str() {
-            "cmake" => Ok(Box::new(cmake::CMakeBuildSystem::new(root))),
-            "xmake" => Ok(Box::new(xmake::XMakeBuildSystem::new(root))),
-            "meson" => Ok(Box::new(meson::MesonBuildSystem::new(root))),
-            "make" => Ok(Box::new(make::MakeBuildSystem::new(root))),
-            "ninja" => Ok(Box::new(ninja::NinjaBuildSystem::new(root))),
-            "autotools" => Ok(Box::new(autotools::AutotoolsBuildSystem::new(root))),
-            "scons" => Ok(Box::new(scons::SConsBuildSystem::new(root))),
-            "bazel" => Ok(Box::new(bazel::BazelBuildSystem::new(root))),
-            "buck2" => Ok(Box::new(buck2::Buck2BuildSystem::new(root))),
-            "premake" => Ok(Box::new(premake::PremakeBuildSystem::new(root))),
-            "qmake" => Ok(Box::new(qmake::QMakeBuildSystem::new(root))),
-            "conan" => Ok(Box::new(conan::ConanBuildSystem::new(root))),
-            "vcpkg" => Ok(Box::new(vcpkg::VcpkgBuildSystem::new(root))),
+            "cmake" => Ok(Box::new(cmake::CMakeBuildSystem::new(root, options))),
+            "xmake" => Ok(Box::new(xmake::XMakeBuildSystem::new(root, options))),
+            "meson" => Ok(Box::new(meson::MesonBuildSystem::new(root, options))),
+            "make" => Ok(Box::new(make::MakeBuildSystem::new(root, options))),
+            "ninja" => Ok(Box::new(ninja::NinjaBuildSystem::new(root, options))),
+            "autotools" => Ok(Box::new(autotools::AutotoolsBuildSystem::new(root, options))),
+            "scons" => Ok(Box::new(scons::SConsBuildSystem::new(root, options))),
+            "bazel" => Ok(Box::new(bazel::BazelBuildSystem::new(root, options))),
+            "buck2" => Ok(Box::new(buck2::Buck2BuildSystem::new(root, options))),
+            "premake" => Ok(Box::new(premake::PremakeBuildSystem::new(root, options))),
+            "qmake" => Ok(Box::new(qmake::QMakeBuildSystem::new(root, options))),
+            "conan" => Ok(Box::new(conan::ConanBuildSystem::new(root, options))),
+            "vcpkg" => Ok(Box::new(vcpkg::VcpkgBuildSystem::new(root, options))),
+            "porters" => Ok(Box::new(native::NativeBuildSystem::new(
+                root, config, options,
+            ))),
             _ => Err(anyhow!("Unknown build system: {}", system)),
         };
     }
@@ -93,59 +508,185 @@ pub fn detect_build_system(root: &str, config: &PortersConfig) -> Result<Box<dyn
 
     // Package managers first (they might wrap other build systems)
     if conan::ConanBuildSystem::detect(root_path) {
-        return Ok(Box::new(conan::ConanBuildSystem::new(root)));
+        return Ok(Box::new(conan::ConanBuildSystem::new(root, options)));
     }
 
     if vcpkg::VcpkgBuildSystem::detect(root_path) {
-        return Ok(Box::new(vcpkg::VcpkgBuildSystem::new(root)));
+        return Ok(Box::new(vcpkg::VcpkgBuildSystem::new(root, options)));
     }
 
     // Modern build systems
     if bazel::BazelBuildSystem::detect(root_path) {
-        return Ok(Box::new(bazel::BazelBuildSystem::new(root)));
+        return Ok(Box::new(bazel::BazelBuildSystem::new(root, options)));
     }
 
     if buck2::Buck2BuildSystem::detect(root_path) {
-        return Ok(Box::new(buck2::Buck2BuildSystem::new(root)));
+        return Ok(Box::new(buck2::Buck2BuildSystem::new(root, options)));
     }
 
     if cmake::CMakeBuildSystem::detect(root_path) {
-        return Ok(Box::new(cmake::CMakeBuildSystem::new(root)));
+        return Ok(Box::new(cmake::CMakeBuildSystem::new(root, options)));
     }
 
     if xmake::XMakeBuildSystem::detect(root_path) {
-        return Ok(Box::new(xmake::XMakeBuildSystem::new(root)));
+        return Ok(Box::new(xmake::XMakeBuildSystem::new(root, options)));
     }
 
     if meson::MesonBuildSystem::detect(root_path) {
-        return Ok(Box::new(meson::MesonBuildSystem::new(root)));
+        return Ok(Box::new(meson::MesonBuildSystem::new(root, options)));
     }
 
     if premake::PremakeBuildSystem::detect(root_path) {
-        return Ok(Box::new(premake::PremakeBuildSystem::new(root)));
+        return Ok(Box::new(premake::PremakeBuildSystem::new(root, options)));
     }
 
     if qmake::QMakeBuildSystem::detect(root_path) {
-        return Ok(Box::new(qmake::QMakeBuildSystem::new(root)));
+        return Ok(Box::new(qmake::QMakeBuildSystem::new(root, options)));
     }
 
     // Traditional build systems
     if ninja::NinjaBuildSystem::detect(root_path) {
-        return Ok(Box::new(ninja::NinjaBuildSystem::new(root)));
+        return Ok(Box::new(ninja::NinjaBuildSystem::new(root, options)));
     }
 
     if autotools::AutotoolsBuildSystem::detect(root_path) {
-        return Ok(Box::new(autotools::AutotoolsBuildSystem::new(root)));
+        return Ok(Box::new(autotools::AutotoolsBuildSystem::new(root, options)));
     }
 
     if scons::SConsBuildSystem::detect(root_path) {
-        return Ok(Box::new(scons::SConsBuildSystem::new(root)));
+        return Ok(Box::new(scons::SConsBuildSystem::new(root, options)));
     }
 
     if make::MakeBuildSystem::detect(root_path) {
-        return Ok(Box::new(make::MakeBuildSystem::new(root)));
+        return Ok(Box::new(make::MakeBuildSystem::new(root, options)));
     }
 
-    // Default to CMake
-    Ok(Box::new(cmake::CMakeBuildSystem::new(root)))
+    // No build file: compile the sources directly
+    Ok(Box::new(native::NativeBuildSystem::new(
+        root, config, options,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_config_options() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        let config: PortersConfig = toml::from_str(
+            r#"
+[project]
+name = "app"
+version = "0.1.0"
+
+[build]
+include = ["include"]
+
+[build.flags]
+cflags = ["-std=c11"]
+cxxflags = ["-std=c++17"]
+ldflags = ["-pthread"]
+defines = ["USE_X"]
+
+[build.linking]
+libraries = ["m", "vendor/libfoo.a"]
+library_paths = ["lib"]
+"#,
+        )
+        .unwrap();
+
+        let mut options = BuildOptions {
+            defines: vec!["PORTERS_FEATURE_SIMD".to_string()],
+            include_dirs: vec![PathBuf::from("/deps/zlib/include")],
+            library_dirs: vec![PathBuf::from("/deps/zlib/lib")],
+            ..BuildOptions::default()
+        };
+        options = options.with_build_config(&root, &config.build);
+
+        let include = format!("-I{}", root.join("include").display());
+        assert_eq!(
+            options.c_flags(),
+            vec![
+                "-DPORTERS_FEATURE_SIMD",
+                "-DUSE_X",
+                &include,
+                "-I/deps/zlib/include",
+                "-std=c11"
+            ]
+        );
+        assert_eq!(options.cxx_flags().last().unwrap(), "-std=c++17");
+        assert_eq!(
+            options.link_flags(),
+            vec![
+                "-pthread".to_string(),
+                format!("-L{}", root.join("lib").display()),
+                "-L/deps/zlib/lib".to_string(),
+            ]
+        );
+        assert_eq!(
+            options.link_libraries(),
+            vec![
+                "-lm".to_string(),
+                root.join("vendor/libfoo.a").display().to_string()
+            ]
+        );
+
+        // Applying the settings again changes nothing
+        let again = options.clone().with_build_config(&root, &config.build);
+        assert_eq!(again.fingerprint(), options.fingerprint());
+
+        assert_eq!(options.profile_dir(), root.join("build").join("debug"));
+        assert_eq!(options.cache_dir, root.join(".porters"));
+
+        // Absolute directories build out of tree
+        let out = tempfile::TempDir::new().unwrap();
+        let build = BuildConfig {
+            build_dir: Some(out.path().join("app").display().to_string()),
+            cache_dir: Some("cache".to_string()),
+            ..config.build.clone()
+        };
+        let options = BuildOptions::default().with_build_config(&root, &build);
+        assert_eq!(options.build_dir, out.path().join("app"));
+        assert_eq!(options.cache_dir, root.join("cache"));
+    }
+
+    #[test]
+    fn test_clean_build_dir() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path().join("app");
+        let build_dir = root.join("out");
+        for dir in [
+            "debug",
+            "release",
+            "x86_64-unknown-linux-gnu/debug",
+            "notes",
+        ] {
+            std::fs::create_dir_all(build_dir.join(dir)).unwrap();
+        }
+
+        let mut options = BuildOptions {
+            build_dir: build_dir.clone(),
+            ..BuildOptions::default()
+        };
+        options.clean_build_dir(&root).unwrap();
+        assert!(!build_dir.join("debug").exists());
+        assert!(!build_dir.join("release").exists());
+        assert!(!build_dir.join("x86_64-unknown-linux-gnu").exists());
+        // Only what Porters builds is removed
+        assert!(build_dir.join("notes").exists());
+
+        // The project itself, or a directory above it, is never removed
+        std::fs::create_dir_all(root.join("debug")).unwrap();
+        for dir in [
+            root.clone(),
+            temp.path().to_path_buf(),
+            build_dir.join(".."),
+        ] {
+            options.build_dir = dir;
+            assert!(options.clean_build_dir(&root).is_err());
+        }
+        assert!(root.join("debug").exists());
+    }
 }
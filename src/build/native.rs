@@ -0,0 +1,905 @@
+//! Built-in compiler driver
+//!
+//! Projects without a build file are built by Porters itself: every source
+//! file found by the project scan is compiled with the detected GCC or Clang
+//! into `.porters/obj/<profile>`, and the objects are linked into an executable, or a
+//! static or shared library for `project-type = "library"`.
+//!
+//! Compilers write `-MMD` depfiles next to the objects, so a source is only
+//! compiled again when it, a header it includes or its compile command
+//! changed. Sources compile in parallel, one job per CPU unless `-j N` is
+//! passed to `porters build`.
+
+use anyhow::{Context, Result, anyhow, bail};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::SystemTime;
+
+use super::diagnostics::Diagnostics;
+use super::{BuildOptions, BuildSystem};
+use crate::config::{LibraryType, PortersConfig, ProjectType};
+use crate::deps::ResolvedDependency;
+use crate::scan::ProjectSources;
+use crate::util::pretty::*;
+
+/// Directories whose sources aren't part of the project's own target
+const SKIPPED_DIRS: &[&str] = &[
+    "ports",
+    "tests",
+    "test",
+    "examples",
+    "benches",
+    "benchmarks",
+];
+
+/// Directories holding test programs, one per source file
+const TEST_DIRS: &[&str] = &["tests", "test"];
+
+/// Porters' own build backend
+pub struct NativeBuildSystem {
+    root: PathBuf,
+    options: BuildOptions,
+    library_type: LibraryType,
+    project_type: ProjectType,
+    /// Base name of the executable or library
+    output_name: String,
+    entry_point: Option<String>,
+    /// Where objects and depfiles go
+    obj_dir: PathBuf,
+    /// Where the executable or library goes
+    out_dir: PathBuf,
+}
+
+/// C and C++ compiler commands, program first
+#[derive(Debug, Clone)]
+struct Compilers {
+    c: Vec<String>,
+    cxx: Vec<String>,
+}
+
+/// Compilation of one source file
+#[derive(Debug, Clone)]
+struct CompileJob {
+    source: PathBuf,
+    object: PathBuf,
+    depfile: PathBuf,
+    command: Vec<String>,
+}
+
+impl NativeBuildSystem {
+    pub fn new(root: &str, config: &PortersConfig, options: BuildOptions) -> Self {
+        let root = Path::new(root)
+            .canonicalize()
+            .unwrap_or_else(|_| PathBuf::from(root));
+        let build = &config.build;
+        let output_name = build
+            .executable_name
+            .clone()
+            .filter(|_| config.project.project_type == ProjectType::Application)
+            .or_else(|| build.output_name.clone())
+            .unwrap_or_else(|| config.project.name.clone());
+        // Objects and outputs are kept apart per profile
+        let obj_dir = options.cache_dir.join("obj").join(&options.profile.name);
+        let out_dir = options.profile_dir();
+
+        Self {
+            root,
+            options,
+            project_type: config.project.project_type.clone(),
+            entry_point: config.project.entry_point.clone(),
+            output_name,
+            obj_dir,
+            out_dir,
+            library_type: build.library_type,
+        }
+    }
+
+    /// The executable or library this project builds
+    pub fn output_path(&self) -> PathBuf {
+        let file_name = match (&self.project_type, self.library_type) {
+            (ProjectType::Application, _) => {
+                format!("{}{}", self.output_name, std::env::consts::EXE_SUFFIX)
+            }
+            (ProjectType::Library, LibraryType::Static) if cfg!(windows) => {
+                format!("{}.lib", self.output_name)
+            }
+            (ProjectType::Library, LibraryType::Static) => {
+                format!("lib{}.a", self.output_name)
+            }
+            (ProjectType::Library, LibraryType::Shared) => format!(
+                "{}{}{}",
+                std::env::consts::DLL_PREFIX,
+                self.output_name,
+                std::env::consts::DLL_SUFFIX
+            ),
+        };
+        self.out_dir.join(file_name)
+    }
+
+    fn shared(&self) -> bool {
+        self.project_type == ProjectType::Library && self.library_type == LibraryType::Shared
+    }
+
+    /// Sources of the project's own target
+    fn project_sources(&self, sources: &ProjectSources) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = sources
+            .source_files
+            .iter()
+            .filter(|path| {
+                let Ok(relative) = path.strip_prefix(&self.root) else {
+                    return false;
+                };
+                let first = relative
+                    .components()
+                    .next()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .unwrap_or_default();
+                !SKIPPED_DIRS.contains(&first.as_ref())
+                    && !relative
+                        .components()
+                        .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+            })
+            .cloned()
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// Test programs under `tests/` or `test/`
+    fn test_sources(&self, sources: &ProjectSources) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = sources
+            .source_files
+            .iter()
+            .filter(|path| {
+                path.strip_prefix(&self.root).is_ok_and(|relative| {
+                    relative
+                        .components()
+                        .next()
+                        .is_some_and(|first| TEST_DIRS.iter().any(|dir| first.as_os_str() == *dir))
+                })
+            })
+            .cloned()
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// Flags every source is compiled with besides the porters.toml
+    /// settings: the include directories found by the scan, and `-fPIC` for
+    /// shared libraries
+    fn compile_flags(&self, sources: &ProjectSources) -> Vec<String> {
+        let mut scanned = sources.include_paths.clone();
+        scanned.sort();
+        let mut flags: Vec<String> = scanned
+            .iter()
+            .filter(|dir| !self.options.include_dirs.contains(dir))
+            .map(|dir| format!("-I{}", dir.display()))
+            .collect();
+
+        if self.shared() && !cfg!(windows) {
+            flags.push("-fPIC".to_string());
+        }
+        flags
+    }
+
+    /// Compile command for one source, writing `object` and its depfile
+    fn compile_job(&self, compilers: &Compilers, flags: &[String], source: &Path) -> CompileJob {
+        let relative = source.strip_prefix(&self.root).unwrap_or(source);
+        let object = self.obj_dir.join(format!("{}.o", relative.display()));
+        let depfile = object.with_extension("d");
+
+        let (mut command, language_flags) = if is_c_source(source) {
+            (compilers.c.clone(), self.options.c_flags())
+        } else {
+            (compilers.cxx.clone(), self.options.cxx_flags())
+        };
+        command.extend(language_flags);
+        command.extend(self.options.profile.compile_flags());
+        command.extend(flags.iter().cloned());
+        command.extend([
+            "-MMD".to_string(),
+            "-MP".to_string(),
+            "-MF".to_string(),
+            depfile.display().to_string(),
+            "-c".to_string(),
+            source.display().to_string(),
+            "-o".to_string(),
+            object.display().to_string(),
+        ]);
+
+        CompileJob {
+            source: source.to_path_buf(),
+            object,
+            depfile,
+            command,
+        }
+    }
+
+    /// Linker inputs after the objects: every library in the dependencies'
+    /// library directories, then the libraries from porters.toml
+    fn link_libraries(&self, deps: &[ResolvedDependency]) -> Vec<String> {
+        let mut args = Vec::new();
+
+        for dep in deps {
+            for path in &dep.lib_paths {
+                if !cfg!(windows) {
+                    args.push(format!("-Wl,-rpath,{}", path.display()));
+                }
+                args.extend(
+                    dependency_libraries(path)
+                        .into_iter()
+                        .map(|lib| format!("-l{}", lib)),
+                );
+            }
+        }
+        args.extend(self.options.link_libraries());
+        args
+    }
+
+    /// Command that links `objects` into `output`
+    fn link_command(
+        &self,
+        compilers: &Compilers,
+        has_cxx: bool,
+        objects: &[PathBuf],
+        output: &Path,
+        deps: &[ResolvedDependency],
+        library: bool,
+    ) -> Vec<String> {
+        let objects = objects.iter().map(|object| object.display().to_string());
+
+        if library && !self.shared() {
+            let mut command = vec![std::env::var("AR").unwrap_or_else(|_| "ar".to_string())];
+            command.push("rcs".to_string());
+            command.push(output.display().to_string());
+            command.extend(objects);
+            return command;
+        }
+
+        let mut command = if has_cxx {
+            compilers.cxx.clone()
+        } else {
+            compilers.c.clone()
+        };
+        if library {
+            command.push(if cfg!(target_os = "macos") {
+                "-dynamiclib".to_string()
+            } else {
+                "-shared".to_string()
+            });
+        }
+        command.extend(self.options.link_flags());
+        command.extend(self.options.profile.link_flags());
+        command.extend(objects);
+        command.extend(["-o".to_string(), output.display().to_string()]);
+        command.extend(self.link_libraries(deps));
+        command
+    }
+
+    /// Compile the project's sources, returning the objects and whether any
+    /// was compiled again
+    fn compile(
+        &self,
+        compilers: &Compilers,
+        files: &[PathBuf],
+        flags: &[String],
+        jobs: usize,
+    ) -> Result<(Vec<PathBuf>, bool)> {
+        let all: Vec<CompileJob> = files
+            .iter()
+            .map(|source| self.compile_job(compilers, flags, source))
+            .collect();
+        let objects = all.iter().map(|job| job.object.clone()).collect();
+
+        let stale: Vec<CompileJob> = all
+            .into_iter()
+            .filter(|job| job.needs_compile(&self.root))
+            .collect();
+        if stale.is_empty() {
+            return Ok((objects, false));
+        }
+
+        print_build(&format!(
+            "Compiling {} of {} sources...",
+            stale.len(),
+            files.len()
+        ));
+        run_parallel(&stale, jobs, &self.root, &self.options.diagnostics)?;
+        Ok((objects, true))
+    }
+
+    /// Link when an object or the link command changed
+    fn link(
+        &self,
+        command: &[String],
+        objects: &[PathBuf],
+        output: &Path,
+        compiled: bool,
+    ) -> Result<()> {
+        let command_file = self.obj_dir.join(format!(
+            "{}.cmd",
+            output.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        let command_line = command.join(" ");
+
+        let up_to_date = !compiled
+            && fs::read_to_string(&command_file).ok().as_deref() == Some(command_line.as_str())
+            && modified(output).is_some_and(|built| {
+                objects
+                    .iter()
+                    .all(|object| modified(object).is_some_and(|time| time <= built))
+            });
+        if up_to_date {
+            return Ok(());
+        }
+
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // `ar` adds to an existing archive, which would keep removed objects
+        if output
+            .extension()
+            .is_some_and(|ext| ext == "a" || ext == "lib")
+        {
+            fs::remove_file(output).ok();
+        }
+
+        print_build(&format!("Linking {}...", display_path(output, &self.root)));
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut command_in(command, &self.root)?)
+            .with_context(|| format!("Failed to run {}", command[0]))?;
+        if !status.success() {
+            print_error(&format!(
+                "Linking {} failed",
+                display_path(output, &self.root)
+            ));
+            return Err(anyhow!("Build failed"));
+        }
+        fs::write(&command_file, command_line)?;
+        Ok(())
+    }
+
+    /// Build the project, returning its objects
+    fn build_project(
+        &self,
+        sources: &ProjectSources,
+        deps: &[ResolvedDependency],
+        args: &[String],
+    ) -> Result<Vec<PathBuf>> {
+        let files = self.project_sources(sources);
+        if files.is_empty() {
+            bail!(
+                "No C/C++ sources found in {}; add some under src/ or add a build file",
+                self.root.display()
+            );
+        }
+
+        let compilers = detect_compilers()?;
+        let flags = self.compile_flags(sources);
+        let jobs = parallel_jobs(args);
+        let (objects, compiled) = self.compile(&compilers, &files, &flags, jobs)?;
+
+        let output = self.output_path();
+        let has_cxx = files.iter().any(|file| !is_c_source(file));
+        let command = self.link_command(
+            &compilers,
+            has_cxx,
+            &objects,
+            &output,
+            deps,
+            self.project_type == ProjectType::Library,
+        );
+        self.link(&command, &objects, &output, compiled)?;
+
+        print_success(&format!("Built {}", display_path(&output, &self.root)));
+        Ok(objects)
+    }
+
+    /// Whether an object holds the program's entry point (`main`)
+    fn is_entry_point(&self, source: &Path) -> bool {
+        let relative = source.strip_prefix(&self.root).unwrap_or(source);
+        let without_extension = relative.with_extension("");
+        source.file_stem().is_some_and(|stem| stem == "main")
+            || self
+                .entry_point
+                .as_deref()
+                .is_some_and(|entry| without_extension == Path::new(entry))
+    }
+}
+
+impl BuildSystem for NativeBuildSystem {
+    fn name(&self) -> &str {
+        "Porters"
+    }
+
+    fn detect(root: &Path) -> bool {
+        // Builds anything with sources, so it comes last
+        crate::scan::scan_project(root).is_ok_and(|sources| !sources.source_files.is_empty())
+    }
+
+    fn configure(&self, _sources: &ProjectSources, _deps: &[ResolvedDependency]) -> Result<()> {
+        // Nothing to configure; every build checks what changed
+        Ok(())
+    }
+
+    fn build(
+        &self,
+        sources: &ProjectSources,
+        deps: &[ResolvedDependency],
+        args: &[String],
+    ) -> Result<()> {
+        print_build("Building with the Porters compiler driver...");
+        self.build_project(sources, deps, args)?;
+        Ok(())
+    }
+
+    fn run(&self, args: &[String]) -> Result<()> {
+        if self.project_type == ProjectType::Library {
+            bail!("{} is a library; there is nothing to run", self.output_name);
+        }
+        let executable = self.output_path();
+        if !executable.exists() {
+            bail!(
+                "{} doesn't exist; run 'porters build' first",
+                executable.display()
+            );
+        }
+
+        print_info(&format!(
+            "🚀 Running {}",
+            display_path(&executable, &self.root)
+        ));
+        let status = Command::new(&executable)
+            .args(args)
+            .status()
+            .with_context(|| format!("Failed to run {}", executable.display()))?;
+        if !status.success() {
+            return Err(anyhow!("Execution failed with status: {}", status));
+        }
+        Ok(())
+    }
+
+    fn test(&self, sources: &ProjectSources, deps: &[ResolvedDependency]) -> Result<()> {
+        let tests = self.test_sources(sources);
+        if tests.is_empty() {
+            print_warning("No tests found in tests/");
+            return Ok(());
+        }
+
+        let files = self.project_sources(sources);
+        let objects = if files.is_empty() {
+            vec![]
+        } else {
+            self.build_project(sources, deps, &[])?
+        };
+        // Tests bring their own main
+        let library_objects: Vec<PathBuf> = files
+            .iter()
+            .zip(&objects)
+            .filter(|(source, _)| !self.is_entry_point(source))
+            .map(|(_, object)| object.clone())
+            .collect();
+
+        let compilers = detect_compilers()?;
+        let flags = self.compile_flags(sources);
+        let (test_objects, compiled) =
+            self.compile(&compilers, &tests, &flags, parallel_jobs(&[]))?;
+
+        print_build("Running tests...");
+        let mut failed = Vec::new();
+        for (source, object) in tests.iter().zip(&test_objects) {
+            let name = source.file_stem().unwrap_or_default().to_string_lossy();
+            let executable = self.out_dir.join("tests").join(format!(
+                "{}{}",
+                name,
+                std::env::consts::EXE_SUFFIX
+            ));
+            let mut inputs = library_objects.clone();
+            inputs.push(object.clone());
+            let has_cxx = files
+                .iter()
+                .chain(std::iter::once(source))
+                .any(|file| !is_c_source(file));
+            let command = self.link_command(&compilers, has_cxx, &inputs, &executable, deps, false);
+            self.link(&command, &inputs, &executable, compiled)?;
+
+            let status = Command::new(&executable)
+                .current_dir(&self.root)
+                .status()
+                .with_context(|| format!("Failed to run {}", executable.display()))?;
+            if status.success() {
+                print_success(&format!("{} passed", name));
+            } else {
+                print_error(&format!("{} failed ({})", name, status));
+                failed.push(name.to_string());
+            }
+        }
+
+        if !failed.is_empty() {
+            return Err(anyhow!(
+                "{} of {} tests failed: {}",
+                failed.len(),
+                tests.len(),
+                failed.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    fn clean(&self) -> Result<()> {
+        print_build("Cleaning Porters build...");
+        // Every profile's objects and outputs
+        let obj_root = self.obj_dir.parent().unwrap_or(&self.obj_dir);
+        self.options.remove_profile_dirs(obj_root, &self.root)?;
+        self.options.clean_build_dir(&self.root)
+    }
+}
+
+impl CompileJob {
+    fn command_file(&self) -> PathBuf {
+        self.object.with_extension("cmd")
+    }
+
+    /// Whether the object is missing or older than its source, a header it
+    /// includes or its compile command
+    fn needs_compile(&self, root: &Path) -> bool {
+        let Some(built) = modified(&self.object) else {
+            return true;
+        };
+        if fs::read_to_string(self.command_file()).ok().as_deref()
+            != Some(self.command.join(" ").as_str())
+        {
+            return true;
+        }
+        let Ok(depfile) = fs::read_to_string(&self.depfile) else {
+            return true;
+        };
+
+        std::iter::once(self.source.clone())
+            .chain(parse_depfile(&depfile))
+            .any(|input| modified(&root.join(input)).is_none_or(|time| time > built))
+    }
+
+    fn run(&self, root: &Path, diagnostics: &Diagnostics) -> Result<()> {
+        if let Some(parent) = self.object.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let source = display_path(&self.source, root);
+        print_build(&format!("Compiling {}", source));
+
+        let status = diagnostics
+            .run_job(&mut command_in(&self.command, root)?)
+            .with_context(|| format!("Failed to run {}", self.command[0]))?;
+        if !status.success() {
+            // A failed compile must not look up to date next time
+            fs::remove_file(&self.object).ok();
+            print_error(&format!("Failed to compile {}", source));
+            bail!("Failed to compile {}", source);
+        }
+        fs::write(self.command_file(), self.command.join(" "))?;
+        Ok(())
+    }
+}
+
+/// Run compile jobs on `jobs` threads, stopping at the first failure
+fn run_parallel(
+    compile_jobs: &[CompileJob],
+    jobs: usize,
+    root: &Path,
+    diagnostics: &Diagnostics,
+) -> Result<()> {
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let errors = Mutex::new(Vec::new());
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, compile_jobs.len().max(1)) {
+            scope.spawn(|| {
+                while !failed.load(Ordering::Relaxed) {
+                    let Some(job) = compile_jobs.get(next.fetch_add(1, Ordering::Relaxed)) else {
+                        break;
+                    };
+                    if let Err(err) = job.run(root, diagnostics) {
+                        failed.store(true, Ordering::Relaxed);
+                        errors.lock().unwrap().push(err);
+                    }
+                }
+            });
+        }
+    });
+
+    let errors = errors.into_inner().unwrap();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Build failed ({} sources failed to compile)",
+            errors.len()
+        ))
+    }
+}
+
+/// `command` as a process run in `root`
+fn command_in(command: &[String], root: &Path) -> Result<Command> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| anyhow!("Empty command"))?;
+    let mut cmd = Command::new(program);
+    cmd.args(args).current_dir(root);
+    Ok(cmd)
+}
+
+/// Find the C and C++ compilers: `CC`/`CXX`, else the first of the usual
+/// GCC and Clang commands that runs
+fn detect_compilers() -> Result<Compilers> {
+    let find = |var: &str, candidates: &[&str]| -> Option<Vec<String>> {
+        std::env::var(var)
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .into_iter()
+            .chain(candidates.iter().map(|c| c.to_string()))
+            .map(|command| {
+                command
+                    .split_whitespace()
+                    .map(String::from)
+                    .collect::<Vec<_>>()
+            })
+            .find(|command| {
+                Command::new(&command[0])
+                    .args(&command[1..])
+                    .arg("--version")
+                    .output()
+                    .is_ok_and(|output| output.status.success())
+            })
+    };
+
+    let cxx = find("CXX", &["c++", "g++", "clang++"]);
+    let c = find("CC", &["cc", "gcc", "clang"]);
+    match (c, cxx) {
+        (Some(c), Some(cxx)) => Ok(Compilers { c, cxx }),
+        // A C++ compiler driver compiles C as well
+        (None, Some(cxx)) => Ok(Compilers {
+            c: cxx.clone(),
+            cxx,
+        }),
+        (Some(c), None) => Ok(Compilers { cxx: c.clone(), c }),
+        (None, None) => Err(anyhow!(
+            "No C/C++ compiler found. Install GCC or Clang, or set CC and CXX"
+        )),
+    }
+}
+
+/// Number of parallel compile jobs: `-j N`, `-jN` or `--jobs N` in the
+/// build arguments, otherwise one per CPU
+fn parallel_jobs(args: &[String]) -> usize {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "-j" | "--jobs" => args.next().map(String::as_str),
+            _ => arg
+                .strip_prefix("--jobs=")
+                .or_else(|| arg.strip_prefix("-j")),
+        };
+        if let Some(jobs) = value
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|&j| j > 0)
+        {
+            return jobs;
+        }
+    }
+    num_cpus::get()
+}
+
+/// Prerequisites listed in a Make-style depfile (`main.o: main.c util.h`)
+fn parse_depfile(content: &str) -> Vec<PathBuf> {
+    let joined = content.replace("\\\r\n", " ").replace("\\\n", " ");
+    let mut prerequisites = Vec::new();
+
+    for line in joined.lines() {
+        // The target ends at the first ": "; paths may contain drive letters
+        let Some((_, rest)) = line.split_once(": ") else {
+            continue;
+        };
+
+        let mut current = String::new();
+        let mut chars = rest.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.peek() == Some(&' ') => {
+                    current.push(' ');
+                    chars.next();
+                }
+                '$' if chars.peek() == Some(&'$') => {
+                    current.push('$');
+                    chars.next();
+                }
+                c if c.is_whitespace() => {
+                    if !current.is_empty() {
+                        prerequisites.push(PathBuf::from(std::mem::take(&mut current)));
+                    }
+                }
+                c => current.push(c),
+            }
+        }
+        if !current.is_empty() {
+            prerequisites.push(PathBuf::from(current));
+        }
+    }
+
+    prerequisites.sort();
+    prerequisites.dedup();
+    prerequisites
+}
+
+/// Library names (for `-l`) of the libraries in a dependency's lib directory
+fn dependency_libraries(dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut libraries: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let name = file_name.strip_prefix("lib")?;
+            [".a", ".so", ".dylib"]
+                .iter()
+                .find_map(|suffix| name.strip_suffix(suffix))
+                .map(String::from)
+        })
+        .collect();
+    libraries.sort();
+    libraries.dedup();
+    libraries
+}
+
+fn is_c_source(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "c")
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// A path relative to the project root for messages
+fn display_path(path: &Path, root: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::diagnostics::MessageFormat;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_depfile() {
+        let depfile = "/p/.porters/obj/src/main.cpp.o: /p/src/main.cpp /p/include/my\\ lib.h \\\n  /p/src/util.h\n/p/include/my\\ lib.h:\n/p/src/util.h:\n";
+        assert_eq!(
+            parse_depfile(depfile),
+            vec![
+                PathBuf::from("/p/include/my lib.h"),
+                PathBuf::from("/p/src/main.cpp"),
+                PathBuf::from("/p/src/util.h"),
+            ]
+        );
+        assert_eq!(
+            parse_depfile("C:\\p\\main.o: C:\\p\\main.c\n"),
+            vec![PathBuf::from("C:\\p\\main.c")]
+        );
+    }
+
+    #[test]
+    fn test_parallel_jobs() {
+        let args = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert_eq!(parallel_jobs(&args(&["-j", "3"])), 3);
+        assert_eq!(parallel_jobs(&args(&["-j8"])), 8);
+        assert_eq!(parallel_jobs(&args(&["--jobs=2"])), 2);
+        assert_eq!(parallel_jobs(&args(&[])), num_cpus::get());
+    }
+
+    #[test]
+    #[ignore] // Requires a C++ compiler to be installed
+    fn test_incremental_build() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("include")).unwrap();
+        fs::write(root.join("include/answer.h"), "int answer();\n").unwrap();
+        fs::write(
+            root.join("src/answer.cpp"),
+            "#include \"answer.h\"\nint answer() { return ANSWER; }\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("src/main.cpp"),
+            "#include \"answer.h\"\nint main() { return answer() == 42 ? 0 : 1; }\n",
+        )
+        .unwrap();
+
+        let config: PortersConfig = toml::from_str(
+            "[project]\nname = \"app\"\nversion = \"0.1.0\"\n\n[build.flags]\ndefines = [\"ANSWER=42\"]\n",
+        )
+        .unwrap();
+        let options = BuildOptions::default().with_build_config(root, &config.build);
+        let backend = NativeBuildSystem::new(root.to_str().unwrap(), &config, options);
+        let sources = crate::scan::scan_project(root).unwrap();
+        let compilers = detect_compilers().unwrap();
+        let flags = backend.compile_flags(&sources);
+        let files = backend.project_sources(&sources);
+
+        backend.build(&sources, &[], &[]).unwrap();
+        assert!(backend.output_path().exists());
+        backend.run(&[]).unwrap();
+
+        // Nothing changed
+        let (_, compiled) = backend.compile(&compilers, &files, &flags, 2).unwrap();
+        assert!(!compiled);
+
+        // A header change recompiles the sources including it
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        fs::write(root.join("include/answer.h"), "int answer(); // changed\n").unwrap();
+        let stale: Vec<_> = files
+            .iter()
+            .map(|file| backend.compile_job(&compilers, &flags, file))
+            .filter(|job| job.needs_compile(root))
+            .collect();
+        assert_eq!(stale.len(), 2);
+    }
+
+    /// Collects what is written to it, for reading back in a test
+    #[derive(Clone, Default)]
+    struct Captured(std::sync::Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[ignore] // Requires a C compiler to be installed
+    fn test_failed_compile_reports_json_messages() {
+        let temp = TempDir::new().unwrap();
+        let root = temp.path();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/main.c"), "int main() { return missing; }\n").unwrap();
+
+        let config: PortersConfig =
+            toml::from_str("[project]\nname = \"app\"\nversion = \"0.1.0\"\n").unwrap();
+        let captured = Captured::default();
+        let mut options = BuildOptions::default().with_build_config(root, &config.build);
+        options.diagnostics = Diagnostics::with_output(MessageFormat::Json, captured.clone());
+        let backend = NativeBuildSystem::new(root.to_str().unwrap(), &config, options);
+        let sources = crate::scan::scan_project(root).unwrap();
+
+        assert!(backend.build(&sources, &[], &[]).is_err());
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let messages: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert!(
+            messages
+                .iter()
+                .any(|message| message["reason"] == "compiler-message"
+                    && message["severity"] == "error"
+                    && message["file"].as_str().unwrap().ends_with("main.c")
+                    && message["line"] == 1),
+            "{}",
+            output
+        );
+    }
+}
@@ -8,7 +8,7 @@ use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Command;
 
-use super::BuildSystem;
+use super::{BuildOptions, BuildSystem};
 use crate::deps::ResolvedDependency;
 use crate::scan::ProjectSources;
 use crate::util::pretty::*;
@@ -18,12 +18,14 @@ use crate::util::pretty::*;
 /// Handles projects with build.ninja files, executing fast incremental builds.
 pub struct NinjaBuildSystem {
     root: String,
+    options: BuildOptions,
 }
 
 impl NinjaBuildSystem {
-    pub fn new(root: &str) -> Self {
+    pub fn new(root: &str, options: BuildOptions) -> Self {
         Self {
             root: root.to_string(),
+            options,
         }
     }
 }
@@ -52,16 +54,16 @@ impl BuildSystem for NinjaBuildSystem {
         let mut cmd = Command::new("ninja");
         cmd.current_dir(&self.root);
         cmd.args(args);
+        self.options.apply_env(&mut cmd);
 
-        let output = cmd
-            .output()
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
             .with_context(|| "Failed to run ninja. Is Ninja installed?")?;
 
-        if !output.status.success() {
-            print_error(&format!(
-                "Ninja build failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        if !status.success() {
+            print_error("Ninja build failed");
             return Err(anyhow::anyhow!("Ninja build failed"));
         }
 
@@ -81,10 +83,15 @@ impl BuildSystem for NinjaBuildSystem {
         let mut cmd = Command::new("ninja");
         cmd.current_dir(&self.root);
         cmd.arg("test");
+        self.options.apply_env(&mut cmd);
 
-        let output = cmd.output().with_context(|| "Failed to run ninja test")?;
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to run ninja test")?;
 
-        if !output.status.success() {
+        if !status.success() {
             print_error("Tests failed");
             return Err(anyhow::anyhow!("Tests failed"));
         }
@@ -8,7 +8,7 @@ use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Command;
 
-use super::BuildSystem;
+use super::{BuildOptions, BuildSystem};
 use crate::deps::ResolvedDependency;
 use crate::scan::ProjectSources;
 use crate::util::pretty::*;
@@ -19,12 +19,14 @@ use crate::util::pretty::*;
 /// and executing builds through the target build system.
 pub struct PremakeBuildSystem {
     root: String,
+    options: BuildOptions,
 }
 
 impl PremakeBuildSystem {
-    pub fn new(root: &str) -> Self {
+    pub fn new(root: &str, options: BuildOptions) -> Self {
         Self {
             root: root.to_string(),
+            options,
         }
     }
 }
@@ -53,16 +55,16 @@ impl BuildSystem for PremakeBuildSystem {
         let mut cmd = Command::new("premake5");
         cmd.current_dir(&self.root);
         cmd.arg(action);
+        self.options.apply_env(&mut cmd);
 
-        let output = cmd
-            .output()
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
             .with_context(|| "Failed to run premake5. Is Premake installed?")?;
 
-        if !output.status.success() {
-            print_error(&format!(
-                "Premake generation failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        if !status.success() {
+            print_error("Premake generation failed");
             return Err(anyhow::anyhow!("Premake generation failed"));
         }
 
@@ -91,15 +93,17 @@ impl BuildSystem for PremakeBuildSystem {
         };
 
         cmd.current_dir(&self.root);
+        self.options.apply_env(&mut cmd);
         cmd.args(args);
 
-        let output = cmd.output().with_context(|| "Failed to build project")?;
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to build project")?;
 
-        if !output.status.success() {
-            print_error(&format!(
-                "Build failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        if !status.success() {
+            print_error("Build failed");
             return Err(anyhow::anyhow!("Build failed"));
         }
 
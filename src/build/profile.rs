@@ -0,0 +1,315 @@
+//! Build profiles
+//!
+//! A profile says how to compile: optimization level, debug info, LTO,
+//! sanitizers and extra flags. `debug` and `release` are built in, and
+//! porters.toml can change them or add its own under `[profile.<name>]`:
+//!
+//! ```toml
+//! [profile.asan]
+//! inherits = "debug"
+//! sanitize = ["address", "undefined"]
+//! ```
+//!
+//! Each build system adapter maps the profile to its own setting, such as
+//! `CMAKE_BUILD_TYPE` or Meson's `optimization`, and each profile builds
+//! into its own directory under the build directory.
+
+use anyhow::{Result, bail};
+use std::collections::BTreeMap;
+
+use crate::config::{BuildFlags, OptLevel, ProfileConfig};
+
+/// Profile used unless `--release` or `--profile` is given
+pub const DEFAULT_PROFILE: &str = "debug";
+
+/// Profile selected by `--release`
+pub const RELEASE_PROFILE: &str = "release";
+
+/// A resolved build profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub name: String,
+    pub opt_level: OptLevel,
+    pub debug: bool,
+    pub lto: bool,
+    pub sanitize: Vec<String>,
+    /// Extra flags and defines
+    pub flags: BuildFlags,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::builtin(DEFAULT_PROFILE).expect("debug is a built-in profile")
+    }
+}
+
+impl Profile {
+    /// The `debug` and `release` profiles as they are without porters.toml
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "debug" => Some(Self {
+                name: name.to_string(),
+                opt_level: OptLevel::O0,
+                debug: true,
+                lto: false,
+                sanitize: vec![],
+                flags: BuildFlags::default(),
+            }),
+            "release" => Some(Self {
+                name: name.to_string(),
+                opt_level: OptLevel::O3,
+                debug: false,
+                lto: false,
+                sanitize: vec![],
+                flags: BuildFlags {
+                    defines: vec!["NDEBUG".to_string()],
+                    ..BuildFlags::default()
+                },
+            }),
+            _ => None,
+        }
+    }
+
+    /// Resolve the profile `name` against porters.toml's `[profile]` tables
+    pub fn resolve(name: &str, profiles: &BTreeMap<String, ProfileConfig>) -> Result<Self> {
+        Self::resolve_chain(name, profiles, &mut Vec::new())
+    }
+
+    fn resolve_chain(
+        name: &str,
+        profiles: &BTreeMap<String, ProfileConfig>,
+        chain: &mut Vec<String>,
+    ) -> Result<Self> {
+        if chain.iter().any(|seen| seen == name) {
+            chain.push(name.to_string());
+            bail!("Profiles inherit from each other: {}", chain.join(" -> "));
+        }
+        chain.push(name.to_string());
+
+        let Some(config) = profiles.get(name) else {
+            return Self::builtin(name).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown profile `{}`; available profiles: {}",
+                    name,
+                    Self::names(profiles).join(", ")
+                )
+            });
+        };
+
+        let mut profile = match (&config.inherits, Self::builtin(name)) {
+            (Some(parent), _) => Self::resolve_chain(parent, profiles, chain)?,
+            (None, Some(builtin)) => builtin,
+            (None, None) => bail!(
+                "Profile `{}` needs `inherits = \"debug\"` or `inherits = \"release\"`",
+                name
+            ),
+        };
+
+        profile.name = name.to_string();
+        if let Some(opt_level) = config.opt_level {
+            profile.opt_level = opt_level;
+        }
+        if let Some(debug) = config.debug {
+            profile.debug = debug;
+        }
+        if let Some(lto) = config.lto {
+            profile.lto = lto;
+        }
+        if let Some(sanitize) = &config.sanitize {
+            profile.sanitize = sanitize.clone();
+        }
+        profile.flags.cflags.extend(config.cflags.iter().cloned());
+        profile
+            .flags
+            .cxxflags
+            .extend(config.cxxflags.iter().cloned());
+        profile.flags.ldflags.extend(config.ldflags.iter().cloned());
+        profile.flags.defines.extend(config.defines.iter().cloned());
+        Ok(profile)
+    }
+
+    /// Names of the built-in profiles and those porters.toml defines
+    pub fn names(profiles: &BTreeMap<String, ProfileConfig>) -> Vec<String> {
+        [DEFAULT_PROFILE, RELEASE_PROFILE]
+            .into_iter()
+            .chain(
+                profiles
+                    .keys()
+                    .map(String::as_str)
+                    .filter(|p| Self::builtin(p).is_none()),
+            )
+            .map(String::from)
+            .collect()
+    }
+
+    /// Name of the profile selected by `--release` and `--profile`
+    pub fn selected(release: bool, profile: Option<&str>) -> &str {
+        match profile {
+            Some(profile) => profile,
+            None if release => RELEASE_PROFILE,
+            None => DEFAULT_PROFILE,
+        }
+    }
+
+    fn sanitize_flag(&self) -> Option<String> {
+        (!self.sanitize.is_empty()).then(|| format!("-fsanitize={}", self.sanitize.join(",")))
+    }
+
+    /// Code generation flags for GCC and Clang: `-O`, `-g`, `-flto` and
+    /// `-fsanitize`
+    pub fn compile_flags(&self) -> Vec<String> {
+        let mut flags = vec![format!("-O{}", self.opt_level.as_str())];
+        if self.debug {
+            flags.push("-g".to_string());
+        }
+        if self.lto {
+            flags.push("-flto".to_string());
+        }
+        flags.extend(self.sanitizer_compile_flags());
+        flags
+    }
+
+    /// Code generation flags the linker needs too: `-flto` and `-fsanitize`
+    pub fn link_flags(&self) -> Vec<String> {
+        let mut flags = Vec::new();
+        if self.lto {
+            flags.push("-flto".to_string());
+        }
+        flags.extend(self.sanitize_flag());
+        flags
+    }
+
+    /// Sanitizer flags, for build systems without a setting of their own
+    pub fn sanitizer_compile_flags(&self) -> Vec<String> {
+        match self.sanitize_flag() {
+            Some(flag) => vec![flag, "-fno-omit-frame-pointer".to_string()],
+            None => vec![],
+        }
+    }
+
+    /// Sanitizer flags for the linker
+    pub fn sanitizer_link_flags(&self) -> Vec<String> {
+        self.sanitize_flag().into_iter().collect()
+    }
+
+    /// Whether the profile optimizes at all
+    pub fn optimized(&self) -> bool {
+        self.opt_level != OptLevel::O0
+    }
+
+    /// The CMake build type closest to the profile
+    pub fn cmake_build_type(&self) -> &'static str {
+        match (self.opt_level, self.debug) {
+            (OptLevel::O0, _) => "Debug",
+            (OptLevel::Size | OptLevel::MinSize, false) => "MinSizeRel",
+            (_, true) => "RelWithDebInfo",
+            (_, false) => "Release",
+        }
+    }
+
+    /// The XMake mode closest to the profile
+    pub fn xmake_mode(&self) -> &'static str {
+        match (self.opt_level, self.debug) {
+            (OptLevel::O0, _) => "debug",
+            (OptLevel::Size | OptLevel::MinSize, false) => "minsizerel",
+            (_, true) => "releasedbg",
+            (_, false) => "release",
+        }
+    }
+
+    /// Meson's `optimization` option for the profile
+    pub fn meson_optimization(&self) -> &'static str {
+        match self.opt_level {
+            OptLevel::MinSize => "s",
+            level => level.as_str(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profiles(toml: &str) -> BTreeMap<String, ProfileConfig> {
+        #[derive(serde::Deserialize)]
+        struct Manifest {
+            profile: BTreeMap<String, ProfileConfig>,
+        }
+        toml::from_str::<Manifest>(toml).unwrap().profile
+    }
+
+    #[test]
+    fn test_resolve() {
+        let profiles = profiles(
+            r#"
+[profile.release]
+lto = true
+
+[profile.asan]
+inherits = "debug"
+opt-level = 1
+sanitize = ["address", "undefined"]
+
+[profile.small]
+inherits = "release"
+opt-level = "z"
+defines = ["SMALL"]
+
+[profile.orphan]
+debug = true
+
+[profile.a]
+inherits = "b"
+
+[profile.b]
+inherits = "a"
+"#,
+        );
+
+        let release = Profile::resolve("release", &profiles).unwrap();
+        assert!(release.lto && !release.debug);
+        assert_eq!(release.compile_flags(), vec!["-O3", "-flto"]);
+        assert_eq!(release.cmake_build_type(), "Release");
+
+        let asan = Profile::resolve("asan", &profiles).unwrap();
+        assert_eq!(
+            asan.compile_flags(),
+            vec![
+                "-O1",
+                "-g",
+                "-fsanitize=address,undefined",
+                "-fno-omit-frame-pointer"
+            ]
+        );
+        assert_eq!(asan.link_flags(), vec!["-fsanitize=address,undefined"]);
+        assert_eq!(asan.cmake_build_type(), "RelWithDebInfo");
+
+        let small = Profile::resolve("small", &profiles).unwrap();
+        assert_eq!(small.flags.defines, vec!["NDEBUG", "SMALL"]);
+        assert!(small.lto);
+        assert_eq!(small.cmake_build_type(), "MinSizeRel");
+
+        assert_eq!(
+            Profile::resolve("debug", &profiles).unwrap(),
+            Profile::default()
+        );
+        assert!(
+            Profile::resolve("orphan", &profiles)
+                .unwrap_err()
+                .to_string()
+                .contains("inherits")
+        );
+        assert!(
+            Profile::resolve("a", &profiles)
+                .unwrap_err()
+                .to_string()
+                .contains("a -> b -> a")
+        );
+        assert!(
+            Profile::resolve("missing", &profiles)
+                .unwrap_err()
+                .to_string()
+                .contains("available profiles: debug, release, a, asan")
+        );
+    }
+}
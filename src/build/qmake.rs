@@ -8,7 +8,7 @@ use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Command;
 
-use super::BuildSystem;
+use super::{BuildOptions, BuildSystem};
 use crate::deps::ResolvedDependency;
 use crate::scan::ProjectSources;
 use crate::util::pretty::*;
@@ -19,14 +19,95 @@ use crate::util::pretty::*;
 /// Makefiles for Qt-based applications.
 pub struct QMakeBuildSystem {
     root: String,
+    options: BuildOptions,
 }
 
 impl QMakeBuildSystem {
-    pub fn new(root: &str) -> Self {
+    pub fn new(root: &str, options: BuildOptions) -> Self {
         Self {
             root: root.to_string(),
+            options,
         }
     }
+
+    /// qmake variable assignments carrying porters.toml's compiler and
+    /// linker settings
+    fn qmake_variables(&self) -> Vec<String> {
+        let options = &self.options;
+        let profile = &options.profile;
+        let defines: Vec<&str> = options
+            .defines
+            .iter()
+            .chain(&options.flags.defines)
+            .chain(&profile.flags.defines)
+            .map(String::as_str)
+            .collect();
+        let include_dirs: Vec<String> = options
+            .include_dirs
+            .iter()
+            .map(|dir| dir.display().to_string())
+            .collect();
+        let mut libs: Vec<String> = options
+            .library_dirs
+            .iter()
+            .map(|dir| format!("-L{}", dir.display()))
+            .collect();
+        libs.extend(options.link_libraries());
+
+        // The profile picks the debug or release configuration; sanitizers
+        // go in as flags
+        let (config, other) = if profile.optimized() {
+            ("release", "debug")
+        } else {
+            ("debug", "release")
+        };
+        let mut configs = vec![config];
+        if profile.optimized() && profile.debug {
+            configs.push("force_debug_info");
+        }
+        if profile.lto {
+            configs.push("ltcg");
+        }
+        let sanitizers = profile.sanitizer_compile_flags();
+        let cflags = [
+            &options.flags.cflags[..],
+            &profile.flags.cflags,
+            &sanitizers,
+        ]
+        .concat();
+        let cxxflags = [
+            &options.flags.cxxflags[..],
+            &profile.flags.cxxflags,
+            &sanitizers,
+        ]
+        .concat();
+        let lflags = [
+            &options.flags.ldflags[..],
+            &profile.flags.ldflags,
+            &profile.sanitizer_link_flags(),
+        ]
+        .concat();
+
+        [
+            ("CONFIG", configs.join(" ")),
+            ("DEFINES", defines.join(" ")),
+            ("INCLUDEPATH", include_dirs.join(" ")),
+            ("QMAKE_CFLAGS", cflags.join(" ")),
+            ("QMAKE_CXXFLAGS", cxxflags.join(" ")),
+            ("QMAKE_LFLAGS", lflags.join(" ")),
+            ("LIBS", libs.join(" ")),
+        ]
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(name, value)| format!("{}+={}", name, value))
+        .chain(std::iter::once(format!("CONFIG-={}", other)))
+        .collect()
+    }
+
+    /// Records the settings the Makefile was generated with
+    fn stamp(&self) -> std::path::PathBuf {
+        self.options.cache_dir.join("qmake-options")
+    }
 }
 
 impl BuildSystem for QMakeBuildSystem {
@@ -53,19 +134,22 @@ impl BuildSystem for QMakeBuildSystem {
 
         let mut cmd = Command::new("qmake");
         cmd.current_dir(&self.root);
+        cmd.args(self.qmake_variables());
+        self.options.apply_feature_env(&mut cmd);
 
-        let output = cmd
-            .output()
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
             .with_context(|| "Failed to run qmake. Is Qt/QMake installed?")?;
 
-        if !output.status.success() {
-            print_error(&format!(
-                "QMake failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        if !status.success() {
+            print_error("QMake failed");
             return Err(anyhow::anyhow!("QMake failed"));
         }
 
+        std::fs::create_dir_all(&self.options.cache_dir)?;
+        std::fs::write(self.stamp(), self.options.fingerprint())?;
         print_success("QMake successful");
         Ok(())
     }
@@ -76,8 +160,10 @@ impl BuildSystem for QMakeBuildSystem {
         deps: &[ResolvedDependency],
         args: &[String],
     ) -> Result<()> {
-        // Generate Makefile if it doesn't exist
-        if !Path::new(&self.root).join("Makefile").exists() {
+        // Generate the Makefile if it doesn't exist or the build settings changed
+        if !Path::new(&self.root).join("Makefile").exists()
+            || std::fs::read_to_string(self.stamp()).ok() != Some(self.options.fingerprint())
+        {
             self.configure(sources, deps)?;
         }
 
@@ -88,15 +174,17 @@ impl BuildSystem for QMakeBuildSystem {
         };
 
         cmd.current_dir(&self.root);
+        self.options.apply_env(&mut cmd);
         cmd.args(args);
 
-        let output = cmd.output().with_context(|| "Failed to build")?;
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to build")?;
 
-        if !output.status.success() {
-            print_error(&format!(
-                "Build failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        if !status.success() {
+            print_error("Build failed");
             return Err(anyhow::anyhow!("Build failed"));
         }
 
@@ -119,10 +207,15 @@ impl BuildSystem for QMakeBuildSystem {
         let mut cmd = Command::new(cmd_name);
         cmd.current_dir(&self.root);
         cmd.arg("check");
+        self.options.apply_env(&mut cmd);
 
-        let output = cmd.output().with_context(|| "Failed to run tests")?;
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to run tests")?;
 
-        if !output.status.success() {
+        if !status.success() {
             print_error("Tests failed");
             return Err(anyhow::anyhow!("Tests failed"));
         }
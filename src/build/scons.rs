@@ -8,7 +8,7 @@ use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Command;
 
-use super::BuildSystem;
+use super::{BuildOptions, BuildSystem};
 use crate::deps::ResolvedDependency;
 use crate::scan::ProjectSources;
 use crate::util::pretty::*;
@@ -19,12 +19,14 @@ use crate::util::pretty::*;
 /// build configuration and execution.
 pub struct SConsBuildSystem {
     root: String,
+    options: BuildOptions,
 }
 
 impl SConsBuildSystem {
-    pub fn new(root: &str) -> Self {
+    pub fn new(root: &str, options: BuildOptions) -> Self {
         Self {
             root: root.to_string(),
+            options,
         }
     }
 }
@@ -53,16 +55,16 @@ impl BuildSystem for SConsBuildSystem {
         let mut cmd = Command::new("scons");
         cmd.current_dir(&self.root);
         cmd.args(args);
+        self.options.apply_env(&mut cmd);
 
-        let output = cmd
-            .output()
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
             .with_context(|| "Failed to run scons. Is SCons installed?")?;
 
-        if !output.status.success() {
-            print_error(&format!(
-                "SCons build failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        if !status.success() {
+            print_error("SCons build failed");
             return Err(anyhow::anyhow!("SCons build failed"));
         }
 
@@ -76,9 +78,9 @@ impl BuildSystem for SConsBuildSystem {
         cmd.arg("run");
         cmd.args(args);
 
-        let output = cmd.output().with_context(|| "Failed to run scons run")?;
+        let status = cmd.status().with_context(|| "Failed to run scons run")?;
 
-        if !output.status.success() {
+        if !status.success() {
             print_warning("No 'run' target defined in SConstruct");
             print_info("Try running the executable manually from the build directory");
         }
@@ -90,10 +92,15 @@ impl BuildSystem for SConsBuildSystem {
         let mut cmd = Command::new("scons");
         cmd.current_dir(&self.root);
         cmd.arg("test");
+        self.options.apply_env(&mut cmd);
 
-        let output = cmd.output().with_context(|| "Failed to run scons test")?;
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to run scons test")?;
 
-        if !output.status.success() {
+        if !status.success() {
             print_error("Tests failed");
             return Err(anyhow::anyhow!("Tests failed"));
         }
@@ -8,7 +8,7 @@ use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Command;
 
-use super::BuildSystem;
+use super::{BuildOptions, BuildSystem};
 use crate::deps::ResolvedDependency;
 use crate::scan::ProjectSources;
 use crate::util::pretty::*;
@@ -19,12 +19,14 @@ use crate::util::pretty::*;
 /// CMake toolchains for cross-platform builds.
 pub struct VcpkgBuildSystem {
     root: String,
+    options: BuildOptions,
 }
 
 impl VcpkgBuildSystem {
-    pub fn new(root: &str) -> Self {
+    pub fn new(root: &str, options: BuildOptions) -> Self {
         Self {
             root: root.to_string(),
+            options,
         }
     }
 }
@@ -45,15 +47,14 @@ impl BuildSystem for VcpkgBuildSystem {
         cmd.current_dir(&self.root);
         cmd.arg("install");
 
-        let output = cmd
-            .output()
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
             .with_context(|| "Failed to run vcpkg. Is vcpkg installed and in PATH?")?;
 
-        if !output.status.success() {
-            print_error(&format!(
-                "vcpkg install failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        if !status.success() {
+            print_error("vcpkg install failed");
             return Err(anyhow::anyhow!("vcpkg install failed"));
         }
 
@@ -87,41 +88,46 @@ impl BuildSystem for VcpkgBuildSystem {
 
             let mut cmd = Command::new("cmake");
             cmd.current_dir(&self.root);
-            cmd.arg("-B").arg("build");
+            cmd.arg("-B").arg(self.options.profile_dir());
             cmd.arg("-S").arg(".");
+            cmd.args(self.options.cmake_options());
+            self.options.apply_feature_env(&mut cmd);
+            // Apply porters.toml's build settings and find the dependencies
+            let script = super::cmake::write_dependency_script(&self.options.profile_dir(), deps)?;
+            cmd.arg(format!("-DCMAKE_PROJECT_INCLUDE={}", script.display()));
 
             if toolchain.exists() {
                 cmd.arg(format!("-DCMAKE_TOOLCHAIN_FILE={}", toolchain.display()));
             }
 
-            cmd.arg("-DCMAKE_BUILD_TYPE=Release");
-
-            let output = cmd
-                .output()
+            let status = self
+                .options
+                .diagnostics
+                .run(&mut cmd)
                 .with_context(|| "Failed to configure with CMake")?;
 
-            if !output.status.success() {
-                print_error(&format!(
-                    "CMake configure failed:\n{}",
-                    String::from_utf8_lossy(&output.stderr)
-                ));
+            if !status.success() {
+                print_error("CMake configure failed");
                 return Err(anyhow::anyhow!("CMake configure failed"));
             }
 
             let mut build_cmd = Command::new("cmake");
             build_cmd.current_dir(&self.root);
-            build_cmd.arg("--build").arg("build");
+            build_cmd.arg("--build").arg(self.options.profile_dir());
+            build_cmd
+                .arg("--config")
+                .arg(self.options.profile.cmake_build_type());
             build_cmd.args(args);
+            self.options.apply_feature_env(&mut build_cmd);
 
-            let output = build_cmd
-                .output()
+            let status = self
+                .options
+                .diagnostics
+                .run(&mut build_cmd)
                 .with_context(|| "Failed to build with CMake")?;
 
-            if !output.status.success() {
-                print_error(&format!(
-                    "Build failed:\n{}",
-                    String::from_utf8_lossy(&output.stderr)
-                ));
+            if !status.success() {
+                print_error("Build failed");
                 return Err(anyhow::anyhow!("Build failed"));
             }
         } else {
@@ -137,7 +143,11 @@ impl BuildSystem for VcpkgBuildSystem {
 
     fn run(&self, args: &[String]) -> Result<()> {
         print_warning("vcpkg is a package manager. Run the executable from the build directory.");
-        print_info(&format!("Try: ./build/<executable> {}", args.join(" ")));
+        print_info(&format!(
+            "Try: {}/<executable> {}",
+            self.options.profile_dir().display(),
+            args.join(" ")
+        ));
         Ok(())
     }
 
@@ -145,11 +155,16 @@ impl BuildSystem for VcpkgBuildSystem {
         // vcpkg doesn't have built-in test support, defer to CMake if available
         if Path::new(&self.root).join("CMakeLists.txt").exists() {
             let mut cmd = Command::new("ctest");
-            cmd.current_dir(Path::new(&self.root).join("build"));
+            cmd.current_dir(self.options.profile_dir());
+            cmd.arg("-C").arg(self.options.profile.cmake_build_type());
 
-            let output = cmd.output().with_context(|| "Failed to run tests")?;
+            let status = self
+                .options
+                .diagnostics
+                .run(&mut cmd)
+                .with_context(|| "Failed to run tests")?;
 
-            if !output.status.success() {
+            if !status.success() {
                 print_error("Tests failed");
                 return Err(anyhow::anyhow!("Tests failed"));
             }
@@ -161,10 +176,7 @@ impl BuildSystem for VcpkgBuildSystem {
     }
 
     fn clean(&self) -> Result<()> {
-        let build_dir = Path::new(&self.root).join("build");
-        if build_dir.exists() {
-            std::fs::remove_dir_all(&build_dir).ok();
-        }
+        self.options.clean_build_dir(Path::new(&self.root))?;
 
         print_success("Clean successful");
         Ok(())
@@ -8,7 +8,7 @@ use anyhow::{Context, Result};
 use std::path::Path;
 use std::process::Command;
 
-use super::BuildSystem;
+use super::{BuildOptions, BuildSystem};
 use crate::deps::ResolvedDependency;
 use crate::scan::ProjectSources;
 use crate::util::pretty::*;
@@ -19,14 +19,48 @@ use crate::util::pretty::*;
 /// with integrated dependency management.
 pub struct XMakeBuildSystem {
     root: String,
+    options: BuildOptions,
 }
 
 impl XMakeBuildSystem {
-    pub fn new(root: &str) -> Self {
+    pub fn new(root: &str, options: BuildOptions) -> Self {
         Self {
             root: root.to_string(),
+            options,
         }
     }
+
+    /// `xmake config` flags carrying the profile and porters.toml's compiler
+    /// and linker settings
+    pub(crate) fn config_flags(&self) -> Vec<String> {
+        let profile = &self.options.profile;
+        // XMake has no mode for sanitizers, so they go in as flags
+        let sanitizers = profile.sanitizer_compile_flags();
+        let with_sanitizers = |flags: Vec<String>| [flags, sanitizers.clone()].concat().join(" ");
+
+        let mut link_flags = self.options.link_flags();
+        link_flags.extend(profile.sanitizer_link_flags());
+        link_flags.extend(self.options.link_libraries());
+        let link_flags = link_flags.join(" ");
+
+        let mut flags = vec![
+            format!("--mode={}", profile.xmake_mode()),
+            format!("--buildir={}", self.options.profile_dir().display()),
+            format!("--cflags={}", with_sanitizers(self.options.c_flags())),
+            format!("--cxxflags={}", with_sanitizers(self.options.cxx_flags())),
+            format!("--ldflags={}", link_flags),
+            format!("--shflags={}", link_flags),
+        ];
+        if profile.lto {
+            flags.push("--policies=build.optimization.lto".to_string());
+        }
+        flags
+    }
+
+    /// Records the settings the project was configured with
+    fn stamp(&self) -> std::path::PathBuf {
+        Path::new(&self.root).join(".xmake").join("porters-options")
+    }
 }
 
 impl BuildSystem for XMakeBuildSystem {
@@ -41,51 +75,56 @@ impl BuildSystem for XMakeBuildSystem {
     fn configure(&self, _sources: &ProjectSources, _deps: &[ResolvedDependency]) -> Result<()> {
         print_build("Configuring XMake...");
 
-        let output = Command::new("xmake")
-            .arg("config")
-            .current_dir(&self.root)
-            .output()
+        let mut cmd = Command::new("xmake");
+        cmd.arg("config").args(self.config_flags()).current_dir(&self.root);
+        self.options.apply_feature_env(&mut cmd);
+
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
             .with_context(|| "Failed to run xmake config")?;
 
-        if !output.status.success() {
-            print_error(&format!(
-                "XMake configuration failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        if !status.success() {
+            print_error("XMake configuration failed");
             return Err(anyhow::anyhow!("XMake configuration failed"));
         }
 
+        std::fs::create_dir_all(Path::new(&self.root).join(".xmake"))?;
+        std::fs::write(self.stamp(), self.options.fingerprint())?;
         Ok(())
     }
 
     fn build(
         &self,
-        _sources: &ProjectSources,
-        _deps: &[ResolvedDependency],
+        sources: &ProjectSources,
+        deps: &[ResolvedDependency],
         args: &[String],
     ) -> Result<()> {
+        // Configure first if needed, or again when the build settings changed
+        if std::fs::read_to_string(self.stamp()).ok() != Some(self.options.fingerprint()) {
+            self.configure(sources, deps)?;
+        }
+
         print_build("Building with XMake...");
 
         let mut cmd = Command::new("xmake");
         for arg in args {
             cmd.arg(arg);
         }
+        self.options.apply_feature_env(&mut cmd);
 
-        let output = cmd
-            .current_dir(&self.root)
-            .output()
+        let status = self
+            .options
+            .diagnostics
+            .run(cmd.current_dir(&self.root))
             .with_context(|| "Failed to run xmake")?;
 
-        if !output.status.success() {
-            print_error(&format!(
-                "Build failed:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+        if !status.success() {
+            print_error("Build failed");
             return Err(anyhow::anyhow!("Build failed"));
         }
 
-        print!("{}", String::from_utf8_lossy(&output.stdout));
-
         Ok(())
     }
 
@@ -114,15 +153,16 @@ impl BuildSystem for XMakeBuildSystem {
 
         print_build("Running tests with XMake...");
 
-        let output = Command::new("xmake")
-            .arg("test")
-            .current_dir(&self.root)
-            .output()
-            .with_context(|| "Failed to run xmake test")?;
+        let mut cmd = Command::new("xmake");
+        cmd.arg("test").current_dir(&self.root);
 
-        print!("{}", String::from_utf8_lossy(&output.stdout));
+        let status = self
+            .options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to run xmake test")?;
 
-        if !output.status.success() {
+        if !status.success() {
             print_error("Tests failed");
             return Err(anyhow::anyhow!("Tests failed"));
         }
@@ -38,6 +38,11 @@ impl GlobalCache {
         Self { cache_dir, enabled }
     }
 
+    /// Whether packages are stored in the global cache
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
     /// Initialize global cache directory
     pub fn init(&self) -> Result<()> {
         if self.enabled && !self.cache_dir.exists() {
@@ -1,6 +1,6 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,9 +16,18 @@ pub struct PortersConfig {
     #[serde(default)]
     pub features: HashMap<String, Vec<String>>,
 
+    /// Platform-specific dependencies (`[target.'linux-aarch64'.dependencies]`)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub target: HashMap<String, TargetDependencies>,
+
     #[serde(default)]
     pub build: BuildConfig,
 
+    /// Build profiles (`[profile.release]`), overriding or adding to the
+    /// built-in `debug` and `release` profiles
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profile: BTreeMap<String, ProfileConfig>,
+
     #[serde(default)]
     pub requires: ToolRequirements,
 
@@ -100,6 +109,7 @@ pub enum ProjectType {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
 pub enum Dependency {
     Simple(String),
     Detailed {
@@ -121,6 +131,10 @@ pub enum Dependency {
         #[serde(skip_serializing_if = "Option::is_none")]
         path: Option<String>,
 
+        /// Subdirectory of a Git repository that holds the package
+        #[serde(skip_serializing_if = "Option::is_none")]
+        subdir: Box<Option<String>>,
+
         #[serde(default)]
         optional: bool,
 
@@ -130,11 +144,37 @@ pub enum Dependency {
         #[serde(skip_serializing_if = "Option::is_none")]
         platforms: Option<Vec<String>>,
 
-        #[serde(skip_serializing_if = "Option::is_none")]
-        constraints: Box<Option<String>>,
+        /// Toolchain requirements checked before building
+        #[serde(
+            default,
+            deserialize_with = "deserialize_constraints",
+            skip_serializing_if = "Option::is_none"
+        )]
+        constraints: Box<Option<DependencyConstraints>>,
 
+        /// Expected SHA-256 hash of the fetched sources
         #[serde(skip_serializing_if = "Option::is_none")]
         checksum: Box<Option<String>>,
+
+        /// URL of a source archive (`.tar.gz`, `.tgz`, `.tar` or `.zip`)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        url: Box<Option<String>>,
+
+        /// Expected SHA-256 hash of the archive file
+        #[serde(skip_serializing_if = "Option::is_none")]
+        sha256: Box<Option<String>>,
+
+        /// Leading directory removed from the archive's entries
+        #[serde(rename = "strip-prefix", skip_serializing_if = "Option::is_none")]
+        strip_prefix: Box<Option<String>>,
+
+        /// Name of the `[[registries]]` entry the package is resolved from
+        #[serde(skip_serializing_if = "Option::is_none")]
+        registry: Box<Option<String>>,
+
+        /// Build options of a registry package, overriding the registry's defaults
+        #[serde(skip_serializing_if = "Option::is_none")]
+        options: Box<Option<toml::Table>>,
     },
 }
 
@@ -176,9 +216,21 @@ pub struct BuildConfig {
     /// Executable output name override (default: output-name or project name)
     #[serde(skip_serializing_if = "Option::is_none", rename = "executable-name")]
     pub executable_name: Option<String>,
+
+    /// Kind of library the built-in backend links for library projects
+    #[serde(default, rename = "library-type")]
+    pub library_type: LibraryType,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LibraryType {
+    #[default]
+    Static,
+    Shared,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BuildFlags {
     #[serde(default)]
     pub cflags: Vec<String>,
@@ -205,6 +257,114 @@ pub struct LinkingConfig {
     pub frameworks: Vec<String>,
 }
 
+/// A build profile from porters.toml (`[profile.<name>]`)
+///
+/// Unset fields come from the profile named by `inherits`, or from the
+/// built-in profile of the same name.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProfileConfig {
+    /// Profile this one starts from; required for profiles other than
+    /// `debug` and `release`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inherits: Option<String>,
+
+    /// Optimization level: 0, 1, 2, 3, "s" (size) or "z" (smallest size)
+    #[serde(skip_serializing_if = "Option::is_none", rename = "opt-level")]
+    pub opt_level: Option<OptLevel>,
+
+    /// Generate debug info
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug: Option<bool>,
+
+    /// Link-time optimization
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lto: Option<bool>,
+
+    /// Sanitizers to build with, such as "address" or "undefined"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sanitize: Option<Vec<String>>,
+
+    /// Extra flags, added to those of the inherited profile
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cflags: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cxxflags: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ldflags: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub defines: Vec<String>,
+}
+
+/// Compiler optimization level (`-O<level>`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptLevel {
+    O0,
+    O1,
+    O2,
+    O3,
+    /// Optimize for size (`-Os`)
+    Size,
+    /// Optimize aggressively for size (`-Oz`)
+    MinSize,
+}
+
+impl OptLevel {
+    /// The level as written after `-O`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OptLevel::O0 => "0",
+            OptLevel::O1 => "1",
+            OptLevel::O2 => "2",
+            OptLevel::O3 => "3",
+            OptLevel::Size => "s",
+            OptLevel::MinSize => "z",
+        }
+    }
+}
+
+impl Serialize for OptLevel {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            OptLevel::O0 => serializer.serialize_u8(0),
+            OptLevel::O1 => serializer.serialize_u8(1),
+            OptLevel::O2 => serializer.serialize_u8(2),
+            OptLevel::O3 => serializer.serialize_u8(3),
+            OptLevel::Size | OptLevel::MinSize => serializer.serialize_str(self.as_str()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for OptLevel {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Level {
+            Number(i64),
+            Name(String),
+        }
+
+        let level = match Level::deserialize(deserializer)? {
+            Level::Number(n) => n.to_string(),
+            Level::Name(name) => name,
+        };
+        match level.as_str() {
+            "0" => Ok(OptLevel::O0),
+            "1" => Ok(OptLevel::O1),
+            "2" => Ok(OptLevel::O2),
+            "3" => Ok(OptLevel::O3),
+            "s" => Ok(OptLevel::Size),
+            "z" => Ok(OptLevel::MinSize),
+            other => Err(serde::de::Error::custom(format!(
+                "invalid opt-level `{}`; expected 0, 1, 2, 3, \"s\" or \"z\"",
+                other
+            ))),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct BuildScripts {
     #[serde(skip_serializing_if = "Option::is_none", rename = "pre-build")]
@@ -237,6 +397,110 @@ pub struct CustomBuild {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub clean: Option<String>,
 }
+/// Toolchain requirements of a dependency
+///
+/// Mirrors the constraints registry packages declare, e.g.
+/// `constraints = { min-cpp-standard = "17", compilers = { gcc = ">=9" } }`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DependencyConstraints {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_cpp_standard: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cpp_standard: Option<String>,
+
+    /// Supported compilers and their version requirements
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub compilers: HashMap<String, String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arch: Vec<String>,
+
+    /// Environment variables that must be set to the given values
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub environment: HashMap<String, String>,
+}
+
+/// Read `constraints`, accepting the free-form string older manifests allowed
+///
+/// That string was never checked, so it is read as no constraints;
+/// `porters validate` points it out.
+fn deserialize_constraints<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Box<Option<DependencyConstraints>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Constraints {
+        Table(DependencyConstraints),
+        Legacy(#[allow(dead_code)] String),
+    }
+
+    let constraints = match Option::<Constraints>::deserialize(deserializer)? {
+        Some(Constraints::Table(constraints)) => Some(constraints),
+        Some(Constraints::Legacy(_)) | None => None,
+    };
+    Ok(Box::new(constraints))
+}
+
+impl Dependency {
+    /// Platforms the dependency is restricted to (`None` means every platform)
+    pub fn platforms(&self) -> Option<&[String]> {
+        match self {
+            Dependency::Detailed {
+                platforms: Some(platforms),
+                ..
+            } => Some(platforms),
+            _ => None,
+        }
+    }
+
+    /// The same dependency, only required on `platform`
+    fn restricted_to(&self, platform: &str) -> Dependency {
+        let mut dep = match self {
+            Dependency::Simple(version) => Dependency::Detailed {
+                version: Some(version.clone()),
+                git: None,
+                branch: None,
+                tag: None,
+                rev: None,
+                path: None,
+                subdir: Box::new(None),
+                optional: false,
+                features: vec![],
+                platforms: None,
+                constraints: Box::new(None),
+                checksum: Box::new(None::<String>),
+                url: Box::new(None),
+                sha256: Box::new(None),
+                strip_prefix: Box::new(None),
+                registry: Box::new(None),
+                options: Box::new(None),
+            },
+            detailed => detailed.clone(),
+        };
+        if let Dependency::Detailed { platforms, .. } = &mut dep {
+            *platforms = Some(vec![platform.to_string()]);
+        }
+        dep
+    }
+}
+
+/// Dependencies that only apply when building for a platform
+///
+/// Keys of the `[target]` table are platform names: an OS (`linux`) or an
+/// OS and architecture (`linux-aarch64`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TargetDependencies {
+    #[serde(default)]
+    pub dependencies: HashMap<String, Dependency>,
+
+    #[serde(default, rename = "dev-dependencies")]
+    pub dev_dependencies: HashMap<String, Dependency>,
+}
 
 /// Tool version requirements
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -329,11 +593,17 @@ impl PortersConfig {
                 tag: None,
                 rev: None,
                 path: None,
+                subdir: Box::new(None),
                 optional,
                 features: vec![],
                 platforms: None,
                 constraints: Box::new(None),
                 checksum: Box::new(None::<String>),
+                url: Box::new(None),
+                sha256: Box::new(None),
+                strip_prefix: Box::new(None),
+                registry: Box::new(None),
+                options: Box::new(None),
             }
         } else if Path::new(package).exists() {
             // Path dependency
@@ -344,14 +614,20 @@ impl PortersConfig {
                 tag: None,
                 rev: None,
                 path: Some(package.to_string()),
+                subdir: Box::new(None),
                 optional,
                 features: vec![],
                 platforms: None,
                 constraints: Box::new(None),
                 checksum: Box::new(None::<String>),
+                url: Box::new(None),
+                sha256: Box::new(None),
+                strip_prefix: Box::new(None),
+                registry: Box::new(None),
+                options: Box::new(None),
             }
         } else {
-            // Simple package name (for future registry support)
+            // Simple package name (resolved through the registry)
             Dependency::Detailed {
                 version: Some("*".to_string()),
                 git: None,
@@ -359,11 +635,17 @@ impl PortersConfig {
                 tag: None,
                 rev: None,
                 path: None,
+                subdir: Box::new(None),
                 optional,
                 features: vec![],
                 platforms: None,
                 constraints: Box::new(None),
                 checksum: Box::new(None::<String>),
+                url: Box::new(None),
+                sha256: Box::new(None),
+                strip_prefix: Box::new(None),
+                registry: Box::new(None),
+                options: Box::new(None),
             }
         };
 
@@ -393,21 +675,43 @@ impl PortersConfig {
     pub fn remove_dependency(&mut self, package: &str) -> Result<()> {
         self.dependencies.remove(package);
         self.dev_dependencies.remove(package);
+        for target in self.target.values_mut() {
+            target.dependencies.remove(package);
+            target.dev_dependencies.remove(package);
+        }
         Ok(())
     }
 
-    /// Get all dependencies (including dev dependencies)
-    pub fn all_dependencies(&self) -> HashMap<String, &Dependency> {
-        let mut all = HashMap::new();
-
-        for (name, dep) in &self.dependencies {
-            all.insert(name.clone(), dep);
+    /// Get dependencies for every platform, including `[target]` tables
+    ///
+    /// Entries of a `[target.<platform>]` table come back restricted to that
+    /// platform through their `platforms` list. A name may appear several
+    /// times when it is declared for more than one platform.
+    pub fn platform_dependencies(&self, dev: bool) -> Vec<(String, Dependency)> {
+        let mut all: Vec<(String, Dependency)> = self
+            .dependencies
+            .iter()
+            .map(|(name, dep)| (name.clone(), dep.clone()))
+            .collect();
+        if dev {
+            all.extend(
+                self.dev_dependencies
+                    .iter()
+                    .map(|(name, dep)| (name.clone(), dep.clone())),
+            );
         }
 
-        for (name, dep) in &self.dev_dependencies {
-            all.insert(name.clone(), dep);
+        for (platform, target) in &self.target {
+            let mut tables = vec![&target.dependencies];
+            if dev {
+                tables.push(&target.dev_dependencies);
+            }
+            for (name, dep) in tables.into_iter().flatten() {
+                all.push((name.clone(), dep.restricted_to(platform)));
+            }
         }
 
+        all.sort_by(|a, b| a.0.cmp(&b.0));
         all
     }
 
@@ -524,16 +828,38 @@ fn default_true() -> bool {
 }
 
 /// Registry configuration
+///
+/// Registries are queried in the order they are listed, before the default
+/// `porters` registry. An entry named `porters` replaces the default one.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegistryConfig {
     pub name: String,
+    /// Git repository, `sparse+https://` URL or local directory holding the
+    /// package definitions
     pub url: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(skip_serializing_if = "Option::is_none", alias = "auth-token")]
     pub auth_token: Option<String>,
     #[serde(default = "default_true")]
     pub enabled: bool,
 }
 
+impl RegistryConfig {
+    /// Token to authenticate with
+    ///
+    /// `PORTERS_REGISTRY_<NAME>_TOKEN` takes precedence over `auth_token`,
+    /// so tokens don't have to be committed to porters.toml.
+    pub fn token(&self) -> Option<String> {
+        let var = format!(
+            "PORTERS_REGISTRY_{}_TOKEN",
+            self.name.to_uppercase().replace('-', "_")
+        );
+        std::env::var(var)
+            .ok()
+            .filter(|token| !token.is_empty())
+            .or_else(|| self.auth_token.clone())
+    }
+}
+
 /// Cross-compilation configuration
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CrossCompileConfig {
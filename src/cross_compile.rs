@@ -3,13 +3,20 @@
 //! This module provides cross-compilation capabilities for building
 //! C/C++ projects for multiple target platforms and architectures.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use colored::Colorize;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 use std::process::Command;
 
+use crate::build::BuildOptions;
+use crate::build::cmake::write_dependency_script;
+use crate::build::meson::MesonBuildSystem;
+use crate::build::xmake::XMakeBuildSystem;
+use crate::deps::ResolvedDependency;
+use crate::util::pretty::*;
+
 /// Cross-compilation target
 ///
 /// Represents a specific platform and architecture combination
@@ -48,6 +55,32 @@ pub enum Target {
 }
 
 impl Target {
+    /// Parse a target from its name (`linux-aarch64`) or triple
+    pub fn parse(value: &str) -> Option<Target> {
+        let value = value.trim().to_lowercase();
+        Target::all()
+            .into_iter()
+            .find(|target| target.name() == value || target.triple() == value)
+    }
+
+    /// Get target name, as used in `[target.<name>]` tables
+    pub fn name(&self) -> &'static str {
+        match self {
+            Target::LinuxX8664 => "linux-x86_64",
+            Target::LinuxAarch64 => "linux-aarch64",
+            Target::LinuxArmv7 => "linux-armv7",
+            Target::WindowsX8664 => "windows-x86_64",
+            Target::WindowsI686 => "windows-i686",
+            Target::MacosX8664 => "macos-x86_64",
+            Target::MacosAarch64 => "macos-aarch64",
+            Target::BaremetalArm => "baremetal-arm",
+            Target::BaremetalRiscv => "baremetal-riscv",
+            Target::AndroidAarch64 => "android-aarch64",
+            Target::IosAarch64 => "ios-aarch64",
+            Target::Wasm32 => "wasm32",
+        }
+    }
+
     /// Get all targets for a platform
     pub fn for_platform(platform: &str) -> Vec<Target> {
         match platform.to_lowercase().as_str() {
@@ -185,12 +218,23 @@ impl CrossCompiler {
         }
     }
 
-    /// Compile for specific target
-    pub fn compile(&self, target: &Target, build_system: &str) -> Result<PathBuf> {
-        println!(
-            "🔨  Cross-compiling for {}...",
+    /// Compile for specific target against the dependencies resolved for it
+    ///
+    /// `options` carry porters.toml's build settings, the profile and the
+    /// dependencies' directories, as for a host build. The target's own flags
+    /// are added to them, and the target builds into
+    /// `<build-dir>/<triple>/<profile>`.
+    pub fn compile(
+        &self,
+        target: &Target,
+        build_system: &str,
+        deps: &[ResolvedDependency],
+        options: &BuildOptions,
+    ) -> Result<PathBuf> {
+        print_build(&format!(
+            "Cross-compiling for {}...",
             target.display_name().cyan()
-        );
+        ));
 
         // Get target configuration
         let target_config = self.config.targets.get(target.triple());
@@ -198,42 +242,49 @@ impl CrossCompiler {
         // Check toolchain availability
         self.check_toolchain(target)?;
 
+        let options = Self::target_options(target, target_config, options);
+
         // Create build directory
-        let build_dir = self.project_root.join("build").join(target.triple());
+        let build_dir = options.profile_dir();
         std::fs::create_dir_all(&build_dir)?;
 
         // Compile based on build system
         match build_system.to_lowercase().as_str() {
-            "cmake" => self.compile_cmake(target, target_config, &build_dir)?,
-            "make" => self.compile_make(target, target_config, &build_dir)?,
-            "meson" => self.compile_meson(target, target_config, &build_dir)?,
-            "xmake" => self.compile_xmake(target, target_config, &build_dir)?,
+            "cmake" => self.compile_cmake(target, target_config, deps, &options)?,
+            "make" => self.compile_make(target, &options)?,
+            "meson" => self.compile_meson(target, target_config, &options)?,
+            "xmake" => self.compile_xmake(target, &options)?,
             _ => anyhow::bail!(
                 "Cross-compilation not supported for build system: {}",
                 build_system
             ),
         }
 
-        println!(
-            "✅ Cross-compilation successful for {}",
+        print_success(&format!(
+            "Cross-compilation successful for {}",
             target.display_name().green()
-        );
+        ));
         Ok(build_dir)
     }
 
-    /// Compile all targets
-    pub fn compile_all(&self, targets: &[Target], build_system: &str) -> Result<Vec<PathBuf>> {
+    /// Compile all targets, each with the dependencies resolved for it and
+    /// the build options made from them
+    pub fn compile_all(
+        &self,
+        targets: &[(Target, Vec<ResolvedDependency>, BuildOptions)],
+        build_system: &str,
+    ) -> Result<Vec<PathBuf>> {
         let mut build_dirs = Vec::new();
 
-        for target in targets {
-            match self.compile(target, build_system) {
+        for (target, deps, options) in targets {
+            match self.compile(target, build_system, deps, options) {
                 Ok(dir) => build_dirs.push(dir),
                 Err(e) => {
-                    println!(
-                        "❌ Failed to compile for {}: {}",
+                    print_error(&format!(
+                        "Failed to compile for {}: {}",
                         target.display_name().red(),
                         e
-                    );
+                    ));
                 }
             }
         }
@@ -241,6 +292,32 @@ impl CrossCompiler {
         Ok(build_dirs)
     }
 
+    /// `options` for one target: its flags come after porters.toml's, and it
+    /// builds into a directory named after its triple
+    fn target_options(
+        target: &Target,
+        config: Option<&TargetConfig>,
+        options: &BuildOptions,
+    ) -> BuildOptions {
+        let mut options = options.clone();
+        options.build_dir = options.build_dir.join(target.triple());
+        if let Some(cfg) = config {
+            options
+                .flags
+                .cflags
+                .extend(cfg.flags.cflags.iter().cloned());
+            options
+                .flags
+                .cxxflags
+                .extend(cfg.flags.cxxflags.iter().cloned());
+            options
+                .flags
+                .ldflags
+                .extend(cfg.flags.ldflags.iter().cloned());
+        }
+        options
+    }
+
     /// Check if toolchain is available
     fn check_toolchain(&self, target: &Target) -> Result<()> {
         let toolchain = target.toolchain();
@@ -249,10 +326,10 @@ impl CrossCompiler {
         let output = Command::new("which").arg(cc).output();
 
         if output.is_err() || !output.unwrap().status.success() {
-            println!(
-                "⚠️  Toolchain {} not found. Install instructions:",
+            print_warning(&format!(
+                "Toolchain {} not found. Install instructions:",
                 cc.yellow()
-            );
+            ));
             self.print_install_instructions(target);
             anyhow::bail!("Required toolchain not available");
         }
@@ -264,25 +341,27 @@ impl CrossCompiler {
     fn print_install_instructions(&self, target: &Target) {
         match target {
             Target::LinuxAarch64 => {
-                println!("  sudo apt-get install gcc-aarch64-linux-gnu g++-aarch64-linux-gnu");
+                print_info("  sudo apt-get install gcc-aarch64-linux-gnu g++-aarch64-linux-gnu");
             }
             Target::LinuxArmv7 => {
-                println!("  sudo apt-get install gcc-arm-linux-gnueabihf g++-arm-linux-gnueabihf");
+                print_info(
+                    "  sudo apt-get install gcc-arm-linux-gnueabihf g++-arm-linux-gnueabihf",
+                );
             }
             Target::WindowsX8664 | Target::WindowsI686 => {
-                println!("  sudo apt-get install mingw-w64");
+                print_info("  sudo apt-get install mingw-w64");
             }
             Target::BaremetalArm => {
-                println!("  sudo apt-get install gcc-arm-none-eabi");
+                print_info("  sudo apt-get install gcc-arm-none-eabi");
             }
             Target::Wasm32 => {
-                println!("  Install Emscripten SDK: https://emscripten.org/");
+                print_info("  Install Emscripten SDK: https://emscripten.org/");
             }
             _ => {
-                println!(
+                print_info(&format!(
                     "  Check documentation for {} toolchain",
                     target.display_name()
-                );
+                ));
             }
         }
     }
@@ -292,11 +371,13 @@ impl CrossCompiler {
         &self,
         target: &Target,
         config: Option<&TargetConfig>,
-        build_dir: &Path,
+        deps: &[ResolvedDependency],
+        options: &BuildOptions,
     ) -> Result<()> {
+        let build_dir = options.profile_dir();
         let mut cmd = Command::new("cmake");
-        cmd.current_dir(build_dir);
-        cmd.arg("..");
+        cmd.arg("-S").arg(&self.project_root);
+        cmd.arg("-B").arg(&build_dir);
 
         // Set toolchain file if provided
         if let Some(cfg) = config
@@ -323,73 +404,70 @@ impl CrossCompiler {
         cmd.arg(format!("-DCMAKE_C_COMPILER={}", toolchain[0]));
         cmd.arg(format!("-DCMAKE_CXX_COMPILER={}", toolchain[1]));
 
-        // Add custom flags
         if let Some(cfg) = config {
             for (key, value) in &cfg.env {
                 cmd.env(key, value);
             }
+        }
 
-            if !cfg.flags.cflags.is_empty() {
-                cmd.arg(format!("-DCMAKE_C_FLAGS={}", cfg.flags.cflags.join(" ")));
-            }
-            if !cfg.flags.cxxflags.is_empty() {
-                cmd.arg(format!(
-                    "-DCMAKE_CXX_FLAGS={}",
-                    cfg.flags.cxxflags.join(" ")
-                ));
-            }
+        // Let find_package() see the target's dependencies
+        if !deps.is_empty() {
+            let prefixes: Vec<String> = deps
+                .iter()
+                .map(|dep| dep.path.display().to_string())
+                .collect();
+            cmd.arg(format!("-DCMAKE_PREFIX_PATH={}", prefixes.join(";")));
         }
 
-        let output = cmd.output()?;
-        if !output.status.success() {
-            anyhow::bail!(
-                "CMake configuration failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            );
+        // The profile, features and flags, applied to every target as in a
+        // host build
+        let script = write_dependency_script(&build_dir, deps)?;
+        cmd.arg(format!("-DCMAKE_PROJECT_INCLUDE={}", script.display()));
+        cmd.arg("-UPORTERS_*FEATURE_*");
+        cmd.args(options.cmake_options());
+        options.apply_feature_env(&mut cmd);
+
+        let status = options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to run cmake")?;
+        if !status.success() {
+            anyhow::bail!("CMake configuration failed");
         }
 
         // Build
-        let build_output = Command::new("cmake")
-            .current_dir(build_dir)
+        let mut build = Command::new("cmake");
+        build
             .arg("--build")
-            .arg(".")
-            .output()?;
-
-        if !build_output.status.success() {
-            anyhow::bail!(
-                "Build failed: {}",
-                String::from_utf8_lossy(&build_output.stderr)
-            );
+            .arg(&build_dir)
+            .arg("--config")
+            .arg(options.profile.cmake_build_type());
+        let status = options
+            .diagnostics
+            .run(&mut build)
+            .with_context(|| "Failed to run cmake --build")?;
+        if !status.success() {
+            anyhow::bail!("Build failed");
         }
 
         Ok(())
     }
 
     /// Compile with Make
-    fn compile_make(
-        &self,
-        target: &Target,
-        config: Option<&TargetConfig>,
-        _build_dir: &Path,
-    ) -> Result<()> {
+    fn compile_make(&self, target: &Target, options: &BuildOptions) -> Result<()> {
         let toolchain = target.toolchain();
         let mut cmd = Command::new("make");
         cmd.current_dir(&self.project_root);
         cmd.arg(format!("CC={}", toolchain[0]));
         cmd.arg(format!("CXX={}", toolchain[1]));
-
-        if let Some(cfg) = config {
-            if !cfg.flags.cflags.is_empty() {
-                cmd.arg(format!("CFLAGS={}", cfg.flags.cflags.join(" ")));
-            }
-            if !cfg.flags.cxxflags.is_empty() {
-                cmd.arg(format!("CXXFLAGS={}", cfg.flags.cxxflags.join(" ")));
-            }
-        }
-
-        let output = cmd.output()?;
-        if !output.status.success() {
-            anyhow::bail!("Make failed: {}", String::from_utf8_lossy(&output.stderr));
+        options.apply_env(&mut cmd);
+
+        let status = options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to run make")?;
+        if !status.success() {
+            anyhow::bail!("Make failed");
         }
 
         Ok(())
@@ -400,44 +478,48 @@ impl CrossCompiler {
         &self,
         target: &Target,
         config: Option<&TargetConfig>,
-        build_dir: &Path,
+        options: &BuildOptions,
     ) -> Result<()> {
         // Meson requires cross-file for cross-compilation
         let cross_file = self.generate_meson_cross_file(target, config)?;
+        let build_dir = options.profile_dir();
 
         let mut cmd = Command::new("meson");
+        cmd.current_dir(&self.project_root);
         cmd.arg("setup");
-        cmd.arg(build_dir);
+        cmd.arg(&build_dir);
         cmd.arg("--cross-file");
         cmd.arg(&cross_file);
-
-        let output = cmd.output()?;
-        if !output.status.success() {
-            anyhow::bail!(
-                "Meson setup failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            );
+        // Options on the command line apply to the target machine
+        cmd.args(
+            MesonBuildSystem::new(&self.project_root.to_string_lossy(), options.clone())
+                .meson_options(),
+        );
+        options.apply_feature_env(&mut cmd);
+
+        let status = options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to run meson setup")?;
+        if !status.success() {
+            anyhow::bail!("Meson setup failed");
         }
 
-        let build_output = Command::new("ninja").current_dir(build_dir).output()?;
-
-        if !build_output.status.success() {
-            anyhow::bail!(
-                "Ninja build failed: {}",
-                String::from_utf8_lossy(&build_output.stderr)
-            );
+        let mut build = Command::new("ninja");
+        build.current_dir(&build_dir);
+        let status = options
+            .diagnostics
+            .run(&mut build)
+            .with_context(|| "Failed to run ninja")?;
+        if !status.success() {
+            anyhow::bail!("Ninja build failed");
         }
 
         Ok(())
     }
 
     /// Compile with XMake
-    fn compile_xmake(
-        &self,
-        target: &Target,
-        _config: Option<&TargetConfig>,
-        _build_dir: &Path,
-    ) -> Result<()> {
+    fn compile_xmake(&self, target: &Target, options: &BuildOptions) -> Result<()> {
         let mut cmd = Command::new("xmake");
         cmd.current_dir(&self.project_root);
         cmd.arg("f");
@@ -445,24 +527,28 @@ impl CrossCompiler {
         cmd.arg(self.get_xmake_platform(target));
         cmd.arg("-a");
         cmd.arg(self.get_xmake_arch(target));
-
-        let output = cmd.output()?;
-        if !output.status.success() {
-            anyhow::bail!(
-                "XMake config failed: {}",
-                String::from_utf8_lossy(&output.stderr)
-            );
+        cmd.args(
+            XMakeBuildSystem::new(&self.project_root.to_string_lossy(), options.clone())
+                .config_flags(),
+        );
+        options.apply_feature_env(&mut cmd);
+
+        let status = options
+            .diagnostics
+            .run(&mut cmd)
+            .with_context(|| "Failed to run xmake config")?;
+        if !status.success() {
+            anyhow::bail!("XMake config failed");
         }
 
-        let build_output = Command::new("xmake")
-            .current_dir(&self.project_root)
-            .output()?;
-
-        if !build_output.status.success() {
-            anyhow::bail!(
-                "XMake build failed: {}",
-                String::from_utf8_lossy(&build_output.stderr)
-            );
+        let mut build = Command::new("xmake");
+        build.current_dir(&self.project_root);
+        let status = options
+            .diagnostics
+            .run(&mut build)
+            .with_context(|| "Failed to run xmake")?;
+        if !status.success() {
+            anyhow::bail!("XMake build failed");
         }
 
         Ok(())
@@ -0,0 +1,628 @@
+//! Archive fetcher for tarball and zip dependencies
+//!
+//! Many C libraries are released as tarballs rather than Git repositories.
+//! An archive dependency (`url = "https://…/zlib-1.3.1.tar.gz"`) is
+//! downloaded, verified against its pinned `sha256`, unpacked (dropping the
+//! `strip-prefix` directory, if given) and kept in the global package cache
+//! under the archive's hash, so each archive is only downloaded once.
+
+use anyhow::{Context, Result, anyhow, bail};
+use sha2::{Digest, Sha256};
+use std::io::{Cursor, Read};
+use std::path::{Component, Path, PathBuf};
+
+use super::git::sanitize;
+use crate::cache::GlobalCache;
+use crate::util::pretty::*;
+
+/// An archive dependency source as written in porters.toml
+#[derive(Debug, Clone, Copy)]
+pub struct ArchiveSource<'a> {
+    pub url: &'a str,
+    /// Expected SHA-256 hash of the archive file
+    pub sha256: Option<&'a str>,
+    /// Leading directory removed from every entry of the archive
+    pub strip_prefix: Option<&'a str>,
+}
+
+/// Archive formats that can be unpacked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    TarGz,
+    Tar,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Format implied by the file name of a URL
+    pub fn from_url(url: &str) -> Option<Self> {
+        let file = url.split(['?', '#']).next().unwrap_or(url).to_lowercase();
+        if file.ends_with(".tar.gz") || file.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if file.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else if file.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else {
+            None
+        }
+    }
+
+    /// Format recognized from the first bytes of an archive
+    pub(crate) fn sniff(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0x1f, 0x8b]) {
+            Some(ArchiveFormat::TarGz)
+        } else if data.starts_with(b"PK\x03\x04") {
+            Some(ArchiveFormat::Zip)
+        } else if data.get(257..262) == Some(b"ustar".as_slice()) {
+            Some(ArchiveFormat::Tar)
+        } else {
+            None
+        }
+    }
+
+    /// File extension of the format
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::Zip => "zip",
+        }
+    }
+}
+
+/// An unpacked archive
+#[derive(Debug, Clone)]
+pub struct ArchiveCheckout {
+    /// SHA-256 hash of the archive file
+    pub sha256: String,
+    /// Directory holding the unpacked sources
+    pub path: PathBuf,
+}
+
+/// Downloads archive dependencies and unpacks them into the package cache
+///
+/// Unpacked archives go to the global cache; when it is disabled they are
+/// kept under `<cache>/archives/src` instead.
+pub struct ArchiveFetcher {
+    root: PathBuf,
+    offline: bool,
+    global: GlobalCache,
+}
+
+impl ArchiveFetcher {
+    pub fn new(cache_dir: &Path, offline: bool) -> Self {
+        let root = cache_dir.join("archives");
+        let global =
+            GlobalCache::new().unwrap_or_else(|_| GlobalCache::with_dir(root.clone(), false));
+        Self {
+            root,
+            offline,
+            global,
+        }
+    }
+
+    /// Download, verify and unpack an archive, reusing a cached copy
+    pub async fn fetch(&self, name: &str, source: &ArchiveSource<'_>) -> Result<ArchiveCheckout> {
+        let expected = source.sha256.map(normalize_sha256);
+        if let Some(sha256) = &expected
+            && let Some(path) = self.cached(name, sha256, source.strip_prefix)
+        {
+            return Ok(ArchiveCheckout {
+                sha256: sha256.clone(),
+                path,
+            });
+        }
+
+        // Local archives, such as those of a registry mirror, need no network
+        if self.offline && !source.url.starts_with("file://") {
+            bail!(
+                "🔒 {} ({}) is not cached and network access is disabled",
+                name,
+                source.url
+            );
+        }
+
+        print_info(&format!("⬇️  Downloading {}...", source.url));
+        let data = download(source.url)
+            .await
+            .with_context(|| format!("Failed to download {} from {}", name, source.url))?;
+
+        let sha256 = hex::encode(Sha256::digest(&data));
+        match &expected {
+            Some(expected) if *expected != sha256 => bail!(
+                "Checksum mismatch for {} ({}):\n  expected: {}\n  actual:   {}",
+                name,
+                source.url,
+                expected,
+                sha256
+            ),
+            Some(_) => {}
+            None => {
+                print_warning(&format!(
+                    "{} has no sha256 in porters.toml; the downloaded archive has sha256 {}",
+                    name, sha256
+                ));
+                // The same archive may already be unpacked under its hash
+                if let Some(path) = self.cached(name, &sha256, source.strip_prefix) {
+                    return Ok(ArchiveCheckout { sha256, path });
+                }
+            }
+        }
+
+        let format = ArchiveFormat::from_url(source.url)
+            .or_else(|| ArchiveFormat::sniff(&data))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unsupported archive format: {} (expected .tar.gz, .tgz, .tar or .zip)",
+                    source.url
+                )
+            })?;
+
+        let key = cache_key(&sha256, source.strip_prefix);
+        let staging = self
+            .root
+            .join("tmp")
+            .join(format!("{}-{}", sanitize(name), key));
+        if staging.exists() {
+            std::fs::remove_dir_all(&staging)?;
+        }
+        std::fs::create_dir_all(&staging)?;
+
+        print_info(&format!("📦 Unpacking {}...", name));
+        unpack(&data, format, source.strip_prefix, &staging)
+            .with_context(|| format!("Failed to unpack {}", source.url))?;
+
+        let path = if self.global.is_enabled() {
+            self.global.store_package(name, &key, &staging)?;
+            std::fs::remove_dir_all(&staging)?;
+            self.global.get_package_cache_path(name, &key)
+        } else {
+            let dest = self.local_path(name, &key);
+            if dest.exists() {
+                std::fs::remove_dir_all(&dest)?;
+            }
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::rename(&staging, &dest)
+                .with_context(|| format!("Failed to create {}", dest.display()))?;
+            dest
+        };
+
+        Ok(ArchiveCheckout { sha256, path })
+    }
+
+    /// Directory of an archive that was already unpacked
+    fn cached(&self, name: &str, sha256: &str, strip_prefix: Option<&str>) -> Option<PathBuf> {
+        let key = cache_key(sha256, strip_prefix);
+        if self.global.has_package(name, &key) {
+            return Some(self.global.get_package_cache_path(name, &key));
+        }
+        let local = self.local_path(name, &key);
+        local.exists().then_some(local)
+    }
+
+    fn local_path(&self, name: &str, key: &str) -> PathBuf {
+        self.root
+            .join("src")
+            .join(format!("{}-{}", sanitize(name), key))
+    }
+}
+
+/// Version number in an archive's file name (`zlib-1.3.1.tar.gz` → `1.3.1`)
+pub fn version_from_url(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let file = path.rsplit('/').next()?;
+    let re = regex::Regex::new(r"\d+(?:\.\d+)+").ok()?;
+    re.find(file).map(|m| m.as_str().to_string())
+}
+
+/// Strip a `sha256:` prefix and normalize case
+pub(crate) fn normalize_sha256(value: &str) -> String {
+    let value = value.trim();
+    value
+        .strip_prefix("sha256:")
+        .unwrap_or(value)
+        .to_ascii_lowercase()
+}
+
+/// Cache version of an unpacked archive (`sha256-<hash>[-<prefix>]`)
+fn cache_key(sha256: &str, strip_prefix: Option<&str>) -> String {
+    let mut key = format!("sha256-{}", &sha256[..sha256.len().min(16)]);
+    if let Some(prefix) = strip_prefix {
+        key.push('-');
+        key.push_str(&sanitize(prefix.trim_matches('/')));
+    }
+    key
+}
+
+/// Read an archive from an `http(s)://` or `file://` URL
+pub(crate) async fn download(url: &str) -> Result<Vec<u8>> {
+    if url.starts_with("file://") {
+        let path = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|u| u.to_file_path().ok())
+            .ok_or_else(|| anyhow!("Invalid file URL: {}", url))?;
+        return std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()));
+    }
+
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        bail!(
+            "Unsupported archive URL '{}' (expected http://, https:// or file://)",
+            url
+        );
+    }
+
+    let response = reqwest::Client::new()
+        .get(url)
+        .header("User-Agent", "porters")
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// Unpack an archive into `dest`, keeping only entries under `strip_prefix`
+fn unpack(
+    data: &[u8],
+    format: ArchiveFormat,
+    strip_prefix: Option<&str>,
+    dest: &Path,
+) -> Result<()> {
+    let unpacked = match format {
+        ArchiveFormat::TarGz => unpack_tar(
+            tar::Archive::new(flate2::read::GzDecoder::new(data)),
+            strip_prefix,
+            dest,
+        )?,
+        ArchiveFormat::Tar => unpack_tar(tar::Archive::new(data), strip_prefix, dest)?,
+        ArchiveFormat::Zip => unpack_zip(data, strip_prefix, dest)?,
+    };
+
+    if unpacked == 0 {
+        match strip_prefix {
+            Some(prefix) => bail!(
+                "No entries of the archive are under strip-prefix '{}'",
+                prefix
+            ),
+            None => bail!("The archive is empty"),
+        }
+    }
+    Ok(())
+}
+
+/// Unpack a tar archive into `dest`
+///
+/// Links are unpacked too, so nothing may be written or linked through one:
+/// entries are refused when a directory on their way is a symbolic link,
+/// symbolic links must be relative and stay inside `dest`, and hard links
+/// must name another entry.
+fn unpack_tar<R: Read>(
+    mut archive: tar::Archive<R>,
+    strip_prefix: Option<&str>,
+    dest: &Path,
+) -> Result<usize> {
+    let dest = dest
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {}", dest.display()))?;
+    let mut unpacked = 0;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let kind = entry.header().entry_type();
+        if kind.is_pax_global_extensions() {
+            continue;
+        }
+
+        let path = entry.path()?.into_owned();
+        let Some(relative) = relative_path(&path, strip_prefix)? else {
+            continue;
+        };
+        let target = prepare_target(&dest, &relative)?;
+
+        if kind.is_hard_link() {
+            // Hard links name another entry of the archive
+            let link = entry
+                .link_name()?
+                .ok_or_else(|| anyhow!("Hard link '{}' has no target", path.display()))?
+                .into_owned();
+            if let Some(source) = relative_path(&link, strip_prefix)? {
+                check_no_links(&dest, &source)?;
+                std::fs::copy(dest.join(source), &target)
+                    .with_context(|| format!("Failed to unpack {}", path.display()))?;
+            }
+        } else {
+            if kind.is_symlink() {
+                let link = entry
+                    .link_name()?
+                    .ok_or_else(|| anyhow!("Symbolic link '{}' has no target", path.display()))?;
+                if !link_stays_inside(&relative, &link) {
+                    bail!(
+                        "Archive entry '{}' links to '{}', outside the archive",
+                        path.display(),
+                        link.display()
+                    );
+                }
+            }
+            entry
+                .unpack(&target)
+                .with_context(|| format!("Failed to unpack {}", path.display()))?;
+        }
+        unpacked += 1;
+    }
+    Ok(unpacked)
+}
+
+fn unpack_zip(data: &[u8], strip_prefix: Option<&str>, dest: &Path) -> Result<usize> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))?;
+    let mut unpacked = 0;
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        let path = file
+            .enclosed_name()
+            .ok_or_else(|| anyhow!("Archive entry '{}' points outside the archive", file.name()))?;
+        let Some(relative) = relative_path(&path, strip_prefix)? else {
+            continue;
+        };
+        let target = dest.join(relative);
+
+        if file.is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else {
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut out = std::fs::File::create(&target)?;
+            std::io::copy(&mut file, &mut out)
+                .with_context(|| format!("Failed to unpack {}", path.display()))?;
+
+            #[cfg(unix)]
+            if let Some(mode) = file.unix_mode() {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&target, std::fs::Permissions::from_mode(mode & 0o777))?;
+            }
+        }
+        unpacked += 1;
+    }
+    Ok(unpacked)
+}
+
+/// Where an entry at `relative` is unpacked, once its directory exists
+///
+/// Fails if the entry would be written through a symbolic link unpacked
+/// earlier, which could point anywhere.
+fn prepare_target(dest: &Path, relative: &Path) -> Result<PathBuf> {
+    check_no_links(dest, relative)?;
+    let target = dest.join(relative);
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+        let parent = parent.canonicalize()?;
+        if !parent.starts_with(dest) {
+            bail!(
+                "Archive entry '{}' points outside the archive",
+                relative.display()
+            );
+        }
+    }
+    // Replace a link left by an earlier entry instead of writing through it
+    if target.is_symlink() {
+        std::fs::remove_file(&target)?;
+    }
+    Ok(target)
+}
+
+/// Fail if a directory on the way to `relative` is a symbolic link
+fn check_no_links(dest: &Path, relative: &Path) -> Result<()> {
+    let mut path = dest.to_path_buf();
+    for component in relative.parent().into_iter().flat_map(Path::components) {
+        path.push(component);
+        if path.is_symlink() {
+            bail!(
+                "Archive entry '{}' goes through the symbolic link '{}'",
+                relative.display(),
+                path.strip_prefix(dest).unwrap_or(&path).display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Whether a symbolic link at `relative` pointing to `link` stays inside
+/// the archive
+///
+/// `..` may only lead the link, so it can't climb back out of a directory
+/// that is itself a link.
+fn link_stays_inside(relative: &Path, link: &Path) -> bool {
+    let mut depth = relative.components().count().saturating_sub(1);
+    let mut leading = true;
+    for component in link.components() {
+        match component {
+            Component::ParentDir if leading && depth > 0 => depth -= 1,
+            Component::Normal(_) => leading = false,
+            Component::CurDir => {}
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Path of an archive entry below `strip_prefix`, or `None` if it lies outside it
+fn relative_path(path: &Path, strip_prefix: Option<&str>) -> Result<Option<PathBuf>> {
+    let mut relative = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            _ => bail!(
+                "Archive entry '{}' points outside the archive",
+                path.display()
+            ),
+        }
+    }
+
+    let relative = match strip_prefix {
+        Some(prefix) => match relative.strip_prefix(prefix.trim_matches('/')) {
+            Ok(rest) => rest.to_path_buf(),
+            Err(_) => return Ok(None),
+        },
+        None => relative,
+    };
+    Ok((!relative.as_os_str().is_empty()).then_some(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn tarball(path: &Path) -> String {
+        let file = std::fs::File::create(path).unwrap();
+        let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        for (name, content) in [
+            ("zlib-1.3.1/zlib.h", "header"),
+            ("zlib-1.3.1/src/deflate.c", "source"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+        hex::encode(Sha256::digest(std::fs::read(path).unwrap()))
+    }
+
+    fn fetcher(temp: &TempDir, offline: bool) -> ArchiveFetcher {
+        ArchiveFetcher {
+            root: temp.path().join("cache/archives"),
+            offline,
+            global: GlobalCache::with_dir(temp.path().join("global"), true),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_tarball_with_strip_prefix() {
+        let temp = TempDir::new().unwrap();
+        let archive = temp.path().join("zlib-1.3.1.tar.gz");
+        let sha256 = tarball(&archive);
+        let url = reqwest::Url::from_file_path(&archive).unwrap().to_string();
+
+        let pinned = format!("sha256:{}", sha256.to_uppercase());
+        let source = ArchiveSource {
+            url: &url,
+            sha256: Some(&pinned),
+            strip_prefix: Some("zlib-1.3.1"),
+        };
+        let checkout = fetcher(&temp, false).fetch("zlib", &source).await.unwrap();
+        assert_eq!(checkout.sha256, sha256);
+        assert!(checkout.path.starts_with(temp.path().join("global/zlib")));
+        assert_eq!(
+            std::fs::read_to_string(checkout.path.join("src/deflate.c")).unwrap(),
+            "source"
+        );
+        assert!(!checkout.path.join("zlib-1.3.1").exists());
+
+        let wrong = "0".repeat(64);
+        let source = ArchiveSource {
+            url: &url,
+            sha256: Some(&wrong),
+            strip_prefix: None,
+        };
+        let err = fetcher(&temp, false)
+            .fetch("zlib", &source)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"));
+
+        // The pinned archive is served from the cache without downloading
+        std::fs::remove_file(&archive).unwrap();
+        let source = ArchiveSource {
+            url: &url,
+            sha256: Some(&sha256),
+            strip_prefix: Some("zlib-1.3.1/"),
+        };
+        let cached = fetcher(&temp, true).fetch("zlib", &source).await.unwrap();
+        assert_eq!(cached.path, checkout.path);
+
+        assert_eq!(version_from_url(&url).as_deref(), Some("1.3.1"));
+    }
+
+    #[test]
+    fn test_unpack_zip() {
+        let temp = TempDir::new().unwrap();
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file(
+                "pkg/include/foo.h",
+                zip::write::SimpleFileOptions::default(),
+            )
+            .unwrap();
+        writer.write_all(b"foo").unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        assert_eq!(ArchiveFormat::sniff(&data), Some(ArchiveFormat::Zip));
+        unpack(&data, ArchiveFormat::Zip, Some("pkg"), temp.path()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(temp.path().join("include/foo.h")).unwrap(),
+            "foo"
+        );
+        assert!(unpack(&data, ArchiveFormat::Zip, Some("other"), temp.path()).is_err());
+
+        assert!(relative_path(Path::new("../etc/passwd"), None).is_err());
+        assert_eq!(
+            ArchiveFormat::from_url("https://x.org/a.tgz?dl=1"),
+            Some(ArchiveFormat::TarGz)
+        );
+    }
+
+    fn tar_with_link(link: &Path, file: &str) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        header.set_mode(0o777);
+        builder.append_link(&mut header, "pkg/x", link).unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, file, &b"evil"[..])
+            .unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_unpack_tar_rejects_escaping_links() {
+        let temp = TempDir::new().unwrap();
+        let outside = temp.path().join("outside");
+        std::fs::create_dir(&outside).unwrap();
+
+        for link in [outside.clone(), PathBuf::from("../outside")] {
+            let dest = temp.path().join("dest");
+            std::fs::create_dir_all(&dest).unwrap();
+            let data = tar_with_link(&link, "pkg/x/passwd");
+            let err = unpack(&data, ArchiveFormat::Tar, Some("pkg"), &dest).unwrap_err();
+            assert!(err.to_string().contains("outside the archive"), "{err}");
+            assert!(!outside.join("passwd").exists());
+            std::fs::remove_dir_all(&dest).unwrap();
+        }
+
+        // A link inside the archive is kept, but nothing is written through it
+        let dest = temp.path().join("dest");
+        std::fs::create_dir_all(dest.join("sub")).unwrap();
+        let data = tar_with_link(Path::new("sub"), "pkg/x/passwd");
+        let err = unpack(&data, ArchiveFormat::Tar, Some("pkg"), &dest).unwrap_err();
+        assert!(err.to_string().contains("symbolic link"), "{err}");
+        assert!(!dest.join("sub/passwd").exists());
+
+        let data = tar_with_link(Path::new("sub"), "pkg/lib/libz.so.1");
+        unpack(&data, ArchiveFormat::Tar, Some("pkg"), &dest).unwrap();
+        assert!(dest.join("x").is_symlink());
+        assert!(dest.join("lib/libz.so.1").exists());
+    }
+}
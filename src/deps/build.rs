@@ -0,0 +1,559 @@
+//! Building registry dependencies from source
+//!
+//! Registry packages declare build `options` (`shared`, `tests`, ...) with
+//! their default values. Before a project is built, every registry
+//! dependency with a supported build system is configured with those
+//! options, as overridden by `options = { ... }` in porters.toml, then built
+//! and installed into a prefix in the global cache. Headers and libraries
+//! are used from that prefix, and the CMake packages installed there can be
+//! found with `find_package`.
+//!
+//! A registry option is either a value (`"shared": false`) or a table that
+//! names the option for each build system:
+//! `"external_fmt": { "default": true, "cmake": "SPDLOG_FMT_EXTERNAL" }`.
+
+use anyhow::{Context, Result, bail};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use super::ResolvedDependency;
+use crate::build::diagnostics::Diagnostics;
+use crate::build::profile::Profile;
+use crate::registry::{CmakeInstall, PackageDefinition};
+use crate::util::pretty::*;
+
+/// Build systems registry dependencies can be built with
+pub const BUILD_SYSTEMS: &[&str] = &["cmake", "meson", "autotools", "xmake"];
+
+/// Written into a build directory once the dependency is installed
+const BUILT_MARKER: &str = ".porters-built";
+
+/// A build option of a registry package
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildOption {
+    pub value: serde_json::Value,
+    /// Name of the option for each build system, if it isn't the usual one
+    pub names: BTreeMap<String, String>,
+}
+
+impl BuildOption {
+    /// Parse an entry of a registry package's `options`
+    fn from_registry(value: &serde_json::Value) -> Self {
+        match value.as_object() {
+            Some(table) if table.contains_key("default") => Self {
+                value: table["default"].clone(),
+                names: table
+                    .iter()
+                    .filter(|(key, _)| BUILD_SYSTEMS.contains(&key.as_str()))
+                    .filter_map(|(key, name)| Some((key.clone(), name.as_str()?.to_string())))
+                    .collect(),
+            },
+            _ => Self {
+                value: value.clone(),
+                names: BTreeMap::new(),
+            },
+        }
+    }
+}
+
+/// How a registry package is built from source
+#[derive(Debug, Clone, Default)]
+pub struct SourceBuild {
+    /// Build system of the package (`cmake`, `meson`, ...)
+    pub build_system: String,
+    pub options: BTreeMap<String, BuildOption>,
+    /// CMake package the installed dependency provides
+    pub cmake: Option<CmakeInstall>,
+    /// Install prefix, once the dependency is built
+    pub prefix: Option<PathBuf>,
+}
+
+impl SourceBuild {
+    pub fn new(pkg: &PackageDefinition) -> Self {
+        Self {
+            build_system: pkg.build_system.clone(),
+            options: pkg
+                .options
+                .iter()
+                .map(|(name, value)| (name.clone(), BuildOption::from_registry(value)))
+                .collect(),
+            cmake: pkg.cmake_install(),
+            prefix: None,
+        }
+    }
+
+    /// Apply the `options` of a dependency in porters.toml
+    ///
+    /// Options the registry doesn't declare are passed on with a warning,
+    /// under their usual names.
+    pub fn override_options(&mut self, package: &str, overrides: &toml::Table) -> Result<()> {
+        for (name, value) in overrides {
+            let value = match value {
+                toml::Value::Boolean(b) => serde_json::Value::from(*b),
+                toml::Value::String(s) => serde_json::Value::from(s.clone()),
+                toml::Value::Integer(i) => serde_json::Value::from(*i),
+                toml::Value::Float(f) => serde_json::Value::from(*f),
+                _ => bail!(
+                    "Option '{}' of {} must be a boolean, string or number",
+                    name,
+                    package
+                ),
+            };
+            match self.options.get_mut(name) {
+                Some(option) => option.value = value,
+                None => {
+                    print_warning(&format!(
+                        "{} doesn't declare a build option '{}'; passing it on anyway",
+                        package, name
+                    ));
+                    self.options.insert(
+                        name.clone(),
+                        BuildOption {
+                            value,
+                            names: BTreeMap::new(),
+                        },
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The package is used from its headers instead of being built
+    pub fn header_only(&self) -> bool {
+        self.options
+            .get("header_only")
+            .is_some_and(|option| option.value == serde_json::Value::Bool(true))
+    }
+
+    /// Arguments passed to the package's build system when configuring it
+    pub fn configure_args(&self) -> Vec<String> {
+        let system = self.build_system.as_str();
+        let mut args = Vec::new();
+
+        for (name, option) in &self.options {
+            let renamed = option.names.get(system);
+            // Handled by building (or not building) the package at all
+            if renamed.is_none() && name == "header_only" {
+                continue;
+            }
+
+            let flag = match (system, renamed, &option.value) {
+                ("cmake", _, value) => {
+                    let var = match (renamed, name.as_str()) {
+                        (Some(var), _) => var.clone(),
+                        (None, "shared") => "BUILD_SHARED_LIBS".to_string(),
+                        (None, "tests") => "BUILD_TESTING".to_string(),
+                        (None, "examples") => "BUILD_EXAMPLES".to_string(),
+                        (None, "benchmarks") => "BUILD_BENCHMARKS".to_string(),
+                        // Implied by BUILD_SHARED_LIBS
+                        (None, "static") => continue,
+                        (None, name) => name.to_uppercase(),
+                    };
+                    format!("-D{}={}", var, option_value(value, "ON", "OFF"))
+                }
+                ("meson", None, serde_json::Value::Bool(shared)) if name == "shared" => {
+                    let kind = if *shared { "shared" } else { "static" };
+                    format!("-Ddefault_library={}", kind)
+                }
+                ("meson", None, _) if name == "static" => continue,
+                ("meson", _, value) => format!(
+                    "-D{}={}",
+                    renamed.unwrap_or(name),
+                    option_value(value, "true", "false")
+                ),
+                ("autotools", _, serde_json::Value::Bool(enabled)) => {
+                    let feature = renamed.cloned().unwrap_or_else(|| name.replace('_', "-"));
+                    let action = if *enabled { "enable" } else { "disable" };
+                    format!("--{}-{}", action, feature)
+                }
+                ("autotools", _, value) => {
+                    let package = renamed.cloned().unwrap_or_else(|| name.replace('_', "-"));
+                    format!("--with-{}={}", package, option_value(value, "yes", "no"))
+                }
+                ("xmake", None, serde_json::Value::Bool(shared)) if name == "shared" => {
+                    let kind = if *shared { "shared" } else { "static" };
+                    format!("--kind={}", kind)
+                }
+                ("xmake", None, _) if name == "static" => continue,
+                ("xmake", _, value) => format!(
+                    "--{}={}",
+                    renamed.unwrap_or(name),
+                    option_value(value, "y", "n")
+                ),
+                _ => continue,
+            };
+            args.push(flag);
+        }
+
+        args
+    }
+}
+
+/// An option value as written on a command line
+fn option_value(value: &serde_json::Value, on: &str, off: &str) -> String {
+    match value {
+        serde_json::Value::Bool(true) => on.to_string(),
+        serde_json::Value::Bool(false) => off.to_string(),
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// File at the root of a package's sources that its build system needs
+fn build_file(build_system: &str) -> &'static str {
+    match build_system {
+        "cmake" => "CMakeLists.txt",
+        "meson" => "meson.build",
+        "autotools" => "configure",
+        _ => "xmake.lua",
+    }
+}
+
+/// Build and install the registry dependencies that aren't header-only
+///
+/// `deps` must be in build order, as returned by the resolver, so each
+/// package can find the ones it depends on. They are built with the
+/// optimization and debug settings of the project's `profile`, their output
+/// streamed through `diagnostics`. Builds are
+/// cached per package version, configure arguments, profile and the builds
+/// of the packages before it.
+pub fn build_dependencies(
+    deps: &mut [ResolvedDependency],
+    cache_dir: &Path,
+    profile: &Profile,
+    diagnostics: &Diagnostics,
+) -> Result<()> {
+    let mut prefixes: Vec<PathBuf> = Vec::new();
+
+    for dep in deps.iter_mut() {
+        let Some(mut build) = dep.build.take() else {
+            continue;
+        };
+        if BUILD_SYSTEMS.contains(&build.build_system.as_str()) && !build.header_only() {
+            if dep.path.join(build_file(&build.build_system)).exists() {
+                let prefix =
+                    build_dependency(dep, &build, cache_dir, profile, diagnostics, &prefixes)?;
+                install_paths(dep, &prefix);
+                prefixes.push(prefix.clone());
+                build.prefix = Some(prefix);
+            } else {
+                print_warning(&format!(
+                    "{} has no {} at its root; using its headers without building it",
+                    dep.name,
+                    build_file(&build.build_system)
+                ));
+            }
+        }
+        dep.build = Some(build);
+    }
+
+    Ok(())
+}
+
+/// Build a dependency unless an identical build is cached, returning its
+/// install prefix
+fn build_dependency(
+    dep: &ResolvedDependency,
+    build: &SourceBuild,
+    cache_dir: &Path,
+    profile: &Profile,
+    diagnostics: &Diagnostics,
+    prefixes: &[PathBuf],
+) -> Result<PathBuf> {
+    let args = build.configure_args();
+    let profile_args = profile_args(&build.build_system, profile);
+
+    let mut hasher = Sha256::new();
+    hasher.update(dep.checksum.as_deref().unwrap_or_default());
+    hasher.update(dep.path.to_string_lossy().as_bytes());
+    // Upstream prefixes are named after their own keys, so rebuilding a
+    // dependency rebuilds everything that depends on it
+    for arg in args
+        .iter()
+        .chain(&profile_args)
+        .map(String::as_str)
+        .chain(prefixes.iter().filter_map(|p| p.to_str()))
+    {
+        hasher.update(b"\n");
+        hasher.update(arg.as_bytes());
+    }
+    let key = hex::encode(hasher.finalize());
+
+    let dir = cache_dir
+        .join("builds")
+        .join(format!("{}-{}-{}", dep.name, dep.version, &key[..12]));
+    let prefix = dir.join("install");
+    if dir.join(BUILT_MARKER).exists() {
+        return Ok(prefix);
+    }
+
+    print_build(&format!(
+        "Building {} v{} from source ({} profile)...",
+        dep.name, dep.version, profile.name
+    ));
+    if !args.is_empty() {
+        print_info(&format!("Options: {}", args.join(" ")));
+    }
+    if dir.exists() {
+        fs::remove_dir_all(&dir).with_context(|| format!("Failed to remove {}", dir.display()))?;
+    }
+    let build_dir = dir.join("build");
+    fs::create_dir_all(&build_dir)
+        .with_context(|| format!("Failed to create {}", build_dir.display()))?;
+
+    let jobs = num_cpus::get().to_string();
+    let source = dep.path.as_path();
+    let steps: Vec<Command> = match build.build_system.as_str() {
+        "cmake" => {
+            let mut configure = Command::new("cmake");
+            configure
+                .arg("-S")
+                .arg(source)
+                .arg("-B")
+                .arg(&build_dir)
+                .arg(format!("-DCMAKE_INSTALL_PREFIX={}", prefix.display()))
+                .arg("-DCMAKE_INSTALL_LIBDIR=lib")
+                .args(&profile_args)
+                .args(&args);
+            let build_type = profile.cmake_build_type();
+            let mut compile = Command::new("cmake");
+            compile.arg("--build").arg(&build_dir).args([
+                "--config",
+                build_type,
+                "--parallel",
+                &jobs,
+            ]);
+            let mut install = Command::new("cmake");
+            install
+                .arg("--install")
+                .arg(&build_dir)
+                .args(["--config", build_type]);
+            vec![configure, compile, install]
+        }
+        "meson" => {
+            let mut configure = Command::new("meson");
+            configure
+                .arg("setup")
+                .arg(&build_dir)
+                .arg(source)
+                .arg(format!("--prefix={}", prefix.display()))
+                .arg("--libdir=lib")
+                .args(&profile_args)
+                .args(&args);
+            let mut compile = Command::new("meson");
+            compile.arg("compile").arg("-C").arg(&build_dir);
+            let mut install = Command::new("meson");
+            install.arg("install").arg("-C").arg(&build_dir);
+            vec![configure, compile, install]
+        }
+        "autotools" => {
+            // Out-of-tree, so the cached sources stay untouched
+            let mut configure = Command::new(source.join("configure"));
+            configure
+                .current_dir(&build_dir)
+                .arg(format!("--prefix={}", prefix.display()))
+                .arg(format!("--libdir={}", prefix.join("lib").display()))
+                .args(&profile_args)
+                .args(&args);
+            let mut compile = Command::new("make");
+            compile.current_dir(&build_dir).arg(format!("-j{}", jobs));
+            let mut install = Command::new("make");
+            install.current_dir(&build_dir).arg("install");
+            vec![configure, compile, install]
+        }
+        _ => {
+            let mut configure = Command::new("xmake");
+            configure
+                .arg("f")
+                .arg("-P")
+                .arg(source)
+                .arg("-o")
+                .arg(&build_dir)
+                .arg("-y")
+                .args(&profile_args)
+                .args(&args);
+            let mut compile = Command::new("xmake");
+            compile.arg("build").arg("-P").arg(source);
+            let mut install = Command::new("xmake");
+            install
+                .arg("install")
+                .arg("-P")
+                .arg(source)
+                .arg("-o")
+                .arg(&prefix);
+            vec![configure, compile, install]
+        }
+    };
+
+    for mut step in steps {
+        // Packages find the dependencies built before them
+        if !prefixes.is_empty() {
+            step.env("CMAKE_PREFIX_PATH", std::env::join_paths(prefixes)?);
+            step.env(
+                "PKG_CONFIG_PATH",
+                std::env::join_paths(prefixes.iter().map(|p| p.join("lib").join("pkgconfig")))?,
+            );
+        }
+        // xmake keeps its configuration next to the sources otherwise
+        step.env("XMAKE_CONFIGDIR", dir.join("xmake"));
+
+        let program = step.get_program().to_string_lossy().to_string();
+        let status = diagnostics
+            .run(&mut step)
+            .with_context(|| format!("Failed to run {} for {}", program, dep.name))?;
+        if !status.success() {
+            bail!("Building {} with {} failed", dep.name, build.build_system);
+        }
+    }
+
+    fs::write(dir.join(BUILT_MARKER), args.join("\n"))?;
+    print_success(&format!("Built {} v{}", dep.name, dep.version));
+    Ok(prefix)
+}
+
+/// Configure arguments selecting the profile's optimization and debug info
+///
+/// LTO and sanitizers stay with the project, so dependencies link the same
+/// way whatever the profile.
+fn profile_args(build_system: &str, profile: &Profile) -> Vec<String> {
+    match build_system {
+        "cmake" => vec![format!("-DCMAKE_BUILD_TYPE={}", profile.cmake_build_type())],
+        "meson" => vec![
+            format!("--optimization={}", profile.meson_optimization()),
+            format!("--debug={}", profile.debug),
+        ],
+        "autotools" => {
+            let mut flags = format!("-O{}", profile.opt_level.as_str());
+            if profile.debug {
+                flags.push_str(" -g");
+            }
+            vec![format!("CFLAGS={}", flags), format!("CXXFLAGS={}", flags)]
+        }
+        _ => vec![format!("--mode={}", profile.xmake_mode())],
+    }
+}
+
+/// Use the headers and libraries installed into `prefix`
+fn install_paths(dep: &mut ResolvedDependency, prefix: &Path) {
+    let include = prefix.join("include");
+    if include.is_dir() && !dep.include_paths.contains(&include) {
+        dep.include_paths.insert(0, include);
+    }
+    for lib in ["lib", "lib64"] {
+        let lib = prefix.join(lib);
+        if lib.is_dir() && !dep.lib_paths.contains(&lib) {
+            dep.lib_paths.push(lib);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source_build(build_system: &str) -> SourceBuild {
+        let pkg: PackageDefinition = serde_json::from_value(serde_json::json!({
+            "name": "spdlog",
+            "description": "Fast C++ logging library",
+            "repository": "https://github.com/gabime/spdlog",
+            "version": "1.14.1",
+            "license": "MIT",
+            "build_system": build_system,
+            "options": {
+                "shared": false,
+                "static": true,
+                "header_only": false,
+                "tests": false,
+                "external_fmt": { "default": true, "cmake": "SPDLOG_FMT_EXTERNAL" },
+                "level": "info"
+            }
+        }))
+        .unwrap();
+        SourceBuild::new(&pkg)
+    }
+
+    #[test]
+    fn test_configure_args() {
+        assert_eq!(
+            source_build("cmake").configure_args(),
+            vec![
+                "-DSPDLOG_FMT_EXTERNAL=ON",
+                "-DLEVEL=info",
+                "-DBUILD_SHARED_LIBS=OFF",
+                "-DBUILD_TESTING=OFF",
+            ]
+        );
+        assert_eq!(
+            source_build("meson").configure_args(),
+            vec![
+                "-Dexternal_fmt=true",
+                "-Dlevel=info",
+                "-Ddefault_library=static",
+                "-Dtests=false",
+            ]
+        );
+        assert_eq!(
+            source_build("autotools").configure_args(),
+            vec![
+                "--enable-external-fmt",
+                "--with-level=info",
+                "--disable-shared",
+                "--enable-static",
+                "--disable-tests",
+            ]
+        );
+        assert!(source_build("bazel").configure_args().is_empty());
+    }
+
+    #[test]
+    fn test_override_options() {
+        let mut build = source_build("cmake");
+        let overrides: toml::Table =
+            toml::from_str("shared = true\nheader_only = true\nexternal_fmt = false\nlto = true\n")
+                .unwrap();
+        build.override_options("spdlog", &overrides).unwrap();
+
+        assert!(build.header_only());
+        assert_eq!(
+            build.configure_args(),
+            vec![
+                "-DSPDLOG_FMT_EXTERNAL=OFF",
+                "-DLEVEL=info",
+                "-DLTO=ON",
+                "-DBUILD_SHARED_LIBS=ON",
+                "-DBUILD_TESTING=OFF",
+            ]
+        );
+
+        let invalid: toml::Table = toml::from_str("shared = [true]").unwrap();
+        assert!(build.override_options("spdlog", &invalid).is_err());
+    }
+
+    #[test]
+    fn test_profile_args() {
+        let debug = Profile::resolve("debug", &BTreeMap::new()).unwrap();
+        let release = Profile::resolve("release", &BTreeMap::new()).unwrap();
+
+        assert_eq!(
+            profile_args("cmake", &debug),
+            vec!["-DCMAKE_BUILD_TYPE=Debug"]
+        );
+        assert_eq!(
+            profile_args("cmake", &release),
+            vec!["-DCMAKE_BUILD_TYPE=Release"]
+        );
+        assert_eq!(
+            profile_args("meson", &debug),
+            vec!["--optimization=0", "--debug=true"]
+        );
+        assert_eq!(
+            profile_args("autotools", &release),
+            vec!["CFLAGS=-O3", "CXXFLAGS=-O3"]
+        );
+        assert_eq!(profile_args("xmake", &release), vec!["--mode=release"]);
+    }
+}
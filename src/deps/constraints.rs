@@ -0,0 +1,392 @@
+//! Toolchain constraints of dependencies
+//!
+//! A dependency in porters.toml can declare `constraints` on the toolchain
+//! it is built with: a C++ standard range, supported compilers with version
+//! requirements, CPU architectures and required environment variables.
+//! They are checked against the detected toolchain before building.
+
+use anyhow::{Result, anyhow};
+use std::collections::HashSet;
+use std::process::{Command, Stdio};
+
+use super::{ResolvedDependency, platform};
+use crate::config::{DependencyConstraints, PortersConfig};
+use crate::util::pretty::*;
+use crate::version::{Version, VersionReq};
+
+/// The toolchain a project is built with
+#[derive(Debug, Clone, Default)]
+pub struct Toolchain {
+    /// Compiler family (`gcc`, `clang` or `msvc`) and its version
+    pub compiler: Option<(String, Version)>,
+    /// C++ standard as a year (e.g. 2017 for C++17)
+    pub cpp_standard: Option<u32>,
+    /// Target CPU architecture
+    pub arch: String,
+}
+
+impl Toolchain {
+    /// Detect the C++ compiler and the standard the project compiles with
+    ///
+    /// The compiler is taken from `CXX`, falling back to the first of
+    /// `c++`, `g++` and `clang++` found. The standard comes from a `-std=`
+    /// flag in `[build.flags]`, or else the compiler's default. It is left
+    /// unknown for `-std=c++latest`, which names no particular standard.
+    pub fn detect(config: &PortersConfig) -> Self {
+        let host = platform::host_target();
+        let arch = host.split_once('-').map(|(_, a)| a).unwrap_or(&host);
+
+        let mut candidates: Vec<String> = std::env::var("CXX").into_iter().collect();
+        candidates.extend(["c++", "g++", "clang++", "cl"].map(String::from));
+
+        let mut compiler = None;
+        let mut compiler_cmd = None;
+        for cmd in candidates {
+            if let Some(detected) = detect_compiler(&cmd) {
+                compiler = Some(detected);
+                compiler_cmd = Some(cmd);
+                break;
+            }
+        }
+
+        let std_flag = config
+            .build
+            .flags
+            .cxxflags
+            .iter()
+            .rev()
+            .find_map(|flag| standard_flag(flag));
+        let cpp_standard = match std_flag {
+            Some(standard) => parse_standard(standard),
+            None => compiler.as_ref().and_then(|(family, _)| {
+                if family == "msvc" {
+                    // MSVC defaults to C++14 and doesn't report it through __cplusplus
+                    return Some(2014);
+                }
+                default_standard(compiler_cmd.as_deref()?)
+            }),
+        };
+
+        Self {
+            compiler,
+            cpp_standard,
+            arch: arch.to_string(),
+        }
+    }
+}
+
+/// Check the constraints of the project's resolved dependencies
+///
+/// Constraints come from each package's registry entry and from every
+/// manifest that requires it, so transitive dependencies are checked too.
+/// Returns an error listing every unsatisfied constraint.
+pub fn check_dependency_constraints(
+    config: &PortersConfig,
+    resolved: &[ResolvedDependency],
+) -> Result<()> {
+    if resolved.iter().all(|dep| dep.constraints.is_empty()) {
+        return Ok(());
+    }
+
+    let toolchain = Toolchain::detect(config);
+    let mut problems: Vec<String> = resolved
+        .iter()
+        .flat_map(|dep| {
+            dep.constraints
+                .iter()
+                .flat_map(|constraints| check(&dep.name, constraints, &toolchain))
+        })
+        .collect();
+    // The same constraint can be declared by several manifests
+    let mut seen = HashSet::new();
+    problems.retain(|problem| seen.insert(problem.clone()));
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    Err(anyhow!(
+        "Dependency constraints not satisfied:\n  {}",
+        problems.join("\n  ")
+    ))
+}
+
+/// Check one dependency's constraints against a toolchain
+pub fn check(
+    name: &str,
+    constraints: &DependencyConstraints,
+    toolchain: &Toolchain,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let bounds = [
+        (&constraints.min_cpp_standard, "at least"),
+        (&constraints.max_cpp_standard, "at most"),
+    ];
+    for (bound, relation) in bounds {
+        let Some(bound) = bound else {
+            continue;
+        };
+        let Some(required) = parse_standard(bound) else {
+            problems.push(format!("{}: invalid C++ standard '{}'", name, bound));
+            continue;
+        };
+        let Some(actual) = toolchain.cpp_standard else {
+            print_warning(&format!(
+                "Could not determine the C++ standard to check {}'s constraints",
+                name
+            ));
+            continue;
+        };
+        let ok = match relation {
+            "at least" => actual >= required,
+            _ => actual <= required,
+        };
+        if !ok {
+            problems.push(format!(
+                "{} requires {} {} but the project builds with {}",
+                name,
+                relation,
+                display_standard(required),
+                display_standard(actual)
+            ));
+        }
+    }
+
+    if !constraints.compilers.is_empty() {
+        match &toolchain.compiler {
+            None => problems.push(format!(
+                "{} requires a C++ compiler but none was found",
+                name
+            )),
+            Some((family, version)) => {
+                let requirement = constraints
+                    .compilers
+                    .iter()
+                    .find(|(compiler, _)| compiler_family(compiler) == family);
+                match requirement {
+                    None => {
+                        let mut supported: Vec<&str> =
+                            constraints.compilers.keys().map(|c| c.as_str()).collect();
+                        supported.sort();
+                        problems.push(format!(
+                            "{} supports {} but {} {} was detected",
+                            name,
+                            supported.join(", "),
+                            family,
+                            version
+                        ));
+                    }
+                    Some((compiler, req)) => match VersionReq::parse(req) {
+                        Ok(parsed) if parsed.matches(version) => {}
+                        Ok(_) => problems.push(format!(
+                            "{} requires {} {} but {} was detected",
+                            name, compiler, req, version
+                        )),
+                        Err(_) => problems.push(format!(
+                            "{}: invalid version requirement '{}' for {}",
+                            name, req, compiler
+                        )),
+                    },
+                }
+            }
+        }
+    }
+
+    if !constraints.arch.is_empty()
+        && !constraints
+            .arch
+            .iter()
+            .any(|arch| normalize_arch(arch) == normalize_arch(&toolchain.arch))
+    {
+        problems.push(format!(
+            "{} supports architectures {} but the target is {}",
+            name,
+            constraints.arch.join(", "),
+            toolchain.arch
+        ));
+    }
+
+    let mut environment: Vec<(&String, &String)> = constraints.environment.iter().collect();
+    environment.sort();
+    for (var, expected) in environment {
+        match std::env::var(var) {
+            Ok(value) if &value == expected => {}
+            Ok(value) => problems.push(format!(
+                "{} requires {}='{}' but it is '{}'",
+                name, var, expected, value
+            )),
+            Err(_) => problems.push(format!("{} requires {}='{}'", name, var, expected)),
+        }
+    }
+
+    problems
+}
+
+/// Identify the compiler behind a command and its version
+fn detect_compiler(cmd: &str) -> Option<(String, Version)> {
+    let output = if cmd == "cl" {
+        Command::new(cmd).output().ok()?
+    } else {
+        Command::new(cmd).arg("--version").output().ok()?
+    };
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let family = if text.contains("clang") {
+        "clang"
+    } else if text.contains("Microsoft") {
+        "msvc"
+    } else if text.contains("GCC") || text.contains("Free Software Foundation") {
+        "gcc"
+    } else {
+        return None;
+    };
+
+    let re = regex::Regex::new(r"(\d+)\.(\d+)\.(\d+)").ok()?;
+    let caps = re.captures(&text)?;
+    let version = Version::new(
+        caps[1].parse().ok()?,
+        caps[2].parse().ok()?,
+        caps[3].parse().ok()?,
+    );
+
+    Some((family.to_string(), version))
+}
+
+/// The compiler's default C++ standard, read from `__cplusplus`
+fn default_standard(cmd: &str) -> Option<u32> {
+    let mut child = Command::new(cmd)
+        .args(["-x", "c++", "-E", "-dM", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    // Preprocess an empty translation unit
+    drop(child.stdin.take());
+    let output = child.wait_with_output().ok()?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("#define __cplusplus "))
+        .and_then(|value| value.trim().trim_end_matches('L').parse::<u32>().ok())
+        // C++98 reports 199711
+        .map(|value| (value / 100).max(1998))
+}
+
+/// C++ standard named by a compiler flag: `17` for `-std=c++17`,
+/// `latest` for `/std:c++latest`
+fn standard_flag(flag: &str) -> Option<&str> {
+    let value = flag
+        .strip_prefix("-std=")
+        .or_else(|| flag.strip_prefix("/std:"))?;
+    value
+        .strip_prefix("c++")
+        .or_else(|| value.strip_prefix("gnu++"))
+}
+
+/// Parse a C++ standard (`17`, `c++17`, `C++2a`) into its year
+pub(crate) fn parse_standard(value: &str) -> Option<u32> {
+    let value = value.trim().to_lowercase();
+    let value = value.strip_prefix("c++").unwrap_or(&value);
+    match value {
+        "98" => Some(1998),
+        "03" => Some(2003),
+        "0x" | "11" => Some(2011),
+        "1y" | "14" => Some(2014),
+        "1z" | "17" => Some(2017),
+        "2a" | "20" => Some(2020),
+        "2b" | "23" => Some(2023),
+        "2c" | "26" => Some(2026),
+        _ => None,
+    }
+}
+
+fn display_standard(year: u32) -> String {
+    format!("C++{:02}", year % 100)
+}
+
+fn compiler_family(name: &str) -> &str {
+    match name.to_lowercase().as_str() {
+        "gcc" | "g++" | "gnu" => "gcc",
+        "clang" | "clang++" | "apple-clang" | "appleclang" => "clang",
+        "msvc" | "cl" => "msvc",
+        _ => "unknown",
+    }
+}
+
+fn normalize_arch(arch: &str) -> &str {
+    match arch {
+        "amd64" | "x64" => "x86_64",
+        "arm64" => "aarch64",
+        "x86" | "i386" => "i686",
+        "arm" => "armv7",
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toolchain() -> Toolchain {
+        Toolchain {
+            compiler: Some(("gcc".to_string(), Version::new(11, 4, 0))),
+            cpp_standard: Some(2017),
+            arch: "x86_64".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_satisfied_constraints() {
+        let constraints = DependencyConstraints {
+            min_cpp_standard: Some("14".to_string()),
+            max_cpp_standard: Some("c++20".to_string()),
+            compilers: [("g++".to_string(), ">=9".to_string())].into(),
+            arch: vec!["amd64".to_string()],
+            ..Default::default()
+        };
+        assert!(check("fmt", &constraints, &toolchain()).is_empty());
+    }
+
+    #[test]
+    fn test_unsatisfied_constraints() {
+        let constraints = DependencyConstraints {
+            min_cpp_standard: Some("20".to_string()),
+            compilers: [("clang".to_string(), ">=15".to_string())].into(),
+            arch: vec!["aarch64".to_string()],
+            ..Default::default()
+        };
+        let problems = check("ranges", &constraints, &toolchain());
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].contains("at least C++20 but the project builds with C++17"));
+        assert!(problems[1].contains("supports clang but gcc 11.4.0 was detected"));
+
+        let constraints = DependencyConstraints {
+            compilers: [("gcc".to_string(), ">=12".to_string())].into(),
+            ..Default::default()
+        };
+        let problems = check("ranges", &constraints, &toolchain());
+        assert_eq!(
+            problems,
+            vec!["ranges requires gcc >=12 but 11.4.0 was detected"]
+        );
+    }
+
+    #[test]
+    fn test_standard_parsing() {
+        assert_eq!(
+            standard_flag("-std=gnu++2a").and_then(parse_standard),
+            Some(2020)
+        );
+        assert_eq!(standard_flag("/std:c++latest"), Some("latest"));
+        assert_eq!(standard_flag("-O2"), None);
+        assert_eq!(parse_standard("C++98"), Some(1998));
+        // No particular standard, so constraints can't be checked against it
+        assert_eq!(parse_standard("latest"), None);
+    }
+}
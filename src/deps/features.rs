@@ -0,0 +1,237 @@
+//! Cargo-style feature activation
+//!
+//! A package's `[features]` table maps a feature name to the values it
+//! enables:
+//! - `"other"` - another feature of the same package
+//! - `"dep:name"` - the optional dependency `name`
+//! - `"name/feat"` - feature `feat` of dependency `name` (enabling `name` if optional)
+//! - `"name?/feat"` - feature `feat` of dependency `name`, only if it is enabled anyway
+//!
+//! Optional dependencies also act as implicit features named after them.
+
+use anyhow::{Result, anyhow};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::config::{Dependency, PortersConfig};
+
+/// Features selected on the command line
+#[derive(Debug, Clone, Default)]
+pub struct FeatureSelection {
+    /// Features requested with `--features`
+    pub features: Vec<String>,
+    /// `--all-features`
+    pub all_features: bool,
+    /// `--no-default-features`
+    pub no_default_features: bool,
+}
+
+/// Result of expanding requested features through a feature table
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Activation {
+    /// Every feature of the package that ends up enabled
+    pub features: BTreeSet<String>,
+    /// Optional dependencies switched on by those features
+    pub optional_deps: BTreeSet<String>,
+    /// Features to enable on dependencies
+    pub dep_features: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// Expand `requested` features of `package`
+///
+/// `optional` lists the package's optional dependencies. Packages without a
+/// feature table accept any feature name as an opaque flag.
+pub fn activate(
+    package: &str,
+    table: &HashMap<String, Vec<String>>,
+    optional: &BTreeSet<String>,
+    requested: &[String],
+    default_features: bool,
+) -> Result<Activation> {
+    let mut activation = Activation::default();
+    let mut weak: Vec<(String, String)> = Vec::new();
+    let mut queue: Vec<String> = requested.to_vec();
+
+    if default_features && table.contains_key("default") {
+        queue.push("default".to_string());
+    }
+
+    while let Some(value) = queue.pop() {
+        if let Some(name) = value.strip_prefix("dep:") {
+            if !optional.contains(name) {
+                return Err(anyhow!(
+                    "Feature value 'dep:{}' of {} does not name an optional dependency",
+                    name,
+                    package
+                ));
+            }
+            activation.optional_deps.insert(name.to_string());
+        } else if let Some((dep, feature)) = value.split_once('/') {
+            match dep.strip_suffix('?') {
+                Some(dep) => weak.push((dep.to_string(), feature.to_string())),
+                None => {
+                    if optional.contains(dep) {
+                        activation.optional_deps.insert(dep.to_string());
+                    }
+                    activation
+                        .dep_features
+                        .entry(dep.to_string())
+                        .or_default()
+                        .insert(feature.to_string());
+                }
+            }
+        } else if !activation.features.insert(value.clone()) {
+            continue;
+        } else if let Some(values) = table.get(&value) {
+            queue.extend(values.iter().cloned());
+        } else if optional.contains(&value) {
+            activation.optional_deps.insert(value);
+        } else if !table.is_empty() {
+            return Err(anyhow!("Package {} does not have feature '{}'", package, value));
+        }
+    }
+
+    // Weak dependency features only apply to dependencies enabled some other way
+    for (dep, feature) in weak {
+        if !optional.contains(&dep) || activation.optional_deps.contains(&dep) {
+            activation.dep_features.entry(dep).or_default().insert(feature);
+        }
+    }
+
+    Ok(activation)
+}
+
+/// Every feature and optional dependency a package offers (`--all-features`)
+pub fn all_features(
+    table: &HashMap<String, Vec<String>>,
+    optional: &BTreeSet<String>,
+) -> Vec<String> {
+    let mut all: Vec<String> = table.keys().cloned().collect();
+    all.extend(optional.iter().cloned());
+    all.sort();
+    all.dedup();
+    all
+}
+
+/// Names of a project's optional dependencies
+pub fn optional_dependencies(config: &PortersConfig) -> BTreeSet<String> {
+    config
+        .platform_dependencies(true)
+        .into_iter()
+        .filter(|(_, dep)| matches!(dep, Dependency::Detailed { optional: true, .. }))
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// Activate the project's own features from the command line selection
+pub fn activate_project(config: &PortersConfig, selection: &FeatureSelection) -> Result<Activation> {
+    let optional = optional_dependencies(config);
+    let requested = if selection.all_features {
+        all_features(&config.features, &optional)
+    } else {
+        selection
+            .features
+            .iter()
+            .map(|f| f.trim().to_string())
+            .filter(|f| !f.is_empty())
+            .collect()
+    };
+
+    activate(
+        &config.project.name,
+        &config.features,
+        &optional,
+        &requested,
+        !selection.no_default_features,
+    )
+}
+
+/// Name of the preprocessor define for a feature (`PORTERS_FEATURE_<NAME>`)
+///
+/// Features of dependencies are prefixed with the dependency name
+/// (`PORTERS_<DEP>_FEATURE_<NAME>`).
+pub fn feature_define(package: Option<&str>, feature: &str) -> String {
+    let ident = |s: &str| -> String {
+        s.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    };
+
+    match package {
+        Some(package) => format!("PORTERS_{}_FEATURE_{}", ident(package), ident(feature)),
+        None => format!("PORTERS_FEATURE_{}", ident(feature)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(entries: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.iter().map(|s| s.to_string()).collect()))
+            .collect()
+    }
+
+    fn set(items: &[&str]) -> BTreeSet<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_default_and_transitive_features() {
+        let table = table(&[
+            ("default", &["json"]),
+            ("json", &["dep:nlohmann", "fmt/std"]),
+            ("tls", &["openssl"]),
+        ]);
+        let optional = set(&["nlohmann", "openssl"]);
+
+        let activation = activate("app", &table, &optional, &[], true).unwrap();
+        assert_eq!(activation.features, set(&["default", "json"]));
+        assert_eq!(activation.optional_deps, set(&["nlohmann"]));
+        assert_eq!(activation.dep_features["fmt"], set(&["std"]));
+
+        let activation =
+            activate("app", &table, &optional, &["tls".to_string()], false).unwrap();
+        assert_eq!(activation.features, set(&["tls", "openssl"]));
+        assert_eq!(activation.optional_deps, set(&["openssl"]));
+    }
+
+    #[test]
+    fn test_weak_dependency_features() {
+        let table = table(&[("extra", &["zlib?/shared"])]);
+        let optional = set(&["zlib"]);
+
+        let activation =
+            activate("app", &table, &optional, &["extra".to_string()], true).unwrap();
+        assert!(activation.dep_features.is_empty());
+
+        let requested = vec!["extra".to_string(), "zlib".to_string()];
+        let activation = activate("app", &table, &optional, &requested, true).unwrap();
+        assert_eq!(activation.dep_features["zlib"], set(&["shared"]));
+    }
+
+    #[test]
+    fn test_unknown_feature() {
+        let table = table(&[("json", &[])]);
+        let err = activate("app", &table, &set(&[]), &["yaml".to_string()], true).unwrap_err();
+        assert!(err.to_string().contains("does not have feature 'yaml'"));
+
+        // Packages without a feature table accept any flag
+        let activation =
+            activate("lib", &HashMap::new(), &set(&[]), &["yaml".to_string()], true).unwrap();
+        assert_eq!(activation.features, set(&["yaml"]));
+    }
+
+    #[test]
+    fn test_feature_define() {
+        assert_eq!(feature_define(None, "json-io"), "PORTERS_FEATURE_JSON_IO");
+        assert_eq!(feature_define(Some("fmt"), "std"), "PORTERS_FMT_FEATURE_STD");
+    }
+}
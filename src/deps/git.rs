@@ -0,0 +1,573 @@
+//! Git fetcher for Git dependencies
+//!
+//! Every repository is kept once as a bare object store under
+//! `<cache>/git/db`, shared by all revisions and projects using it. Only the
+//! requested branch, tag or revision is fetched, shallowly where the remote
+//! allows it. Each revision is then exported (without `.git`) to
+//! `<cache>/git/checkouts/<repo>/<rev>`, including submodules, or only a
+//! subdirectory of it for `subdir` dependencies.
+
+use anyhow::{Context, Result, anyhow};
+use git2::{FetchOptions, ObjectType, Oid, Repository};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use crate::util::pretty::*;
+
+/// A Git dependency source as written in porters.toml
+#[derive(Debug, Clone, Copy)]
+pub struct GitSource<'a> {
+    pub url: &'a str,
+    pub branch: Option<&'a str>,
+    pub tag: Option<&'a str>,
+    pub rev: Option<&'a str>,
+    /// Subdirectory of the repository that holds the package
+    pub subdir: Option<&'a str>,
+}
+
+impl<'a> GitSource<'a> {
+    pub fn new(url: &'a str) -> Self {
+        Self {
+            url,
+            branch: None,
+            tag: None,
+            rev: None,
+            subdir: None,
+        }
+    }
+
+    /// What to check out, in order of precedence: rev, tag, branch
+    pub fn reference(&self) -> GitReference<'a> {
+        if let Some(rev) = self.rev {
+            GitReference::Rev(rev)
+        } else if let Some(tag) = self.tag {
+            GitReference::Tag(tag)
+        } else if let Some(branch) = self.branch {
+            GitReference::Branch(branch)
+        } else {
+            GitReference::DefaultBranch
+        }
+    }
+}
+
+/// The commit of a repository to check out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitReference<'a> {
+    Rev(&'a str),
+    Tag(&'a str),
+    Branch(&'a str),
+    DefaultBranch,
+}
+
+impl std::fmt::Display for GitReference<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GitReference::Rev(rev) => write!(f, "revision {}", rev),
+            GitReference::Tag(tag) => write!(f, "tag {}", tag),
+            GitReference::Branch(branch) => write!(f, "branch {}", branch),
+            GitReference::DefaultBranch => write!(f, "default branch"),
+        }
+    }
+}
+
+/// A revision exported from the shared store
+#[derive(Debug, Clone)]
+pub struct GitCheckout {
+    /// Commit that was checked out
+    pub rev: String,
+    /// Directory holding the exported sources (the subdirectory, if any)
+    pub path: PathBuf,
+}
+
+/// Fetches Git dependencies into the shared store under `<cache>/git`
+pub struct GitFetcher {
+    root: PathBuf,
+    offline: bool,
+}
+
+impl GitFetcher {
+    pub fn new(cache_dir: &Path, offline: bool) -> Self {
+        Self {
+            root: cache_dir.join("git"),
+            offline,
+        }
+    }
+
+    /// Fetch a source and export the requested revision
+    pub fn checkout(&self, name: &str, source: &GitSource) -> Result<GitCheckout> {
+        let repo = self.database(source.url)?;
+        let reference = source.reference();
+        let commit = self
+            .resolve(&repo, source.url, reference)
+            .with_context(|| format!("Failed to fetch {} {} from {}", name, reference, source.url))?;
+
+        let rev = commit.to_string();
+        let mut dir_name = rev[..12].to_string();
+        if let Some(subdir) = source.subdir {
+            dir_name.push('-');
+            dir_name.push_str(&sanitize(subdir));
+        }
+        let dest = self
+            .root
+            .join("checkouts")
+            .join(store_name(source.url))
+            .join(&dir_name);
+        let marker = dest.with_file_name(format!("{}.ok", dir_name));
+
+        if !marker.exists() {
+            print_info(&format!("Checking out {} at {}...", name, &rev[..12]));
+            let staging = dest.with_file_name(format!("{}.tmp", dir_name));
+            if staging.exists() {
+                std::fs::remove_dir_all(&staging)?;
+            }
+            std::fs::create_dir_all(&staging)?;
+
+            self.export(&repo, source.url, commit, source.subdir, &staging)?;
+
+            if dest.exists() {
+                std::fs::remove_dir_all(&dest)?;
+            }
+            std::fs::rename(&staging, &dest)
+                .with_context(|| format!("Failed to create checkout {}", dest.display()))?;
+            std::fs::write(&marker, &rev)?;
+        }
+
+        Ok(GitCheckout { rev, path: dest })
+    }
+
+    /// Tags of a repository that are already in the local store
+    pub fn local_tags(&self, url: &str) -> Vec<String> {
+        let path = self.database_path(url);
+        let Ok(repo) = Repository::open_bare(&path) else {
+            return vec![];
+        };
+        let mut tags: Vec<String> = repo
+            .tag_names(None)
+            .map(|names| names.iter().flatten().map(String::from).collect())
+            .unwrap_or_default();
+        tags.sort();
+        tags
+    }
+
+    fn database_path(&self, url: &str) -> PathBuf {
+        self.root
+            .join("db")
+            .join(format!("{}.git", store_name(url)))
+    }
+
+    /// Open the bare object store of a repository, creating it if needed
+    fn database(&self, url: &str) -> Result<Repository> {
+        let path = self.database_path(url);
+        if path.exists() {
+            return Repository::open_bare(&path)
+                .with_context(|| format!("Failed to open {}", path.display()));
+        }
+        if self.offline {
+            return Err(anyhow!(
+                "🔒 Offline mode enabled and {} is not in the cache",
+                url
+            ));
+        }
+        std::fs::create_dir_all(&path)?;
+        Repository::init_bare(&path).with_context(|| format!("Failed to create {}", path.display()))
+    }
+
+    /// Find the commit a reference points to, fetching only what's missing
+    fn resolve(&self, repo: &Repository, url: &str, reference: GitReference) -> Result<Oid> {
+        match reference {
+            GitReference::Rev(rev) => {
+                if let Some(oid) = local_commit(repo, rev) {
+                    return Ok(oid);
+                }
+                self.ensure_online(url, reference)?;
+
+                // Servers usually allow fetching a full commit id directly
+                if Oid::from_str(rev).is_ok() && rev.len() == 40 {
+                    let refspec = format!("+{}:refs/porters/revs/{}", rev, rev);
+                    if fetch(repo, url, &[refspec.as_str()], true).is_ok()
+                        && let Some(oid) = local_commit(repo, rev)
+                    {
+                        return Ok(oid);
+                    }
+                }
+
+                // Otherwise fetch the complete history of every branch and tag
+                print_info(&format!("Fetching full history of {}...", url));
+                fetch(
+                    repo,
+                    url,
+                    &["+refs/heads/*:refs/remotes/origin/*", "+refs/tags/*:refs/tags/*"],
+                    false,
+                )?;
+                local_commit(repo, rev)
+                    .ok_or_else(|| anyhow!("Revision {} not found in {}", rev, url))
+            }
+            GitReference::Tag(tag) => {
+                let name = format!("refs/tags/{}", tag);
+                // Tags don't move, so a tag that is already present is reused
+                if repo.find_reference(&name).is_err() {
+                    self.ensure_online(url, reference)?;
+                    fetch(repo, url, &[format!("+{}:{}", name, name).as_str()], true)?;
+                }
+                peel_reference(repo, &name)
+            }
+            GitReference::Branch(branch) => {
+                let name = format!("refs/remotes/origin/{}", branch);
+                if !self.offline {
+                    let refspec = format!("+refs/heads/{}:{}", branch, name);
+                    fetch(repo, url, &[refspec.as_str()], true)?;
+                }
+                peel_reference(repo, &name).map_err(|_| self.missing(url, reference))
+            }
+            GitReference::DefaultBranch => {
+                let name = "refs/remotes/origin/HEAD";
+                if !self.offline {
+                    fetch(repo, url, &[format!("+HEAD:{}", name).as_str()], true)?;
+                }
+                peel_reference(repo, name).map_err(|_| self.missing(url, reference))
+            }
+        }
+    }
+
+    fn ensure_online(&self, url: &str, reference: GitReference) -> Result<()> {
+        if self.offline {
+            return Err(self.missing(url, reference));
+        }
+        Ok(())
+    }
+
+    fn missing(&self, url: &str, reference: GitReference) -> anyhow::Error {
+        anyhow!(
+            "🔒 Offline mode enabled and {} of {} is not in the cache",
+            reference,
+            url
+        )
+    }
+
+    /// Write the tree of a commit (or one of its subdirectories) to `dest`
+    fn export(
+        &self,
+        repo: &Repository,
+        url: &str,
+        commit: Oid,
+        subdir: Option<&str>,
+        dest: &Path,
+    ) -> Result<()> {
+        let root = repo.find_commit(commit)?.tree()?;
+        let submodules = read_gitmodules(repo, &root, url);
+
+        let (tree, prefix) = match subdir {
+            Some(subdir) => {
+                let subdir = subdir.trim_matches('/');
+                let entry = root
+                    .get_path(Path::new(subdir))
+                    .map_err(|_| anyhow!("Subdirectory '{}' not found in {}", subdir, url))?;
+                if entry.kind() != Some(ObjectType::Tree) {
+                    return Err(anyhow!("'{}' in {} is not a directory", subdir, url));
+                }
+                (repo.find_tree(entry.id())?, PathBuf::from(subdir))
+            }
+            None => (root, PathBuf::new()),
+        };
+
+        self.write_tree(repo, &tree, &prefix, dest, &submodules)
+    }
+
+    fn write_tree(
+        &self,
+        repo: &Repository,
+        tree: &git2::Tree,
+        repo_path: &Path,
+        dest: &Path,
+        submodules: &[(String, String)],
+    ) -> Result<()> {
+        std::fs::create_dir_all(dest)?;
+
+        for entry in tree.iter() {
+            let Some(name) = entry.name() else {
+                continue;
+            };
+            let out = dest.join(name);
+            let path = repo_path.join(name);
+
+            match entry.kind() {
+                Some(ObjectType::Tree) => {
+                    let subtree = repo.find_tree(entry.id())?;
+                    self.write_tree(repo, &subtree, &path, &out, submodules)?;
+                }
+                Some(ObjectType::Blob) => {
+                    let blob = repo.find_blob(entry.id())?;
+                    write_blob(&out, blob.content(), entry.filemode())?;
+                }
+                Some(ObjectType::Commit) => {
+                    let path = path.to_string_lossy().replace('\\', "/");
+                    let Some((_, sub_url)) = submodules.iter().find(|(p, _)| *p == path) else {
+                        print_warning(&format!("Skipping submodule {} without a URL", path));
+                        continue;
+                    };
+                    print_info(&format!("Fetching submodule {}...", path));
+                    let sub_repo = self.database(sub_url)?;
+                    let rev = entry.id().to_string();
+                    let commit = self.resolve(&sub_repo, sub_url, GitReference::Rev(&rev))?;
+                    self.export(&sub_repo, sub_url, commit, None, &out)?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Fetch refspecs from `url` into `repo`, shallowly if requested
+///
+/// Not every transport supports shallow fetches, so a failed shallow fetch
+/// is retried in full.
+fn fetch(repo: &Repository, url: &str, refspecs: &[&str], shallow: bool) -> Result<()> {
+    let mut remote = repo.remote_anonymous(url)?;
+
+    if shallow {
+        let mut options = FetchOptions::new();
+        options.depth(1);
+        options.download_tags(git2::AutotagOption::None);
+        if remote.fetch(refspecs, Some(&mut options), None).is_ok() {
+            return Ok(());
+        }
+    }
+
+    let mut options = FetchOptions::new();
+    options.download_tags(git2::AutotagOption::None);
+    if repo.is_shallow() {
+        // Deepen earlier shallow fetches so older commits become reachable
+        options.depth(i32::MAX);
+    }
+    remote
+        .fetch(refspecs, Some(&mut options), None)
+        .with_context(|| format!("Failed to fetch from {}", url))
+}
+
+/// A commit already present in the store
+fn local_commit(repo: &Repository, rev: &str) -> Option<Oid> {
+    repo.revparse_single(rev)
+        .ok()?
+        .peel_to_commit()
+        .ok()
+        .map(|commit| commit.id())
+}
+
+/// Commit a reference points to, peeling annotated tags
+fn peel_reference(repo: &Repository, name: &str) -> Result<Oid> {
+    Ok(repo.find_reference(name)?.peel_to_commit()?.id())
+}
+
+fn write_blob(path: &Path, content: &[u8], mode: i32) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        if mode == 0o120000 {
+            let target = String::from_utf8_lossy(content).to_string();
+            std::os::unix::fs::symlink(target, path)?;
+            return Ok(());
+        }
+        std::fs::write(path, content)?;
+        if mode == 0o100755 {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = mode;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+}
+
+/// Submodule paths and URLs listed in a tree's `.gitmodules`
+fn read_gitmodules(repo: &Repository, tree: &git2::Tree, url: &str) -> Vec<(String, String)> {
+    let Some(content) = tree
+        .get_name(".gitmodules")
+        .and_then(|entry| repo.find_blob(entry.id()).ok())
+        .map(|blob| String::from_utf8_lossy(blob.content()).to_string())
+    else {
+        return vec![];
+    };
+
+    let mut submodules = Vec::new();
+    let mut path: Option<String> = None;
+    let mut sub_url: Option<String> = None;
+    let mut flush = |path: &mut Option<String>, sub_url: &mut Option<String>| {
+        if let (Some(p), Some(u)) = (path.take(), sub_url.take()) {
+            submodules.push((p, resolve_url(url, &u)));
+        }
+    };
+
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            flush(&mut path, &mut sub_url);
+        } else if let Some((key, value)) = line.split_once('=') {
+            match key.trim() {
+                "path" => path = Some(value.trim().trim_matches('/').to_string()),
+                "url" => sub_url = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+    }
+    flush(&mut path, &mut sub_url);
+
+    submodules
+}
+
+/// Resolve a submodule URL relative to its parent repository URL
+fn resolve_url(base: &str, url: &str) -> String {
+    if !url.starts_with("./") && !url.starts_with("../") {
+        return url.to_string();
+    }
+
+    let mut base = base.trim_end_matches('/').to_string();
+    let mut rest = url;
+    loop {
+        if let Some(r) = rest.strip_prefix("./") {
+            rest = r;
+        } else if let Some(r) = rest.strip_prefix("../") {
+            rest = r;
+            if let Some(pos) = base.rfind(['/', ':']) {
+                base.truncate(pos);
+            }
+        } else {
+            break;
+        }
+    }
+    format!("{}/{}", base, rest)
+}
+
+/// Directory name of a repository in the store (`<repo>-<hash>`)
+fn store_name(url: &str) -> String {
+    let normalized = url.trim_end_matches('/').trim_end_matches(".git");
+    let repo_name = normalized
+        .rsplit(['/', ':'])
+        .next()
+        .filter(|n| !n.is_empty())
+        .unwrap_or("repo");
+
+    let mut hasher = Sha256::new();
+    hasher.update(normalized.as_bytes());
+    let hash = hex::encode(hasher.finalize());
+
+    format!("{}-{}", sanitize(repo_name), &hash[..12])
+}
+
+/// Make `value` safe to use in a cache directory name
+pub(crate) fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn commit_all(repo: &Repository, message: &str) -> Oid {
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = git2::Signature::now("test", "test@example.com").unwrap();
+        let parents: Vec<git2::Commit> = repo
+            .head()
+            .ok()
+            .and_then(|h| h.peel_to_commit().ok())
+            .into_iter()
+            .collect();
+        let parents: Vec<&git2::Commit> = parents.iter().collect();
+        repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parents)
+            .unwrap()
+    }
+
+    fn upstream(temp: &TempDir) -> (String, Repository) {
+        let dir = temp.path().join("upstream");
+        let repo = Repository::init(&dir).unwrap();
+        std::fs::create_dir_all(dir.join("libfoo/include")).unwrap();
+        std::fs::write(dir.join("libfoo/include/foo.h"), "v1").unwrap();
+        std::fs::write(dir.join("README"), "readme").unwrap();
+        (dir.to_string_lossy().to_string(), repo)
+    }
+
+    #[test]
+    fn test_checkout_tag_rev_and_subdir() {
+        let temp = TempDir::new().unwrap();
+        let (url, repo) = upstream(&temp);
+        let first = commit_all(&repo, "first");
+
+        // Annotated tag
+        let sig = git2::Signature::now("test", "test@example.com").unwrap();
+        let object = repo.find_object(first, None).unwrap();
+        repo.tag("v1.0.0", &object, &sig, "release", false).unwrap();
+
+        std::fs::write(temp.path().join("upstream/libfoo/include/foo.h"), "v2").unwrap();
+        let second = commit_all(&repo, "second");
+
+        let fetcher = GitFetcher::new(&temp.path().join("cache"), false);
+
+        let mut source = GitSource::new(&url);
+        source.tag = Some("v1.0.0");
+        let tagged = fetcher.checkout("foo", &source).unwrap();
+        assert_eq!(tagged.rev, first.to_string());
+        assert_eq!(
+            std::fs::read_to_string(tagged.path.join("libfoo/include/foo.h")).unwrap(),
+            "v1"
+        );
+        assert!(!tagged.path.join(".git").exists());
+
+        let rev = second.to_string();
+        let source = GitSource {
+            rev: Some(&rev),
+            subdir: Some("libfoo"),
+            ..GitSource::new(&url)
+        };
+        let sparse = fetcher.checkout("foo", &source).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(sparse.path.join("include/foo.h")).unwrap(),
+            "v2"
+        );
+        assert!(!sparse.path.join("README").exists());
+
+        // Both revisions share one object store
+        let dbs = std::fs::read_dir(temp.path().join("cache/git/db")).unwrap().count();
+        assert_eq!(dbs, 1);
+        assert_eq!(fetcher.local_tags(&url), vec!["v1.0.0".to_string()]);
+
+        // Everything needed is cached, so offline checkouts work
+        let offline = GitFetcher::new(&temp.path().join("cache"), true);
+        let mut source = GitSource::new(&url);
+        source.tag = Some("v1.0.0");
+        assert_eq!(offline.checkout("foo", &source).unwrap().rev, first.to_string());
+        source.tag = Some("v9.9.9");
+        assert!(offline.checkout("foo", &source).is_err());
+    }
+
+    #[test]
+    fn test_resolve_submodule_url() {
+        assert_eq!(
+            resolve_url("https://github.com/org/repo.git", "../other.git"),
+            "https://github.com/org/other.git"
+        );
+        assert_eq!(
+            resolve_url("https://github.com/org/repo", "https://x.org/y.git"),
+            "https://x.org/y.git"
+        );
+    }
+}
@@ -1,337 +1,416 @@
 //! Dependency management system
 //!
 //! This module handles dependency resolution, fetching, and validation.
-//! It supports Git-based dependencies, local path dependencies, and optional
-//! registry integration, ensuring proper version tracking and checksum verification.
+//! It supports Git-based dependencies, source archives, local path dependencies,
+//! and version-only dependencies resolved through the Porters registry, ensuring proper version
+//! tracking and checksum verification.
 //! Integrates with global cache for faster dependency resolution.
 
 use anyhow::{Context, Result};
-use git2::Repository;
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
-use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
-use crate::cache::GlobalCache;
-use crate::config::{Dependency, PortersConfig};
+use crate::config::{Dependency, DependencyConstraints, PortersConfig};
 use crate::global_config::GlobalPortersConfig;
+use crate::registry::PackageDefinition;
 use crate::scan;
 use crate::util::pretty::*;
-
-#[allow(unused_imports)]
+use archive::{ArchiveFetcher, ArchiveSource};
+use git::{GitFetcher, GitSource};
+
+pub mod archive;
+pub mod build;
+pub mod constraints;
+pub mod features;
+pub mod git;
+pub mod outdated;
+pub mod platform;
 pub mod resolver;
 
-/// A resolved dependency with all necessary build information
+/// A node of the resolved dependency graph
 ///
-/// Contains the dependency's location, version, source metadata,
-/// and paths for includes and libraries needed during compilation.
+/// Produced by the resolver, serialized into porters.lock, and consumed by
+/// the build adapters. Local paths are machine specific, so they are not
+/// written to the lock file.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResolvedDependency {
     pub name: String,
     pub version: String,
     pub source: DependencySource,
-    pub path: PathBuf,
-    pub include_paths: Vec<PathBuf>,
-    pub lib_paths: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub checksum: Option<String>,
-    /// Nested dependencies (transitive dependencies)
+    /// Direct dependencies of this package (edges of the resolved graph)
     #[serde(default)]
     pub dependencies: Vec<String>,
+    /// Features enabled for this package
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    /// Platforms this package is restricted to (`None` means all platforms)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platforms: Option<Vec<String>>,
+    #[serde(skip)]
+    pub path: PathBuf,
+    #[serde(skip)]
+    pub include_paths: Vec<PathBuf>,
+    #[serde(skip)]
+    pub lib_paths: Vec<PathBuf>,
+    /// How a registry package is built from source
+    #[serde(skip)]
+    pub build: Option<build::SourceBuild>,
+    /// Toolchain constraints from its registry entry and from every
+    /// manifest that requires it
+    #[serde(skip)]
+    pub constraints: Vec<DependencyConstraints>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Where a resolved dependency was fetched from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
 pub enum DependencySource {
-    Git { url: String, rev: String },
-    Path { path: String },
-    Registry { registry: String },
+    Git {
+        url: String,
+        rev: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        branch: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tag: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        subdir: Option<String>,
+    },
+    Archive {
+        url: String,
+        /// SHA-256 hash of the archive file
+        sha256: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        strip_prefix: Option<String>,
+    },
+    Path {
+        path: String,
+    },
+    Registry {
+        registry: String,
+    },
 }
 
-/// Lockfile structure (legacy - see lockfile.rs module for current implementation)
-#[allow(dead_code)]
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Lockfile {
-    pub version: String,
-    pub dependencies: HashMap<String, LockedDependency>,
+/// How dependency resolution treats porters.lock, the network and features
+#[derive(Debug, Clone, Default)]
+pub struct ResolveOptions {
+    /// Re-resolve branches, tags and version requirements instead of
+    /// reusing the revisions pinned in porters.lock
+    pub ignore_lockfile: bool,
+    /// Fail if porters.lock is missing or disagrees with porters.toml
+    pub locked: bool,
+    /// Never touch the network; everything must already be cached
+    pub offline: bool,
+    /// Features of the project to activate
+    pub features: features::FeatureSelection,
+    /// Platform to resolve for (e.g. `linux-aarch64`); `None` means the host
+    pub target: Option<String>,
 }
 
-/// Locked dependency entry (legacy - see lockfile.rs module)
-#[allow(dead_code)]
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LockedDependency {
-    pub version: String,
-    pub source: DependencySource,
-    pub checksum: String,
+impl ResolveOptions {
+    /// Build options from the `--locked` and `--frozen` command line flags
+    ///
+    /// `--frozen` implies `--locked` and additionally forbids network access.
+    pub fn from_flags(locked: bool, frozen: bool) -> Self {
+        Self {
+            locked: locked || frozen,
+            offline: frozen,
+            ..Default::default()
+        }
+    }
+
+    /// Platform whose conditional dependencies apply
+    pub fn target(&self) -> String {
+        self.target.clone().unwrap_or_else(platform::host_target)
+    }
 }
 
 /// Resolve all dependencies from the configuration
+///
+/// Fetches the full transitive dependency graph and returns one entry per
+/// package, ordered so that every dependency precedes its dependents.
 pub async fn resolve_dependencies(config: &PortersConfig) -> Result<Vec<ResolvedDependency>> {
-    let mut resolved = Vec::new();
+    resolve_dependencies_with(config, &ResolveOptions::default()).await
+}
+
+/// Resolve all dependencies, honoring porters.lock according to `options`
+///
+/// Revisions and versions recorded in porters.lock are preferred over
+/// re-resolving branch heads and version requirements.
+pub async fn resolve_dependencies_with(
+    config: &PortersConfig,
+    options: &ResolveOptions,
+) -> Result<Vec<ResolvedDependency>> {
     let cache_dir = get_cache_dir()?;
+    let lock_path = crate::global_packages::project_lock_file(".");
 
-    for (name, dep) in config.all_dependencies() {
-        print_package(&format!("Resolving {}...", name));
+    if options.locked && !lock_path.exists() {
+        return Err(anyhow::anyhow!(
+            "🔒 porters.lock is missing and --locked was passed. Run 'porters lock' to create it"
+        ));
+    }
 
-        let resolved_dep = resolve_dependency(&name, dep, &cache_dir).await?;
-        resolved.push(resolved_dep);
+    let mut resolver = resolver::DependencyResolver::new(cache_dir).with_options(options.clone());
+    if !options.ignore_lockfile && lock_path.exists() {
+        let lock = crate::lockfile::LockFile::load(&lock_path)?;
+        resolver = resolver.with_lockfile(lock);
     }
 
-    Ok(resolved)
+    resolver.resolve(config).await
+}
+
+/// Check whether network access is forbidden, either explicitly or by the
+/// global offline setting
+fn is_offline(forced: bool) -> bool {
+    forced
+        || GlobalPortersConfig::load_or_create()
+            .map(|c| c.is_offline())
+            .unwrap_or(false)
 }
 
-/// Resolve a single dependency
-async fn resolve_dependency(
+/// Fetch a specific registry package version
+///
+/// The version is fetched from its source archive if the registry lists
+/// one, otherwise from the package repository at its recorded commit or
+/// release tag. Sources are checked against the registry's checksum.
+///
+/// Warns when the version has been yanked (only porters.lock can still
+/// select it) or the package is deprecated.
+pub(crate) async fn fetch_registry_package(
     name: &str,
-    dep: &Dependency,
+    pkg: &PackageDefinition,
     cache_dir: &Path,
+    offline: bool,
 ) -> Result<ResolvedDependency> {
-    match dep {
-        Dependency::Simple(version) => {
-            // For now, simple dependencies are not supported (future registry feature)
-            print_warning(&format!(
-                "Registry dependencies not yet supported: {} = {}",
-                name, version
-            ));
-            Err(anyhow::anyhow!("Registry dependencies not yet supported"))
-        }
-        Dependency::Detailed {
-            git,
-            branch,
-            tag,
-            rev,
-            path,
-            version,
-            ..
-        } => {
-            if let Some(git_url) = git {
-                resolve_git_dependency(
-                    name,
-                    git_url,
-                    branch.as_deref(),
-                    tag.as_deref(),
-                    rev.as_deref(),
-                    cache_dir,
-                )
-                .await
-            } else if let Some(local_path) = path {
-                resolve_path_dependency(name, local_path)
-            } else if let Some(_ver) = version {
-                print_warning(&format!(
-                    "Registry dependencies not yet supported: {}",
-                    name
-                ));
-                Err(anyhow::anyhow!("Registry dependencies not yet supported"))
-            } else {
-                Err(anyhow::anyhow!(
-                    "Invalid dependency specification for {}",
-                    name
-                ))
-            }
+    if pkg.yanked {
+        print_warning(&format!(
+            "{} v{} has been yanked from registry '{}'; it is only used because porters.lock pins it",
+            name, pkg.version, pkg.registry
+        ));
+    }
+    if let Some(notice) = pkg.deprecation_notice() {
+        print_warning(&notice);
+    }
+
+    let mut resolved = if let Some(url) = &pkg.url {
+        let source = ArchiveSource {
+            url,
+            sha256: pkg.sha256.as_deref(),
+            strip_prefix: None,
+        };
+        resolve_archive_dependency(name, &source, cache_dir, offline).await?
+    } else if let Some(rev) = &pkg.rev {
+        let source = GitSource {
+            rev: Some(rev),
+            tag: pkg.tag.as_deref(),
+            ..GitSource::new(&pkg.repository)
+        };
+        resolve_git_dependency(name, &source, cache_dir, offline).await?
+    } else {
+        let tag = match &pkg.tag {
+            Some(tag) => tag.clone(),
+            None => find_release_tag(name, &pkg.repository, &pkg.version, cache_dir, offline)?,
+        };
+        let source = GitSource {
+            tag: Some(&tag),
+            ..GitSource::new(&pkg.repository)
+        };
+        resolve_git_dependency(name, &source, cache_dir, offline).await?
+    };
+
+    if let (Some(expected), Some(actual)) = (&pkg.checksum, &resolved.checksum) {
+        let expected = expected.strip_prefix("sha256:").unwrap_or(expected);
+        if !expected.eq_ignore_ascii_case(actual) {
+            anyhow::bail!(
+                "Checksum mismatch for {} v{}:\n  expected: {}\n  actual:   {}\n\
+                 The sources differ from the ones published in the registry",
+                name,
+                pkg.version,
+                expected,
+                actual
+            );
         }
     }
+
+    resolved.version = pkg.version.clone();
+    resolved.source = DependencySource::Registry {
+        registry: pkg.registry.clone(),
+    };
+    resolved.dependencies = pkg.dependencies.keys().cloned().collect();
+    resolved.build = Some(build::SourceBuild::new(pkg));
+
+    Ok(resolved)
 }
 
-/// Resolve a Git dependency
-async fn resolve_git_dependency(
+/// Find the git tag a registry package version was released under
+///
+/// Registries don't always record the tag, so the common naming schemes
+/// (`v1.2.3`, `1.2.3`, `name-1.2.3`) are matched against the remote tags.
+fn find_release_tag(
     name: &str,
     url: &str,
-    branch: Option<&str>,
-    tag: Option<&str>,
-    rev: Option<&str>,
+    version: &str,
     cache_dir: &Path,
-) -> Result<ResolvedDependency> {
-    // Check if offline mode is enabled
-    let global_config = GlobalPortersConfig::load_or_create().ok();
-    let is_offline = global_config
-        .as_ref()
-        .map(|c| c.is_offline())
-        .unwrap_or(false);
-
-    // Create a unique directory name for this dependency
-    let mut hasher = Sha256::new();
-    hasher.update(url.as_bytes());
-    let hash = hex::encode(hasher.finalize());
-    let short_hash = &hash[..12];
-
-    let dep_dir = cache_dir
-        .join("sources")
-        .join(format!("{}-{}", name, short_hash));
-
-    // Try global cache first
-    let global_cache = GlobalCache::new().ok();
-    let version_hint = rev.or(tag).or(branch).unwrap_or("latest");
-
-    if let Some(cache) = &global_cache
-        && cache.has_package(name, version_hint)
-    {
-        print_success(&format!("✨ Using globally cached {}", name));
-        // Ensure destination exists
-        std::fs::create_dir_all(&dep_dir)?;
-        // Retrieve from global cache to local cache
-        if cache.retrieve_package(name, version_hint, &dep_dir).is_ok() {
-            // Scan the retrieved package
-            let sources = scan::scan_project(&dep_dir)?;
-            let checksum = crate::hash::calculate_directory_hash(&dep_dir)
-                .unwrap_or_else(|_| "unknown".to_string());
-
-            return Ok(ResolvedDependency {
-                name: name.to_string(),
-                version: version_hint.to_string(),
-                source: DependencySource::Git {
-                    url: url.to_string(),
-                    rev: version_hint.to_string(),
-                },
-                path: dep_dir,
-                include_paths: sources.include_paths,
-                lib_paths: vec![],
-                checksum: Some(checksum),
-                dependencies: vec![], // Will be resolved if needed
+    offline: bool,
+) -> Result<String> {
+    let candidates = [
+        format!("v{}", version),
+        version.to_string(),
+        format!("{}-{}", name, version),
+    ];
+
+    if is_offline(offline) {
+        // Without network access, only an already-fetched release can be used
+        let cached = GitFetcher::new(cache_dir, true).local_tags(url);
+        return candidates
+            .iter()
+            .find(|tag| cached.contains(tag))
+            .cloned()
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "🔒 Offline mode enabled and {} v{} not found in cache",
+                    name,
+                    version
+                )
             });
-        }
     }
 
-    // If offline and not in cache, fail
-    if is_offline {
-        return Err(anyhow::anyhow!(
-            "🔒 Offline mode enabled and {} not found in cache. Cannot download from {}",
-            name,
-            url
-        ));
-    }
+    let tags = list_remote_tags(url)?;
+
+    candidates
+        .iter()
+        .find(|tag| tags.contains(tag))
+        .cloned()
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "No release tag found for {} v{} in {} (tried {})",
+                name,
+                version,
+                url,
+                candidates.join(", ")
+            )
+        })
+}
 
-    // Clone or update the repository
-    if dep_dir.exists() {
-        print_info(&format!("Updating {} from git...", name));
-        let repo = Repository::open(&dep_dir)
-            .with_context(|| format!("Failed to open repository at {}", dep_dir.display()))?;
+/// List the tag names published by a remote git repository
+pub fn list_remote_tags(url: &str) -> Result<Vec<String>> {
+    let mut remote = git2::Remote::create_detached(url)
+        .with_context(|| format!("Invalid git remote: {}", url))?;
+    remote
+        .connect(git2::Direction::Fetch)
+        .with_context(|| format!("Failed to connect to {}", url))?;
+
+    let mut tags: Vec<String> = remote
+        .list()?
+        .iter()
+        .filter_map(|head| head.name().strip_prefix("refs/tags/"))
+        .map(|tag| tag.trim_end_matches("^{}").to_string())
+        .collect();
+
+    tags.sort();
+    tags.dedup();
+
+    Ok(tags)
+}
 
-        // Fetch updates
-        {
-            let mut remote = repo.find_remote("origin")?;
-            remote.fetch(&["refs/heads/*:refs/heads/*"], None, None)?;
-        }
+/// Resolve a Git dependency
+///
+/// Only the requested revision is fetched into the shared repository store,
+/// and a revision that is already present (e.g. pinned by porters.lock) is
+/// not fetched again. With `offline` set, only cached revisions can be used.
+pub(crate) async fn resolve_git_dependency(
+    name: &str,
+    source: &GitSource<'_>,
+    cache_dir: &Path,
+    offline: bool,
+) -> Result<ResolvedDependency> {
+    let fetcher = GitFetcher::new(cache_dir, is_offline(offline));
+    let checkout = fetcher.checkout(name, source)?;
 
-        // Checkout the requested revision
-        let commit_id = if let Some(rev_str) = rev {
-            let oid = git2::Oid::from_str(rev_str)?;
-            repo.find_commit(oid)?.id()
-        } else if let Some(tag_str) = tag {
-            let reference = repo.find_reference(&format!("refs/tags/{}", tag_str))?;
-            reference.peel_to_commit()?.id()
-        } else if let Some(branch_str) = branch {
-            let reference = repo.find_reference(&format!("refs/heads/{}", branch_str))?;
-            reference.peel_to_commit()?.id()
-        } else {
-            // Default to HEAD of default branch
-            repo.head()?.peel_to_commit()?.id()
-        };
+    // Calculate checksum of the dependency
+    print_info("Calculating checksum...");
+    let checksum = crate::hash::calculate_directory_hash(&checkout.path).with_context(|| {
+        format!(
+            "Failed to calculate checksum for {}",
+            checkout.path.display()
+        )
+    })?;
 
-        // Checkout the commit
-        let commit = repo.find_commit(commit_id)?;
-        repo.checkout_tree(commit.as_object(), None)?;
-        repo.set_head_detached(commit_id)?;
-
-        // Calculate checksum of the dependency
-        print_info("Calculating checksum...");
-        let checksum = crate::hash::calculate_directory_hash(&dep_dir)
-            .with_context(|| format!("Failed to calculate checksum for {}", dep_dir.display()))?;
-
-        // Scan for include paths
-        let sources = scan::scan_project(&dep_dir)?;
-
-        // Check for nested dependencies
-        let nested_deps = resolve_nested_dependencies(&dep_dir).await?;
-
-        let resolved_dep = ResolvedDependency {
-            name: name.to_string(),
-            version: commit_id.to_string()[..8].to_string(),
-            source: DependencySource::Git {
-                url: url.to_string(),
-                rev: commit_id.to_string(),
-            },
-            path: dep_dir.clone(),
-            include_paths: sources.include_paths.clone(),
-            lib_paths: vec![],
-            checksum: Some(checksum.clone()),
-            dependencies: nested_deps,
-        };
+    // Scan for include paths
+    let sources = scan::scan_project(&checkout.path)?;
 
-        // Store in global cache for future use
-        if let Some(cache) = &global_cache {
-            let _ = cache.store_package(name, version_hint, &dep_dir);
-            print_info(&format!(
-                "📦 Cached {} globally for faster future access",
-                name
-            ));
-        }
+    Ok(ResolvedDependency {
+        name: name.to_string(),
+        version: checkout.rev[..8].to_string(),
+        source: DependencySource::Git {
+            url: source.url.to_string(),
+            rev: checkout.rev.clone(),
+            branch: source.branch.map(String::from),
+            tag: source.tag.map(String::from),
+            subdir: source.subdir.map(String::from),
+        },
+        path: checkout.path,
+        include_paths: sources.include_paths,
+        lib_paths: vec![],
+        build: None,
+        constraints: vec![],
+        checksum: Some(checksum),
+        dependencies: vec![], // Filled in by the resolver
+        features: vec![],
+        platforms: None,
+    })
+}
 
-        Ok(resolved_dep)
-    } else {
-        print_info(&format!("Cloning {} from {}...", name, url));
-        std::fs::create_dir_all(&dep_dir)?;
-
-        let repo = Repository::clone(url, &dep_dir)
-            .with_context(|| format!("Failed to clone repository: {}", url))?;
-
-        // Checkout the requested revision
-        let commit_id = if let Some(rev_str) = rev {
-            let oid = git2::Oid::from_str(rev_str)?;
-            repo.find_commit(oid)?.id()
-        } else if let Some(tag_str) = tag {
-            let reference = repo.find_reference(&format!("refs/tags/{}", tag_str))?;
-            reference.peel_to_commit()?.id()
-        } else if let Some(branch_str) = branch {
-            let reference = repo.find_reference(&format!("refs/heads/{}", branch_str))?;
-            reference.peel_to_commit()?.id()
-        } else {
-            // Default to HEAD of default branch
-            repo.head()?.peel_to_commit()?.id()
-        };
+/// Resolve an archive dependency
+///
+/// The archive is downloaded, checked against its pinned `sha256` and
+/// unpacked into the global cache unless an unpacked copy is already there.
+/// The version is taken from the archive's file name where possible.
+pub(crate) async fn resolve_archive_dependency(
+    name: &str,
+    source: &ArchiveSource<'_>,
+    cache_dir: &Path,
+    offline: bool,
+) -> Result<ResolvedDependency> {
+    let fetcher = ArchiveFetcher::new(cache_dir, is_offline(offline));
+    let checkout = fetcher.fetch(name, source).await?;
 
-        // Checkout the commit
-        let commit = repo.find_commit(commit_id)?;
-        repo.checkout_tree(commit.as_object(), None)?;
-        repo.set_head_detached(commit_id)?;
-
-        // Calculate checksum of the dependency
-        print_info("Calculating checksum...");
-        let checksum = crate::hash::calculate_directory_hash(&dep_dir)
-            .with_context(|| format!("Failed to calculate checksum for {}", dep_dir.display()))?;
-
-        // Scan for include paths
-        let sources = scan::scan_project(&dep_dir)?;
-
-        // Check for nested dependencies
-        let nested_deps = resolve_nested_dependencies(&dep_dir).await?;
-
-        let resolved_dep = ResolvedDependency {
-            name: name.to_string(),
-            version: commit_id.to_string()[..8].to_string(),
-            source: DependencySource::Git {
-                url: url.to_string(),
-                rev: commit_id.to_string(),
-            },
-            path: dep_dir.clone(),
-            include_paths: sources.include_paths.clone(),
-            lib_paths: vec![],
-            checksum: Some(checksum.clone()),
-            dependencies: nested_deps,
-        };
+    // Calculate checksum of the dependency
+    print_info("Calculating checksum...");
+    let checksum = crate::hash::calculate_directory_hash(&checkout.path).with_context(|| {
+        format!(
+            "Failed to calculate checksum for {}",
+            checkout.path.display()
+        )
+    })?;
 
-        // Store in global cache for future use
-        if let Some(cache) = &global_cache {
-            let _ = cache.store_package(name, version_hint, &dep_dir);
-            print_success(&format!(
-                "📦 Cached {} globally for faster future access",
-                name
-            ));
-        }
+    // Scan for include paths
+    let sources = scan::scan_project(&checkout.path)?;
 
-        Ok(resolved_dep)
-    }
+    Ok(ResolvedDependency {
+        name: name.to_string(),
+        version: archive::version_from_url(source.url)
+            .unwrap_or_else(|| checkout.sha256[..8].to_string()),
+        source: DependencySource::Archive {
+            url: source.url.to_string(),
+            sha256: checkout.sha256,
+            strip_prefix: source.strip_prefix.map(String::from),
+        },
+        path: checkout.path,
+        include_paths: sources.include_paths,
+        lib_paths: vec![],
+        build: None,
+        constraints: vec![],
+        checksum: Some(checksum),
+        dependencies: vec![], // Filled in by the resolver
+        features: vec![],
+        platforms: None,
+    })
 }
 
 /// Resolve a path dependency
-fn resolve_path_dependency(name: &str, path: &str) -> Result<ResolvedDependency> {
+pub(crate) fn resolve_path_dependency(name: &str, path: &str) -> Result<ResolvedDependency> {
     let path_buf = PathBuf::from(path);
 
     if !path_buf.exists() {
@@ -359,57 +438,63 @@ fn resolve_path_dependency(name: &str, path: &str) -> Result<ResolvedDependency>
         path: abs_path,
         include_paths: sources.include_paths,
         lib_paths: vec![],
+        build: None,
+        constraints: vec![],
         checksum: Some(checksum),
-        dependencies: vec![], // Path dependencies don't track nested deps
+        dependencies: vec![], // Filled in by the resolver
+        features: vec![],
+        platforms: None,
     })
 }
 
-/// Update all dependencies
-pub async fn update_dependencies(config: &PortersConfig) -> Result<()> {
-    print_step("Updating dependencies");
-
-    let cache_dir = get_cache_dir()?;
+/// Verify fetched sources against the checksum pinned in porters.toml
+///
+/// `actual` is the SHA-256 directory hash of the fetched sources. The pinned
+/// value may carry a `sha256:` prefix.
+pub fn verify_checksum(name: &str, spec: &Dependency, actual: &str) -> Result<()> {
+    let Dependency::Detailed { checksum, .. } = spec else {
+        return Ok(());
+    };
+    let Some(expected) = checksum.as_deref() else {
+        return Ok(());
+    };
 
-    for (name, dep) in config.all_dependencies() {
-        if let Dependency::Detailed { git: Some(_), .. } = dep {
-            print_package(&format!("Updating {}...", name));
-            resolve_dependency(&name, dep, &cache_dir).await?;
-        }
+    let expected = expected.trim();
+    let expected = expected.strip_prefix("sha256:").unwrap_or(expected);
+    if !expected.eq_ignore_ascii_case(actual) {
+        anyhow::bail!(
+            "Checksum mismatch for {}:\n  expected: {}\n  actual:   {}\n\
+             The sources changed since the checksum was pinned in porters.toml",
+            name,
+            expected,
+            actual
+        );
     }
 
     Ok(())
 }
 
-/// Generate a lockfile from resolved dependencies
-/// Generate lockfile from resolved dependencies (legacy API)
+/// Update all dependencies
 ///
-/// **Note**: Current implementation uses lockfile.rs module
-#[allow(dead_code)]
-pub fn generate_lockfile(deps: &[ResolvedDependency], path: &str) -> Result<()> {
-    let mut locked_deps = HashMap::new();
+/// Git dependencies move to the newest commit of their branch, tag or
+/// default branch; everything else keeps the version pinned in porters.lock.
+pub async fn update_dependencies(
+    config: &PortersConfig,
+    options: &ResolveOptions,
+) -> Result<Vec<ResolvedDependency>> {
+    print_step("Updating dependencies");
 
-    for dep in deps {
-        let checksum = compute_checksum(&dep.path)?;
-
-        locked_deps.insert(
-            dep.name.clone(),
-            LockedDependency {
-                version: dep.version.clone(),
-                source: dep.source.clone(),
-                checksum,
-            },
-        );
+    let mut resolver =
+        resolver::DependencyResolver::new(get_cache_dir()?).with_options(options.clone());
+    let lock_path = crate::global_packages::project_lock_file(".");
+    if lock_path.exists() {
+        let mut lock = crate::lockfile::LockFile::load(&lock_path)?;
+        lock.dependencies
+            .retain(|_, dep| !matches!(dep.source, DependencySource::Git { .. }));
+        resolver = resolver.with_lockfile(lock);
     }
 
-    let lockfile = Lockfile {
-        version: "1".to_string(),
-        dependencies: locked_deps,
-    };
-
-    let content = toml::to_string_pretty(&lockfile)?;
-    std::fs::write(path, content)?;
-
-    Ok(())
+    resolver.resolve(config).await
 }
 
 /// Vendor dependencies into a local directory
@@ -439,11 +524,19 @@ pub fn print_dependency_graph(deps: &[ResolvedDependency]) -> Result<()> {
     for dep in deps {
         print_graph_node(&dep.name, &dep.version, 0);
 
+        if !dep.dependencies.is_empty() {
+            println!("    ├─ Depends on: {}", dep.dependencies.join(", "));
+        }
+
         match &dep.source {
-            DependencySource::Git { url, rev } => {
+            DependencySource::Git { url, rev, .. } => {
                 println!("    ├─ Source: git ({})", url);
                 println!("    └─ Revision: {}", rev);
             }
+            DependencySource::Archive { url, sha256, .. } => {
+                println!("    ├─ Source: archive ({})", url);
+                println!("    └─ SHA-256: {}", sha256);
+            }
             DependencySource::Path { path } => {
                 println!("    └─ Source: path ({})", path);
             }
@@ -457,32 +550,15 @@ pub fn print_dependency_graph(deps: &[ResolvedDependency]) -> Result<()> {
 }
 
 /// Get the cache directory
-fn get_cache_dir() -> Result<PathBuf> {
+pub(crate) fn get_cache_dir() -> Result<PathBuf> {
     let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
     let cache_dir = home.join(".porters").join("cache");
     std::fs::create_dir_all(&cache_dir)?;
     Ok(cache_dir)
 }
 
-/// Compute checksum of a directory
-/// Compute checksum of directory (utility function)
-#[allow(dead_code)]
-fn compute_checksum(path: &Path) -> Result<String> {
-    let mut hasher = Sha256::new();
-
-    for entry in walkdir::WalkDir::new(path).sort_by_file_name() {
-        let entry = entry?;
-        if entry.file_type().is_file() {
-            let content = std::fs::read(entry.path())?;
-            hasher.update(&content);
-        }
-    }
-
-    Ok(hex::encode(hasher.finalize()))
-}
-
 /// Copy a directory recursively
-fn copy_dir_all(src: &Path, dst: &Path) -> Result<()> {
+pub(crate) fn copy_dir_all(src: &Path, dst: &Path) -> Result<()> {
     std::fs::create_dir_all(dst)?;
 
     for entry in std::fs::read_dir(src)? {
@@ -540,31 +616,3 @@ pub async fn clone_git_repo(url: &str, dest: &Path) -> Result<String> {
 
     Ok(checksum)
 }
-
-/// Resolve nested dependencies from a porters.toml file in the dependency directory
-async fn resolve_nested_dependencies(dep_path: &Path) -> Result<Vec<String>> {
-    let porters_toml = dep_path.join("porters.toml");
-
-    // If the dependency doesn't have a porters.toml, return empty
-    if !porters_toml.exists() {
-        return Ok(vec![]);
-    }
-
-    // Try to load the porters.toml from the dependency
-    match PortersConfig::load(porters_toml.to_str().unwrap_or("")) {
-        Ok(config) => {
-            // Extract dependency names
-            let dep_names: Vec<String> = config.all_dependencies().into_keys().collect();
-
-            if !dep_names.is_empty() {
-                print_info(&format!("  Found {} nested dependencies", dep_names.len()));
-            }
-
-            Ok(dep_names)
-        }
-        Err(_) => {
-            // If we can't load the config, just return empty
-            Ok(vec![])
-        }
-    }
-}
@@ -0,0 +1,578 @@
+//! Dependency update checks
+//!
+//! Lists the release tags of Git dependencies and the published versions of
+//! registry dependencies, and works out for each dependency the newest
+//! version compatible with porters.toml and the newest version overall.
+//! Updates are written back into porters.toml with `toml_edit`, so the file
+//! keeps its formatting and comments.
+
+use anyhow::{Context, Result, anyhow, bail};
+use colored::Colorize;
+use std::collections::HashMap;
+use toml_edit::{DocumentMut, Item, Value};
+
+use super::git::GitFetcher;
+use super::{DependencySource, get_cache_dir, is_offline, list_remote_tags};
+use crate::config::{Dependency, PortersConfig};
+use crate::lockfile::LockFile;
+use crate::registry::Registries;
+use crate::util::pretty::*;
+use crate::version::{Version, VersionReq};
+
+/// Where the versions of a dependency come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateSource {
+    /// Release tags of a Git repository
+    Git,
+    /// Versions published in the registry
+    Registry,
+}
+
+impl std::fmt::Display for UpdateSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateSource::Git => write!(f, "git"),
+            UpdateSource::Registry => write!(f, "registry"),
+        }
+    }
+}
+
+/// A version of a dependency
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    pub version: Version,
+    /// How the version is written in porters.toml (the tag for Git sources)
+    pub label: String,
+}
+
+/// Update status of one dependency
+#[derive(Debug, Clone)]
+pub struct OutdatedDependency {
+    pub name: String,
+    /// Path of the porters.toml table declaring it (e.g. `["dev-dependencies"]`)
+    pub table: Vec<String>,
+    pub source: UpdateSource,
+    /// Version requirement of a registry dependency
+    pub requirement: Option<String>,
+    pub current: Release,
+    /// Newest version compatible with the requirement (or the current tag)
+    pub compatible: Release,
+    /// Newest version available
+    pub latest: Release,
+}
+
+impl OutdatedDependency {
+    /// Whether any newer version exists
+    pub fn is_outdated(&self) -> bool {
+        self.latest.version > self.current.version
+    }
+
+    /// Version to update to, if it is newer than the current one
+    pub fn update_to(&self, latest: bool) -> Option<&Release> {
+        let release = if latest {
+            &self.latest
+        } else {
+            &self.compatible
+        };
+        (release.version > self.current.version).then_some(release)
+    }
+}
+
+/// Check every versioned dependency of the project for newer versions
+///
+/// Git dependencies are checked when they are pinned to a version tag;
+/// branches, revisions, archives and path dependencies are not versioned
+/// and are skipped. Registry dependencies start from the version locked in
+/// porters.lock.
+pub fn check_outdated(config: &PortersConfig, lock: &LockFile) -> Result<Vec<OutdatedDependency>> {
+    let offline = is_offline(config.offline);
+    let mut registries: Option<Registries> = None;
+    let mut results = Vec::new();
+
+    for (table, deps) in manifest_tables(config) {
+        let mut names: Vec<&String> = deps.keys().collect();
+        names.sort();
+
+        for name in names {
+            let dep = &deps[name];
+            let checked = if let Some((req, registry)) = registry_requirement(dep) {
+                if registries.is_none() {
+                    let loaded = Registries::from_config(&config.registries)?.with_offline(offline);
+                    if !offline {
+                        loaded.init()?;
+                    }
+                    registries = Some(loaded);
+                }
+                check_registry(name, req, registry, registries.as_ref().unwrap(), lock)
+            } else if let Dependency::Detailed {
+                git: Some(url),
+                tag: Some(tag),
+                rev: None,
+                ..
+            } = dep
+            {
+                check_git(name, url, tag, offline)
+            } else {
+                continue;
+            };
+
+            match checked {
+                Ok(Some((source, requirement, current, releases))) => {
+                    results.push(evaluate(
+                        name,
+                        &table,
+                        source,
+                        requirement,
+                        current,
+                        releases,
+                    ));
+                }
+                Ok(None) => {}
+                Err(e) => print_warning(&format!("Could not check {}: {:#}", name, e)),
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+/// Version requirement and registry of a dependency resolved through a registry
+fn registry_requirement(dep: &Dependency) -> Option<(&str, Option<&str>)> {
+    match dep {
+        Dependency::Simple(req) => Some((req, None)),
+        Dependency::Detailed {
+            version: Some(req),
+            git: None,
+            path: None,
+            url,
+            registry,
+            ..
+        } if url.is_none() => Some((req, registry.as_ref().as_deref())),
+        _ => None,
+    }
+}
+
+type Checked = Option<(UpdateSource, Option<String>, Release, Vec<Release>)>;
+
+fn check_registry(
+    name: &str,
+    requirement: &str,
+    registry: Option<&str>,
+    registries: &Registries,
+    lock: &LockFile,
+) -> Result<Checked> {
+    let req = VersionReq::parse(requirement)
+        .with_context(|| format!("Invalid version requirement '{}'", requirement))?;
+    let registry = registries.registry_of(name, registry)?;
+    let releases: Vec<Release> = registry
+        .load_versions(name)?
+        .into_iter()
+        // Yanked versions are never offered as updates
+        .filter(|pkg| !pkg.yanked)
+        .filter_map(|pkg| {
+            Some(Release {
+                version: Version::parse(&pkg.version).ok()?,
+                label: pkg.version,
+            })
+        })
+        .collect();
+
+    let locked = lock
+        .dependencies
+        .get(name)
+        .filter(|dep| {
+            matches!(&dep.source, DependencySource::Registry { registry: locked } if locked == registry.name())
+        })
+        .and_then(|dep| {
+            Some(Release {
+                version: Version::parse(&dep.version).ok()?,
+                label: dep.version.clone(),
+            })
+        });
+    let current = locked.or_else(|| {
+        releases
+            .iter()
+            .filter(|r| req.matches(&r.version))
+            .max_by(|a, b| a.version.cmp(&b.version))
+            .cloned()
+    });
+    let Some(current) = current else {
+        bail!("no published version satisfies {}", requirement);
+    };
+
+    Ok(Some((
+        UpdateSource::Registry,
+        Some(requirement.to_string()),
+        current,
+        releases,
+    )))
+}
+
+fn check_git(name: &str, url: &str, tag: &str, offline: bool) -> Result<Checked> {
+    let Some(version) = tag_version(tag, name) else {
+        print_info(&format!(
+            "{} is pinned to tag {}, which is not a version",
+            name, tag
+        ));
+        return Ok(None);
+    };
+
+    let tags = if offline {
+        GitFetcher::new(&get_cache_dir()?, true).local_tags(url)
+    } else {
+        list_remote_tags(url)?
+    };
+    let releases = tags
+        .into_iter()
+        .filter_map(|tag| {
+            Some(Release {
+                version: tag_version(&tag, name)?,
+                label: tag,
+            })
+        })
+        .collect();
+
+    let current = Release {
+        version,
+        label: tag.to_string(),
+    };
+    Ok(Some((UpdateSource::Git, None, current, releases)))
+}
+
+/// Pick the newest compatible and the newest overall release
+fn evaluate(
+    name: &str,
+    table: &[String],
+    source: UpdateSource,
+    requirement: Option<String>,
+    current: Release,
+    releases: Vec<Release>,
+) -> OutdatedDependency {
+    // Git tags carry no requirement, so updates within the same major version are compatible
+    let req = match &requirement {
+        Some(req) => VersionReq::parse(req).unwrap_or(VersionReq::Any),
+        None => VersionReq::Compatible(current.version.clone()),
+    };
+    let newest = |compatible_only: bool| {
+        releases
+            .iter()
+            .filter(|r| !compatible_only || req.matches(&r.version))
+            .filter(|r| r.version > current.version)
+            .max_by(|a, b| a.version.cmp(&b.version))
+            .cloned()
+            .unwrap_or_else(|| current.clone())
+    };
+
+    OutdatedDependency {
+        name: name.to_string(),
+        table: table.to_vec(),
+        source,
+        compatible: newest(true),
+        latest: newest(false),
+        requirement,
+        current,
+    }
+}
+
+/// Tables of porters.toml that declare dependencies, with their paths
+fn manifest_tables(config: &PortersConfig) -> Vec<(Vec<String>, &HashMap<String, Dependency>)> {
+    let mut tables = vec![
+        (vec!["dependencies".to_string()], &config.dependencies),
+        (
+            vec!["dev-dependencies".to_string()],
+            &config.dev_dependencies,
+        ),
+    ];
+
+    let mut targets: Vec<&String> = config.target.keys().collect();
+    targets.sort();
+    for target in targets {
+        let deps = &config.target[target];
+        for (key, table) in [
+            ("dependencies", &deps.dependencies),
+            ("dev-dependencies", &deps.dev_dependencies),
+        ] {
+            tables.push((
+                vec!["target".to_string(), target.clone(), key.to_string()],
+                table,
+            ));
+        }
+    }
+
+    tables
+}
+
+/// Version a tag stands for (`v1.2.3`, `1.2`, `fmt-10.1.1`)
+///
+/// Pre-release tags such as `v2.0.0-rc1` are not versions.
+pub fn tag_version(tag: &str, name: &str) -> Option<Version> {
+    let tag = tag.strip_prefix(&format!("{}-", name)).unwrap_or(tag);
+    let tag = tag.trim_start_matches(['v', 'V']);
+    if tag.is_empty() || !tag.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    Version::parse(tag).ok()
+}
+
+/// Rewrite a version requirement to require `version`, keeping its operator
+///
+/// Returns `None` for `*`, which already allows every version.
+fn bump_requirement(requirement: &str, version: &Version) -> Option<String> {
+    let requirement = requirement.trim();
+    if requirement == "*" {
+        return None;
+    }
+
+    let operator = if requirement.starts_with('<') {
+        // An upper bound can't be moved forward meaningfully
+        "^"
+    } else {
+        [">=", "==", "^", "~", ">"]
+            .into_iter()
+            .find(|op| requirement.starts_with(op))
+            .unwrap_or("")
+    };
+    Some(format!("{}{}", operator, version))
+}
+
+/// Apply updates to the contents of porters.toml
+///
+/// Moves Git dependencies to the newer tag and registry dependencies to a
+/// requirement on the newer version. Everything else in the file, including
+/// comments and formatting, is left as it is.
+pub fn update_manifest(content: &str, deps: &[OutdatedDependency], latest: bool) -> Result<String> {
+    let mut doc: DocumentMut = content.parse().context("Failed to parse porters.toml")?;
+
+    for dep in deps {
+        let Some(release) = dep.update_to(latest) else {
+            continue;
+        };
+
+        let mut table = doc.as_item_mut();
+        for key in &dep.table {
+            table = table
+                .get_mut(key.as_str())
+                .ok_or_else(|| anyhow!("[{}] not found in porters.toml", dep.table.join(".")))?;
+        }
+        let entry = table
+            .get_mut(dep.name.as_str())
+            .ok_or_else(|| anyhow!("{} not found in porters.toml", dep.name))?;
+
+        match dep.source {
+            UpdateSource::Git => {
+                let tag = entry
+                    .get_mut("tag")
+                    .ok_or_else(|| anyhow!("{} has no tag in porters.toml", dep.name))?;
+                replace_string(tag, &release.label)?;
+            }
+            UpdateSource::Registry => {
+                let requirement = dep.requirement.as_deref().unwrap_or("*");
+                let Some(bumped) = bump_requirement(requirement, &release.version) else {
+                    continue;
+                };
+                if entry.is_str() {
+                    replace_string(entry, &bumped)?;
+                } else {
+                    let version = entry
+                        .get_mut("version")
+                        .ok_or_else(|| anyhow!("{} has no version in porters.toml", dep.name))?;
+                    replace_string(version, &bumped)?;
+                }
+            }
+        }
+    }
+
+    Ok(doc.to_string())
+}
+
+/// Replace a string value, keeping the whitespace and comments around it
+fn replace_string(item: &mut Item, value: &str) -> Result<()> {
+    let old = item
+        .as_value_mut()
+        .ok_or_else(|| anyhow!("expected a string value"))?;
+    let decor = old.decor().clone();
+    *old = Value::from(value);
+    *old.decor_mut() = decor;
+    Ok(())
+}
+
+/// Print the outdated dependencies as a table
+pub fn print_outdated(deps: &[OutdatedDependency]) {
+    let rows: Vec<[String; 5]> = deps
+        .iter()
+        .filter(|dep| dep.is_outdated())
+        .map(|dep| {
+            let newer = |release: &Release| {
+                if release.version > dep.current.version {
+                    release.label.clone()
+                } else {
+                    "-".to_string()
+                }
+            };
+            [
+                dep.name.clone(),
+                dep.current.label.clone(),
+                newer(&dep.compatible),
+                newer(&dep.latest),
+                dep.source.to_string(),
+            ]
+        })
+        .collect();
+
+    if rows.is_empty() {
+        print_success("All dependencies are up to date");
+        return;
+    }
+
+    let header = ["Name", "Current", "Compatible", "Latest", "Source"];
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let line = |cells: [&str; 5]| {
+        cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+    };
+
+    println!("{}", line(header).bold());
+    for row in &rows {
+        let cells = [
+            row[0].as_str(),
+            row[1].as_str(),
+            row[2].as_str(),
+            row[3].as_str(),
+            row[4].as_str(),
+        ];
+        println!("{}", line(cells).trim_end());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(version: &str) -> Release {
+        Release {
+            version: Version::parse(version.trim_start_matches('v')).unwrap(),
+            label: version.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_compatible_and_latest_versions() {
+        let tags = ["v1.2.0", "v1.4.1", "v1.3.0", "v2.0.0"]
+            .map(release)
+            .to_vec();
+        let dep = evaluate(
+            "fmt",
+            &["dependencies".to_string()],
+            UpdateSource::Git,
+            None,
+            release("v1.2.0"),
+            tags,
+        );
+        assert_eq!(dep.compatible.label, "v1.4.1");
+        assert_eq!(dep.latest.label, "v2.0.0");
+
+        let versions = ["1.0.0", "1.1.0", "2.1.0"].map(release).to_vec();
+        let dep = evaluate(
+            "zlib",
+            &["dependencies".to_string()],
+            UpdateSource::Registry,
+            Some("~1.0".to_string()),
+            release("1.0.0"),
+            versions,
+        );
+        assert!(dep.update_to(false).is_none());
+        assert_eq!(dep.update_to(true).unwrap().label, "2.1.0");
+
+        assert_eq!(
+            tag_version("fmt-10.1.1", "fmt"),
+            Some(Version::new(10, 1, 1))
+        );
+        assert_eq!(tag_version("v2.0.0-rc1", "fmt"), None);
+        assert_eq!(
+            bump_requirement(">=1.0", &Version::new(2, 1, 0)).unwrap(),
+            ">=2.1.0"
+        );
+        assert_eq!(bump_requirement("*", &Version::new(2, 1, 0)), None);
+    }
+
+    #[test]
+    fn test_update_manifest_preserves_formatting() {
+        let content = r#"[project]
+name = "app"
+version = "0.1.0"
+
+# Formatting library
+[dependencies]
+fmt = { git = "https://github.com/fmtlib/fmt", tag = "10.1.1" } # pinned
+zlib = "^1.2"   # compression
+
+[dev-dependencies.catch2]
+version = "^3.0"
+"#;
+        let update = |name: &str, table: &str, source, requirement: Option<&str>, current, new| {
+            let mut dep = evaluate(
+                name,
+                &[table.to_string()],
+                source,
+                requirement.map(String::from),
+                release(current),
+                vec![release(new)],
+            );
+            dep.compatible = dep.latest.clone();
+            dep
+        };
+        let deps = vec![
+            update(
+                "fmt",
+                "dependencies",
+                UpdateSource::Git,
+                None,
+                "10.1.1",
+                "11.0.2",
+            ),
+            update(
+                "zlib",
+                "dependencies",
+                UpdateSource::Registry,
+                Some("^1.2"),
+                "1.2.0",
+                "1.3.1",
+            ),
+            update(
+                "catch2",
+                "dev-dependencies",
+                UpdateSource::Registry,
+                Some("^3.0"),
+                "3.0.0",
+                "3.5.0",
+            ),
+        ];
+
+        let updated = update_manifest(content, &deps, false).unwrap();
+        assert_eq!(
+            updated,
+            r#"[project]
+name = "app"
+version = "0.1.0"
+
+# Formatting library
+[dependencies]
+fmt = { git = "https://github.com/fmtlib/fmt", tag = "11.0.2" } # pinned
+zlib = "^1.3.1"   # compression
+
+[dev-dependencies.catch2]
+version = "^3.5.0"
+"#
+        );
+    }
+}
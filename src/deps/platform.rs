@@ -0,0 +1,66 @@
+//! Platform filters for conditional dependencies
+//!
+//! Platforms are written as `<os>` (`linux`, `windows`, `macos`, ...) or as
+//! `<os>-<arch>` (`linux-aarch64`), matching the names of cross-compilation
+//! targets. A dependency restricted to `linux` applies to every Linux target,
+//! one restricted to `linux-aarch64` only to that target.
+
+/// Get current platform identifier
+pub fn get_current_platform() -> String {
+    #[cfg(target_os = "windows")]
+    return "windows".to_string();
+
+    #[cfg(target_os = "macos")]
+    return "macos".to_string();
+
+    #[cfg(target_os = "linux")]
+    return "linux".to_string();
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    return "unknown".to_string();
+}
+
+/// Target name of the machine porters runs on (e.g. `linux-x86_64`)
+pub fn host_target() -> String {
+    let arch = match std::env::consts::ARCH {
+        "x86" => "i686",
+        "arm" => "armv7",
+        "riscv32" => "riscv",
+        arch => arch,
+    };
+    format!("{}-{}", get_current_platform(), arch)
+}
+
+/// Check whether a platform filter entry applies to a target
+pub fn matches(platform: &str, target: &str) -> bool {
+    let platform = platform.trim().to_lowercase();
+    let target = target.to_lowercase();
+
+    platform == target || target.split('-').next() == Some(platform.as_str())
+}
+
+/// Check if a target satisfies platform requirements
+///
+/// An empty list places no restriction.
+pub fn check_platform_compatibility(allowed_platforms: &[String], target: &str) -> bool {
+    allowed_platforms.is_empty() || allowed_platforms.iter().any(|p| matches(p, target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_platform_matching() {
+        assert!(matches("linux", "linux-aarch64"));
+        assert!(matches("linux-aarch64", "linux-aarch64"));
+        assert!(matches("Windows", "windows-x86_64"));
+        assert!(!matches("linux-x86_64", "linux-aarch64"));
+        assert!(!matches("lin", "linux-x86_64"));
+
+        let allowed = vec!["windows".to_string(), "macos-aarch64".to_string()];
+        assert!(check_platform_compatibility(&allowed, "macos-aarch64"));
+        assert!(!check_platform_compatibility(&allowed, "macos-x86_64"));
+        assert!(check_platform_compatibility(&[], "wasm32"));
+    }
+}
@@ -1,408 +1,1553 @@
-//! Advanced dependency resolution with constraint solving
+//! Transitive dependency resolution with version solving
 //!
-//! This module handles complex dependency resolution scenarios including:
-//! - Version constraints and conflict detection
-//! - Transitive dependency tracking
-//! - Dependency graph analysis with topological sorting
-//! - Support for Git, path, and registry sources
-
-use crate::config::{Dependency, PortersConfig};
-use anyhow::{Context, Result, anyhow};
+//! This module builds the complete dependency graph of a project:
+//! - Recursively fetches Git, archive, path, and registry dependencies
+//! - Reads each dependency's porters.toml for its own dependencies
+//! - Selects one version per package that satisfies every requirement,
+//!   backtracking over registry versions when a choice leads to a conflict
+//! - Explains conflicts with the chain of packages that introduced them
+//! - Activates features and the optional dependencies they enable,
+//!   unifying the features requested for each package across the graph
+//! - Prefers the revisions and versions pinned in porters.lock
+//! - Skips dependencies restricted to platforms other than the target
+//! - Orders the result topologically for proper build sequencing
+
+use super::archive::{self, ArchiveSource};
+use super::features::{self, Activation};
+use super::git::{GitReference, GitSource};
+use super::platform;
+use super::{
+    DependencySource, ResolveOptions, ResolvedDependency, fetch_registry_package,
+    resolve_archive_dependency, resolve_git_dependency, resolve_path_dependency, verify_checksum,
+};
+use crate::config::{Dependency, PortersConfig, RegistryConfig};
+use crate::lockfile::LockFile;
+use crate::registry::{PackageDefinition, Registries};
+use crate::util::pretty::*;
+use crate::version::{Version, VersionReq};
+use anyhow::{Context, Result, anyhow, bail};
 use petgraph::algo::toposort;
 use petgraph::graph::{DiGraph, NodeIndex};
-use semver::Version;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
 
-/// Dependency resolver with constraint solving and graph analysis
-///
-/// Builds a dependency graph, detects conflicts, and produces a
-/// topologically sorted resolution order for proper build sequencing.
+/// Dependency resolver with version solving and graph analysis
 ///
-/// **Note**: Advanced feature for future constraint-based resolution
-#[allow(dead_code)]
+/// Walks the dependency graph starting at the project's porters.toml,
+/// selects a single version per package, and produces a topologically
+/// sorted list of fetched dependencies.
 pub struct DependencyResolver {
-    resolved: HashMap<String, ResolvedDependency>,
-    conflicts: Vec<DependencyConflict>,
-    dep_graph: DiGraph<String, ()>,
-    node_map: HashMap<String, NodeIndex>,
+    cache_dir: PathBuf,
+    registries: Option<Registries>,
+    /// `[[registries]]` of the project being resolved
+    registry_configs: Vec<RegistryConfig>,
+    options: ResolveOptions,
+    /// Previously locked resolution to prefer when selecting versions
+    lock: Option<LockFile>,
+    /// Git, archive and path sources already fetched, keyed by source
+    fetched: HashMap<String, Arc<Candidate>>,
 }
 
-/// Resolved dependency with version and features
-#[allow(dead_code)]
+/// A requirement placed on a package by the project or by another dependency
 #[derive(Debug, Clone)]
-pub struct ResolvedDependency {
-    pub name: String,
-    pub version: Option<Version>,
-    pub source: DependencySource,
-    pub features: Vec<String>,
-    pub transitive_deps: Vec<String>,
+struct Requirement {
+    name: String,
+    spec: Dependency,
+    /// Packages that led to this requirement, starting with the root project
+    chain: Vec<String>,
+    /// Directory relative `path` specs are resolved against (`None` for the root project)
+    base_dir: Option<PathBuf>,
+    /// `base_dir` as porters.lock records it, when the requiring package is
+    /// itself a path dependency
+    source_dir: Option<PathBuf>,
+    /// Features enabled through the requiring package's feature table
+    features: Vec<String>,
+    /// Platforms the requirement applies to (`None` means every platform)
+    ///
+    /// Requirements of a platform-specific package inherit its platforms.
+    platforms: Option<Vec<String>>,
 }
 
-/// Dependency source specification
-#[allow(dead_code)]
-#[derive(Debug, Clone)]
-pub enum DependencySource {
-    Git { url: String, rev: String },
-    Path { path: String },
-    Registry { version: String },
+/// A concrete version of a package that the solver can select
+#[derive(Debug)]
+struct Candidate {
+    version: Option<Version>,
+    label: String,
+    origin: Origin,
+    dependencies: Vec<(String, Dependency)>,
+    /// Dependencies only required when a feature enables them
+    optional: BTreeSet<String>,
+    /// Feature table of the package
+    features: HashMap<String, Vec<String>>,
+    /// Directory the candidate's own path dependencies are relative to
+    base_dir: Option<PathBuf>,
 }
 
-/// Dependency version conflict information
-#[allow(dead_code)]
-#[derive(Debug, Clone)]
-pub struct DependencyConflict {
+#[derive(Debug)]
+enum Origin {
+    /// Registry version, fetched only once it has been selected
+    Registry(Box<PackageDefinition>),
+    /// Git, archive or path source, already fetched to read its manifest
+    Fetched {
+        key: String,
+        resolved: Box<ResolvedDependency>,
+    },
+}
+
+impl Candidate {
+    /// Path porters.lock records for a path dependency
+    fn path_source(&self) -> Option<&str> {
+        match &self.origin {
+            Origin::Fetched { resolved, .. } => match &resolved.source {
+                DependencySource::Path { path } => Some(path),
+                _ => None,
+            },
+            Origin::Registry(_) => None,
+        }
+    }
+}
+
+/// Where a dependency specification points to
+enum SourceSpec<'a> {
+    /// Version requirement and the registry it must be served from
+    Registry(&'a str, Option<&'a str>),
+    Git(GitSource<'a>),
+    Archive(ArchiveSource<'a>),
+    Path(&'a str),
+}
+
+/// Partial solution explored by the solver
+#[derive(Debug, Clone, Default)]
+struct SolverState {
+    selected: HashMap<String, Arc<Candidate>>,
+    requirements: HashMap<String, Vec<Requirement>>,
+    /// Features activated on each selected package
+    activations: HashMap<String, Activation>,
+    pending: VecDeque<Requirement>,
+}
+
+impl SolverState {
+    /// Queue the dependencies a selected package needs on `target` with its active features
+    fn require_dependencies(
+        &mut self,
+        name: &str,
+        candidate: &Candidate,
+        activation: &Activation,
+        chain: &[String],
+        target: &str,
+    ) {
+        let mut chain = chain.to_vec();
+        chain.push(format!("{} {}", name, candidate.label));
+        let inherited = self
+            .requirements
+            .get(name)
+            .and_then(|reqs| requested_platforms(reqs));
+
+        for (dep_name, spec) in &candidate.dependencies {
+            if candidate.optional.contains(dep_name) && !activation.optional_deps.contains(dep_name)
+            {
+                continue;
+            }
+            let platforms = spec.platforms().map(|p| p.to_vec()).or(inherited.clone());
+            if !applies_to(&platforms, target) {
+                continue;
+            }
+            self.pending.push_back(Requirement {
+                name: dep_name.clone(),
+                spec: spec.clone(),
+                chain: chain.clone(),
+                base_dir: candidate.base_dir.clone(),
+                source_dir: candidate.path_source().map(PathBuf::from),
+                features: activation
+                    .dep_features
+                    .get(dep_name)
+                    .map(|f| f.iter().cloned().collect())
+                    .unwrap_or_default(),
+                platforms,
+            });
+        }
+    }
+}
+
+/// No version of a package satisfies every requirement placed on it
+#[derive(Debug)]
+pub struct VersionConflict {
     pub package: String,
-    pub requested_versions: Vec<String>,
+    /// Each requirement with the chain of packages that introduced it
+    pub requirements: Vec<String>,
+    pub available: Vec<String>,
+}
+
+impl fmt::Display for VersionConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Version conflict for {}:", self.package)?;
+        for requirement in &self.requirements {
+            writeln!(f, "  {}", requirement)?;
+        }
+        if self.available.is_empty() {
+            write!(f, "  no candidate versions available")
+        } else {
+            write!(f, "  available: {}", self.available.join(", "))
+        }
+    }
 }
 
-#[allow(dead_code)]
+impl std::error::Error for VersionConflict {}
+
 impl DependencyResolver {
-    pub fn new() -> Self {
+    pub fn new(cache_dir: PathBuf) -> Self {
         Self {
-            resolved: HashMap::new(),
-            conflicts: Vec::new(),
-            dep_graph: DiGraph::new(),
-            node_map: HashMap::new(),
+            cache_dir,
+            registries: None,
+            registry_configs: Vec::new(),
+            options: ResolveOptions::default(),
+            lock: None,
+            fetched: HashMap::new(),
         }
     }
 
-    /// Resolve all dependencies with constraint checking and transitive resolution
-    pub async fn resolve(
-        &mut self,
-        config: &PortersConfig,
-    ) -> Result<HashMap<String, ResolvedDependency>> {
-        let current_platform = get_current_platform();
+    pub fn with_options(mut self, options: ResolveOptions) -> Self {
+        self.options = options;
+        self
+    }
 
-        // Collect all dependencies (regular + dev)
-        let mut all_deps = config.dependencies.clone();
-        all_deps.extend(config.dev_dependencies.clone());
+    /// Prefer the revisions and versions recorded in a lock file
+    pub fn with_lockfile(mut self, lock: LockFile) -> Self {
+        self.lock = Some(lock);
+        self
+    }
 
-        // Resolve dependencies recursively
-        for (name, dep) in all_deps {
-            self.resolve_dependency_recursive(&name, &dep, &current_platform, None)
-                .await?;
+    /// Use specific registries instead of the project's
+    #[cfg(test)]
+    pub fn with_registries(mut self, registries: Registries) -> Self {
+        self.registries = Some(registries);
+        self
+    }
+
+    /// Resolve and fetch the full dependency graph of a project
+    ///
+    /// Returns one entry per package with dependencies ordered before
+    /// their dependents.
+    pub async fn resolve(&mut self, config: &PortersConfig) -> Result<Vec<ResolvedDependency>> {
+        let state = self.solve_config(config).await?;
+        let resolved = self.materialize(&state).await?;
+
+        if self.options.locked {
+            self.check_locked(&resolved)?;
         }
 
-        // Topologically sort to get dependency order
-        match toposort(&self.dep_graph, None) {
-            Ok(sorted) => {
-                // Dependencies are now in correct build order
-                let build_order: Vec<String> = sorted
-                    .iter()
-                    .filter_map(|idx| self.dep_graph.node_weight(*idx).cloned())
-                    .collect();
+        Ok(resolved)
+    }
 
-                if !build_order.is_empty() {
-                    println!(
-                        "📦 Resolved dependency build order: {}",
-                        build_order.join(" → ")
-                    );
-                }
-            }
-            Err(cycle) => {
-                let unknown = "unknown".to_string();
-                let cycle_node = self
-                    .dep_graph
-                    .node_weight(cycle.node_id())
-                    .unwrap_or(&unknown);
+    /// Select a version for every package reachable from the project
+    async fn solve_config(&mut self, config: &PortersConfig) -> Result<SolverState> {
+        let roots = config.platform_dependencies(true);
+        let target = self.options.target();
+        self.registry_configs = config.registries.clone();
+        // `offline = true` in porters.toml forbids network access like --frozen
+        self.options.offline |= config.offline;
+
+        let activation = features::activate_project(config, &self.options.features)?;
+        let optional = features::optional_dependencies(config);
+
+        for dep_name in activation.dep_features.keys() {
+            if !roots.iter().any(|(name, _)| name == dep_name) {
                 return Err(anyhow!(
-                    "Circular dependency detected involving: {}",
-                    cycle_node
+                    "Feature '{}/...' of {} refers to an unknown dependency",
+                    dep_name,
+                    config.project.name
                 ));
             }
         }
 
-        // Check for conflicts
-        if !self.conflicts.is_empty() {
-            return Err(anyhow!(
-                "Dependency conflicts detected:\n{}",
-                self.format_conflicts()
-            ));
+        let mut state = SolverState::default();
+        for (name, dep) in roots {
+            if optional.contains(&name) && !activation.optional_deps.contains(&name) {
+                continue;
+            }
+            let platforms = dep.platforms().map(|p| p.to_vec());
+            if !applies_to(&platforms, &target) {
+                continue;
+            }
+            let features = activation
+                .dep_features
+                .get(&name)
+                .map(|f| f.iter().cloned().collect())
+                .unwrap_or_default();
+            state.pending.push_back(Requirement {
+                name,
+                spec: dep,
+                chain: vec![config.project.name.clone()],
+                base_dir: None,
+                source_dir: None,
+                features,
+                platforms,
+            });
         }
 
-        Ok(self.resolved.clone())
+        self.solve(state).await
     }
 
-    /// Recursively resolve a dependency and its transitive dependencies
-    async fn resolve_dependency_recursive(
-        &mut self,
-        name: &str,
-        dep: &Dependency,
-        platform: &str,
-        parent: Option<&str>,
-    ) -> Result<()> {
-        // Skip if already resolved
-        if self.resolved.contains_key(name) {
-            // Add edge in dependency graph if parent exists
-            if let Some(parent_name) = parent {
-                self.add_dependency_edge(parent_name, name);
+    /// Process pending requirements, backtracking over candidate versions
+    async fn solve(&mut self, mut state: SolverState) -> Result<SolverState> {
+        let target = self.options.target();
+        while let Some(req) = state.pending.pop_front() {
+            let name = req.name.clone();
+            state
+                .requirements
+                .entry(name.clone())
+                .or_default()
+                .push(req.clone());
+
+            if let Some(selected) = state.selected.get(&name).cloned() {
+                if !self.satisfies(&selected, &req)? {
+                    return Err(self.conflict(&name, &state, &[selected]).into());
+                }
+
+                // A new requirement may enable more features of the selected package
+                let activation = activate_candidate(&name, &selected, &state.requirements[&name])?;
+                if state.activations.get(&name) != Some(&activation) {
+                    state.require_dependencies(&name, &selected, &activation, &req.chain, &target);
+                    state.activations.insert(name, activation);
+                }
+                continue;
             }
-            return Ok(());
-        }
 
-        match dep {
-            Dependency::Simple(version) => {
-                let transitive_deps = Vec::new();
-                self.add_resolved(
-                    name,
-                    None,
-                    DependencySource::Registry {
-                        version: version.clone(),
-                    },
-                    vec![],
-                    transitive_deps,
-                )?;
+            let candidates = self.candidates(&req).await?;
 
-                if let Some(parent_name) = parent {
-                    self.add_dependency_edge(parent_name, name);
+            let mut compatible = Vec::new();
+            for candidate in &candidates {
+                let mut ok = true;
+                for requirement in &state.requirements[&name] {
+                    if !self.satisfies(candidate, requirement)? {
+                        ok = false;
+                        break;
+                    }
+                }
+                if ok {
+                    compatible.push(candidate.clone());
                 }
             }
-            Dependency::Detailed {
-                version,
-                git,
-                path,
-                features,
-                platforms,
-                constraints,
-                ..
-            } => {
-                // Check platform compatibility
-                if let Some(allowed_platforms) = platforms
-                    && !allowed_platforms.contains(&platform.to_string())
-                {
-                    // Skip platform-specific dependency
-                    return Ok(());
+
+            let mut last_conflict = None;
+            for candidate in compatible {
+                let mut next = state.clone();
+                let activation = activate_candidate(&name, &candidate, &state.requirements[&name])?;
+
+                next.require_dependencies(&name, &candidate, &activation, &req.chain, &target);
+                next.activations.insert(name.clone(), activation);
+                next.selected.insert(name.clone(), candidate);
+
+                match Box::pin(self.solve(next)).await {
+                    Ok(solved) => return Ok(solved),
+                    Err(e) if e.is::<VersionConflict>() => last_conflict = Some(e),
+                    Err(e) => return Err(e),
                 }
+            }
+
+            return Err(
+                last_conflict.unwrap_or_else(|| self.conflict(&name, &state, &candidates).into())
+            );
+        }
 
-                // Check version constraints
-                if let Some(constraint) = &**constraints
-                    && !self.check_constraint(constraint)?
+        Ok(state)
+    }
+
+    /// Gather candidate versions for a requirement, newest first
+    async fn candidates(&mut self, req: &Requirement) -> Result<Vec<Arc<Candidate>>> {
+        match source_spec(&req.name, &req.spec)? {
+            SourceSpec::Registry(_, registry) => {
+                let target = self.options.target();
+                let locked = self.locked_registry_version(&req.name);
+                let is_locked = |pkg: &PackageDefinition| {
+                    locked.as_ref().is_some_and(|(registry, version)| {
+                        pkg.registry == *registry && pkg.version == *version
+                    })
+                };
+                let registries = self.registries()?;
+                let mut candidates: Vec<Candidate> = registries
+                    .load_versions(&req.name, registry)
+                    .with_context(|| format!("Failed to resolve registry dependency {}", req.name))?
+                    .into_iter()
+                    // Versions that don't support the target can't be selected
+                    .filter(|pkg| platform::check_platform_compatibility(&pkg.platforms, &target))
+                    // Yanked versions are only kept when porters.lock already uses them
+                    .filter(|pkg| !pkg.yanked || is_locked(pkg))
+                    .filter_map(|pkg| {
+                        let manifest = registry_manifest(&pkg);
+                        Some(Candidate {
+                            label: pkg.version.clone(),
+                            version: Some(manifest.version?),
+                            origin: Origin::Registry(Box::new(pkg)),
+                            dependencies: manifest.dependencies,
+                            optional: manifest.optional,
+                            features: manifest.features,
+                            base_dir: None,
+                        })
+                    })
+                    .collect();
+
+                candidates.sort_by(|a, b| b.version.cmp(&a.version));
+
+                // Try the locked version first so unchanged projects keep it
+                if let Some((locked_registry, locked)) = locked
+                    && let Some(pos) = candidates.iter().position(|c| {
+                        c.label == locked
+                            && matches!(&c.origin, Origin::Registry(pkg) if pkg.registry == locked_registry)
+                    })
                 {
-                    return Err(anyhow!(
-                        "Constraint '{}' not satisfied for {}",
-                        constraint,
-                        name
-                    ));
+                    let candidate = candidates.remove(pos);
+                    candidates.insert(0, candidate);
                 }
 
-                // Determine source and resolve transitively
-                let (source, transitive_deps) = if let Some(git_url) = git {
-                    // For git dependencies, could parse their config (future enhancement)
-                    let deps = Vec::new(); // Simplified for now
-                    (
-                        DependencySource::Git {
-                            url: git_url.clone(),
-                            rev: "HEAD".to_string(),
-                        },
-                        deps,
-                    )
-                } else if let Some(path_str) = path {
-                    // For path dependencies, parse their porters.toml
-                    let deps = self.resolve_path_transitive(path_str)?;
-                    (
-                        DependencySource::Path {
-                            path: path_str.clone(),
-                        },
-                        deps,
-                    )
-                } else {
-                    // Registry dependencies (future feature)
-                    (
-                        DependencySource::Registry {
-                            version: version.clone().unwrap_or("*".to_string()),
+                Ok(candidates.into_iter().map(Arc::new).collect())
+            }
+            SourceSpec::Git(source) => {
+                let key = source_key(req)?;
+                if let Some(candidate) = self.fetched.get(&key) {
+                    return Ok(vec![candidate.clone()]);
+                }
+
+                print_package(&format!("Resolving {}...", req.name));
+                let locked_rev = match source.rev {
+                    Some(_) => None,
+                    None => self.locked_git_rev(&req.name, source.url, source.branch, source.tag),
+                };
+                let source = GitSource {
+                    rev: source.rev.or(locked_rev.as_deref()),
+                    ..source
+                };
+                let resolved = resolve_git_dependency(
+                    &req.name,
+                    &source,
+                    &self.cache_dir,
+                    self.options.offline,
+                )
+                .await?;
+                Ok(vec![self.fetched_candidate(key, resolved)])
+            }
+            SourceSpec::Archive(source) => {
+                let key = source_key(req)?;
+                if let Some(candidate) = self.fetched.get(&key) {
+                    return Ok(vec![candidate.clone()]);
+                }
+
+                print_package(&format!("Resolving {}...", req.name));
+                let locked_sha256 = match source.sha256 {
+                    Some(_) => None,
+                    None => self.locked_archive_sha256(&req.name, source.url),
+                };
+                let source = ArchiveSource {
+                    sha256: source.sha256.or(locked_sha256.as_deref()),
+                    ..source
+                };
+                // An unpinned archive could change under the lock file
+                if source.sha256.is_none() && self.options.locked {
+                    bail!(
+                        "{} has no sha256 in porters.toml or porters.lock, which --locked and --frozen require for archive dependencies",
+                        req.name
+                    );
+                }
+                let resolved = resolve_archive_dependency(
+                    &req.name,
+                    &source,
+                    &self.cache_dir,
+                    self.options.offline,
+                )
+                .await?;
+                Ok(vec![self.fetched_candidate(key, resolved)])
+            }
+            SourceSpec::Path(path) => {
+                let key = source_key(req)?;
+                if let Some(candidate) = self.fetched.get(&key) {
+                    return Ok(vec![candidate.clone()]);
+                }
+
+                print_package(&format!("Resolving {}...", req.name));
+                let (full, recorded) = match &req.base_dir {
+                    Some(base) => (
+                        base.join(path).to_string_lossy().to_string(),
+                        // Relative to the project when reached through path
+                        // dependencies, otherwise to the declaring package
+                        match &req.source_dir {
+                            Some(dir) => normalize_path(&dir.join(path)),
+                            None => path.to_string(),
                         },
-                        Vec::new(),
-                    )
+                    ),
+                    None => (path.to_string(), path.to_string()),
                 };
+                let mut resolved = resolve_path_dependency(&req.name, &full)?;
+                resolved.source = DependencySource::Path { path: recorded };
+                Ok(vec![self.fetched_candidate(key, resolved)])
+            }
+        }
+    }
+
+    /// Turn a fetched Git, archive or path source into a candidate by reading its manifest
+    fn fetched_candidate(&mut self, key: String, resolved: ResolvedDependency) -> Arc<Candidate> {
+        let manifest = read_manifest(&resolved.path);
+        let candidate = Arc::new(Candidate {
+            label: manifest
+                .version
+                .as_ref()
+                .map(|v| v.to_string())
+                .unwrap_or_else(|| resolved.version.clone()),
+            version: manifest.version,
+            dependencies: manifest.dependencies,
+            optional: manifest.optional,
+            features: manifest.features,
+            base_dir: Some(resolved.path.clone()),
+            origin: Origin::Fetched {
+                key: key.clone(),
+                resolved: Box::new(resolved),
+            },
+        });
+        self.fetched.insert(key, candidate.clone());
+        candidate
+    }
+
+    /// Check whether a candidate satisfies a requirement
+    fn satisfies(&self, candidate: &Candidate, req: &Requirement) -> Result<bool> {
+        let version_matches = |version_req: &str| -> Result<bool> {
+            let parsed = VersionReq::parse(version_req).with_context(|| {
+                format!(
+                    "Invalid version requirement '{}' for {}",
+                    version_req, req.name
+                )
+            })?;
+            // Sources without a declared version can't be checked, so accept them
+            Ok(candidate
+                .version
+                .as_ref()
+                .is_none_or(|version| parsed.matches(version)))
+        };
+
+        match (source_spec(&req.name, &req.spec)?, &candidate.origin) {
+            (SourceSpec::Registry(version_req, registry), origin) => {
+                // A requirement naming a registry only accepts versions served by it
+                if let (Some(registry), Origin::Registry(pkg)) = (registry, origin)
+                    && pkg.registry != registry
+                {
+                    return Ok(false);
+                }
+                version_matches(version_req)
+            }
+            (_, Origin::Registry(_)) => Ok(false),
+            (spec, Origin::Fetched { key, resolved }) => {
+                if *key != source_key(req)? {
+                    return Ok(false);
+                }
+                // Requirements on the same archive may pin different hashes
+                if let (
+                    SourceSpec::Archive(ArchiveSource {
+                        sha256: Some(pinned),
+                        ..
+                    }),
+                    DependencySource::Archive { sha256, .. },
+                ) = (spec, &resolved.source)
+                    && archive::normalize_sha256(pinned) != *sha256
+                {
+                    return Ok(false);
+                }
+                match &req.spec {
+                    Dependency::Detailed {
+                        version: Some(version_req),
+                        ..
+                    } => version_matches(version_req),
+                    _ => Ok(true),
+                }
+            }
+        }
+    }
 
-                let ver = version.as_ref().and_then(|v| Version::parse(v).ok());
+    fn conflict(
+        &self,
+        package: &str,
+        state: &SolverState,
+        candidates: &[Arc<Candidate>],
+    ) -> VersionConflict {
+        VersionConflict {
+            package: package.to_string(),
+            requirements: state
+                .requirements
+                .get(package)
+                .map(|reqs| reqs.iter().map(describe_requirement).collect())
+                .unwrap_or_default(),
+            available: candidates.iter().map(|c| c.label.clone()).collect(),
+        }
+    }
 
-                self.add_resolved(
-                    name,
-                    ver,
-                    source.clone(),
-                    features.clone(),
-                    transitive_deps.clone(),
-                )?;
+    /// Fetch the selected packages and order them for building
+    async fn materialize(&mut self, state: &SolverState) -> Result<Vec<ResolvedDependency>> {
+        let mut names: Vec<&String> = state.selected.keys().collect();
+        names.sort();
 
-                if let Some(parent_name) = parent {
-                    self.add_dependency_edge(parent_name, name);
+        let mut resolved = HashMap::new();
+        for name in names {
+            let candidate = &state.selected[name];
+            let mut dep = match &candidate.origin {
+                Origin::Registry(pkg) => {
+                    print_package(&format!("Resolving {} v{}...", name, pkg.version));
+                    fetch_registry_package(name, pkg, &self.cache_dir, self.options.offline).await?
                 }
+                Origin::Fetched { resolved, .. } => (**resolved).clone(),
+            };
+
+            // Checksums pinned in a manifest must match the fetched sources
+            let checksum = match &dep.checksum {
+                Some(checksum) => checksum.clone(),
+                None => crate::hash::calculate_directory_hash(&dep.path)?,
+            };
+            for req in &state.requirements[name] {
+                verify_checksum(name, &req.spec, &checksum)?;
+            }
+
+            dep.dependencies = candidate
+                .dependencies
+                .iter()
+                .map(|(dep_name, _)| dep_name.clone())
+                .filter(|dep_name| state.selected.contains_key(dep_name))
+                .collect();
+            dep.dependencies.sort();
 
-                // Recursively resolve transitive dependencies
-                for trans_dep_name in &transitive_deps {
-                    if let Some(trans_dep_spec) =
-                        self.get_transitive_dep_spec(trans_dep_name, &source).await
+            dep.features = state.activations[name].features.iter().cloned().collect();
+            dep.platforms = requested_platforms(&state.requirements[name]);
+
+            // Toolchain constraints of the registry entry and of every requirement
+            if let Origin::Registry(pkg) = &candidate.origin
+                && let Some(constraints) = &pkg.constraints
+            {
+                dep.constraints.push(constraints.into());
+            }
+            for req in &state.requirements[name] {
+                if let Dependency::Detailed { constraints, .. } = &req.spec
+                    && let Some(constraints) = constraints.as_ref()
+                {
+                    dep.constraints.push(constraints.clone());
+                }
+            }
+
+            // Build options set in porters.toml override the registry's
+            if let Some(build) = &mut dep.build {
+                for req in &state.requirements[name] {
+                    if let Dependency::Detailed { options, .. } = &req.spec
+                        && let Some(options) = options.as_ref()
                     {
-                        Box::pin(self.resolve_dependency_recursive(
-                            trans_dep_name,
-                            &trans_dep_spec,
-                            platform,
-                            Some(name),
-                        ))
-                        .await?;
+                        build.override_options(name, options)?;
                     }
                 }
             }
+
+            resolved.insert(name.clone(), dep);
         }
 
-        Ok(())
-    }
+        // Edges point from a dependency to its dependents so the sort puts dependencies first
+        let mut graph: DiGraph<String, ()> = DiGraph::new();
+        let mut nodes: HashMap<String, NodeIndex> = HashMap::new();
+        let mut sorted_names: Vec<&String> = resolved.keys().collect();
+        sorted_names.sort();
+        for name in sorted_names {
+            nodes.insert(name.clone(), graph.add_node(name.clone()));
+        }
+        for (name, dep) in &resolved {
+            for child in &dep.dependencies {
+                graph.add_edge(nodes[child], nodes[name], ());
+            }
+        }
 
-    /// Resolve transitive dependencies from a path source
-    fn resolve_path_transitive(&self, path: &str) -> Result<Vec<String>> {
-        let dep_config_path = PathBuf::from(path).join("porters.toml");
+        let order = toposort(&graph, None).map_err(|cycle| {
+            let unknown = "unknown".to_string();
+            let node = graph.node_weight(cycle.node_id()).unwrap_or(&unknown);
+            anyhow!("Circular dependency detected involving: {}", node)
+        })?;
 
-        if !dep_config_path.exists() {
-            // Dependency doesn't have porters.toml, no transitive deps
-            return Ok(Vec::new());
+        let build_order: Vec<String> = order.iter().map(|idx| graph[*idx].clone()).collect();
+        if build_order.len() > 1 {
+            print_info(&format!(
+                "📦 Resolved dependency build order: {}",
+                build_order.join(" → ")
+            ));
         }
 
-        // Load the dependency's configuration
-        match PortersConfig::load(&dep_config_path) {
-            Ok(dep_config) => {
-                // Extract all dependency names
-                let mut trans_deps = Vec::new();
-                trans_deps.extend(dep_config.dependencies.keys().cloned());
-                // Don't include dev dependencies of dependencies by default
-                Ok(trans_deps)
+        Ok(build_order
+            .into_iter()
+            .filter_map(|name| resolved.remove(&name))
+            .collect())
+    }
+
+    /// Locked revision of a Git dependency, if porters.lock pins the same source
+    fn locked_git_rev(
+        &self,
+        name: &str,
+        url: &str,
+        branch: Option<&str>,
+        tag: Option<&str>,
+    ) -> Option<String> {
+        match &self.lock.as_ref()?.dependencies.get(name)?.source {
+            DependencySource::Git {
+                url: locked_url,
+                rev,
+                branch: locked_branch,
+                tag: locked_tag,
+                ..
+            } if normalize_url(locked_url) == normalize_url(url)
+                && locked_branch.as_deref() == branch
+                && locked_tag.as_deref() == tag =>
+            {
+                Some(rev.clone())
             }
-            Err(_) => {
-                // Failed to parse config, assume no transitive deps
-                Ok(Vec::new())
+            _ => None,
+        }
+    }
+
+    /// Locked archive hash of an archive dependency, if porters.lock pins the same URL
+    fn locked_archive_sha256(&self, name: &str, url: &str) -> Option<String> {
+        match &self.lock.as_ref()?.dependencies.get(name)?.source {
+            DependencySource::Archive {
+                url: locked_url,
+                sha256,
+                ..
+            } if locked_url == url => Some(sha256.clone()),
+            _ => None,
+        }
+    }
+
+    /// Registry and locked version of a registry dependency
+    fn locked_registry_version(&self, name: &str) -> Option<(String, String)> {
+        let locked = self.lock.as_ref()?.dependencies.get(name)?;
+        match &locked.source {
+            DependencySource::Registry { registry } => {
+                Some((registry.clone(), locked.version.clone()))
             }
+            _ => None,
         }
     }
 
-    /// Get transitive dependency specification
-    async fn get_transitive_dep_spec(
-        &self,
-        trans_dep_name: &str,
-        source: &DependencySource,
-    ) -> Option<Dependency> {
-        match source {
-            DependencySource::Path { path } => {
-                let dep_config_path = PathBuf::from(path).join("porters.toml");
-                if let Ok(dep_config) = PortersConfig::load(&dep_config_path) {
-                    // Try regular dependencies
-                    if let Some(dep) = dep_config.dependencies.get(trans_dep_name) {
-                        return Some(dep.clone());
-                    }
+    /// Ensure the resolution matches porters.lock exactly
+    fn check_locked(&self, resolved: &[ResolvedDependency]) -> Result<()> {
+        let empty = LockFile::default();
+        let lock = self.lock.as_ref().unwrap_or(&empty);
+        let mut problems = Vec::new();
+
+        for dep in resolved {
+            let Some(locked) = lock.dependencies.get(&dep.name) else {
+                problems.push(format!("{} is not in porters.lock", dep.name));
+                continue;
+            };
+
+            let matches = match (&dep.source, &locked.source) {
+                (
+                    DependencySource::Git { url, rev, .. },
+                    DependencySource::Git {
+                        url: locked_url,
+                        rev: locked_rev,
+                        ..
+                    },
+                ) => normalize_url(url) == normalize_url(locked_url) && rev == locked_rev,
+                (
+                    DependencySource::Archive { url, sha256, .. },
+                    DependencySource::Archive {
+                        url: locked_url,
+                        sha256: locked_sha256,
+                        ..
+                    },
+                ) => url == locked_url && sha256 == locked_sha256,
+                (DependencySource::Path { path }, DependencySource::Path { path: locked_path }) => {
+                    path == locked_path
                 }
-                None
+                (
+                    DependencySource::Registry { registry },
+                    DependencySource::Registry {
+                        registry: locked_registry,
+                    },
+                ) => registry == locked_registry && dep.version == locked.version,
+                _ => false,
+            };
+
+            if !matches {
+                problems.push(format!(
+                    "{} resolves to {} but porters.lock has {}",
+                    dep.name, dep.version, locked.version
+                ));
+            } else if dep.features != locked.features {
+                problems.push(format!(
+                    "{} is built with features [{}] but porters.lock has [{}]",
+                    dep.name,
+                    dep.features.join(", "),
+                    locked.features.join(", ")
+                ));
             }
-            _ => {
-                // Git and Registry not yet implemented for transitive resolution
-                None
+        }
+
+        // Entries locked for other platforms are kept for builds on those platforms
+        let target = self.options.target();
+        let mut extra: Vec<&String> = lock
+            .dependencies
+            .iter()
+            .filter(|(_, locked)| applies_to(&locked.platforms, &target))
+            .map(|(name, _)| name)
+            .filter(|name| !resolved.iter().any(|dep| &dep.name == *name))
+            .collect();
+        extra.sort();
+        for name in extra {
+            problems.push(format!("{} is locked but no longer required", name));
+        }
+
+        if problems.is_empty() {
+            return Ok(());
+        }
+
+        Err(anyhow!(
+            "🔒 porters.lock is out of date and --locked was passed:\n  {}\nRun 'porters lock' to update it",
+            problems.join("\n  ")
+        ))
+    }
+
+    fn registries(&mut self) -> Result<&Registries> {
+        if self.registries.is_none() {
+            let registries =
+                Registries::from_config(&self.registry_configs)?.with_offline(self.options.offline);
+            // Offline resolution relies on the indexes that are already present
+            if !self.options.offline {
+                registries.init()?;
+            }
+            self.registries = Some(registries);
+        }
+        Ok(self.registries.as_ref().unwrap())
+    }
+}
+
+/// Classify a dependency specification by source
+fn source_spec<'a>(name: &str, spec: &'a Dependency) -> Result<SourceSpec<'a>> {
+    match spec {
+        Dependency::Simple(version) => Ok(SourceSpec::Registry(version, None)),
+        Dependency::Detailed {
+            version,
+            git,
+            branch,
+            tag,
+            rev,
+            path,
+            subdir,
+            url,
+            sha256,
+            strip_prefix,
+            registry,
+            ..
+        } => {
+            if let Some(git_url) = git {
+                Ok(SourceSpec::Git(GitSource {
+                    url: git_url,
+                    branch: branch.as_deref(),
+                    tag: tag.as_deref(),
+                    rev: rev.as_deref(),
+                    subdir: subdir.as_ref().as_deref(),
+                }))
+            } else if let Some(url) = url.as_ref() {
+                Ok(SourceSpec::Archive(ArchiveSource {
+                    url,
+                    sha256: sha256.as_ref().as_deref(),
+                    strip_prefix: strip_prefix.as_ref().as_deref(),
+                }))
+            } else if let Some(path) = path {
+                Ok(SourceSpec::Path(path))
+            } else if let Some(version) = version {
+                Ok(SourceSpec::Registry(version, registry.as_ref().as_deref()))
+            } else {
+                Err(anyhow!("Invalid dependency specification for {}", name))
             }
         }
     }
+}
 
-    /// Add an edge in the dependency graph
-    fn add_dependency_edge(&mut self, parent: &str, child: &str) {
-        let parent_idx = *self
-            .node_map
-            .entry(parent.to_string())
-            .or_insert_with(|| self.dep_graph.add_node(parent.to_string()));
+/// Identify the source a Git, archive or path requirement points to
+fn source_key(req: &Requirement) -> Result<String> {
+    match source_spec(&req.name, &req.spec)? {
+        SourceSpec::Git(source) => {
+            let reference = match source.reference() {
+                GitReference::Rev(r) | GitReference::Tag(r) | GitReference::Branch(r) => r,
+                GitReference::DefaultBranch => "HEAD",
+            };
+            let mut key = format!("git:{}#{}", normalize_url(source.url), reference);
+            if let Some(subdir) = source.subdir {
+                key.push_str(&format!(":{}", subdir.trim_matches('/')));
+            }
+            Ok(key)
+        }
+        SourceSpec::Archive(source) => {
+            let mut key = format!("archive:{}", source.url);
+            if let Some(prefix) = source.strip_prefix {
+                key.push_str(&format!(":{}", prefix.trim_matches('/')));
+            }
+            Ok(key)
+        }
+        SourceSpec::Path(path) => {
+            let full = match &req.base_dir {
+                Some(base) => base.join(path),
+                None => PathBuf::from(path),
+            };
+            let full = full.canonicalize().unwrap_or(full);
+            Ok(format!("path:{}", full.display()))
+        }
+        SourceSpec::Registry(..) => Ok(format!("registry:{}", req.name)),
+    }
+}
 
-        let child_idx = *self
-            .node_map
-            .entry(child.to_string())
-            .or_insert_with(|| self.dep_graph.add_node(child.to_string()));
+/// Remove `.` and inner `..` components without touching the file system
+///
+/// Leading `..` components are kept, so a path stays relative to the same
+/// directory. Separators are written as `/` to keep porters.lock portable.
+fn normalize_path(path: &Path) -> String {
+    let mut root = String::new();
+    let mut parts: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Prefix(prefix) => root.push_str(&prefix.as_os_str().to_string_lossy()),
+            Component::RootDir => root.push('/'),
+            Component::CurDir => {}
+            Component::ParentDir => match parts.last() {
+                Some(last) if last != ".." => {
+                    parts.pop();
+                }
+                _ => parts.push("..".to_string()),
+            },
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+        }
+    }
+    if root.is_empty() && parts.is_empty() {
+        return ".".to_string();
+    }
+    root + &parts.join("/")
+}
 
-        self.dep_graph.add_edge(parent_idx, child_idx, ());
+/// Activate the features every requirement asks of a package
+fn activate_candidate(
+    name: &str,
+    candidate: &Candidate,
+    requirements: &[Requirement],
+) -> Result<Activation> {
+    let mut requested = Vec::new();
+    for req in requirements {
+        if let Dependency::Detailed { features, .. } = &req.spec {
+            requested.extend(features.iter().cloned());
+        }
+        requested.extend(req.features.iter().cloned());
     }
 
-    fn add_resolved(
-        &mut self,
-        name: &str,
-        version: Option<Version>,
-        source: DependencySource,
-        features: Vec<String>,
-        transitive_deps: Vec<String>,
-    ) -> Result<()> {
-        if let Some(existing) = self.resolved.get(name) {
-            // Check for version conflicts
-            if let (Some(existing_ver), Some(new_ver)) = (&existing.version, &version)
-                && existing_ver != new_ver
-            {
-                self.conflicts.push(DependencyConflict {
-                    package: name.to_string(),
-                    requested_versions: vec![existing_ver.to_string(), new_ver.to_string()],
-                });
+    features::activate(
+        name,
+        &candidate.features,
+        &candidate.optional,
+        &requested,
+        true,
+    )
+}
+
+/// Dependencies and feature table of a registry package
+///
+/// Dependencies that only appear under a feature are optional and are
+/// enabled by it; features marked `default` make up the default feature.
+fn registry_manifest(pkg: &PackageDefinition) -> Manifest {
+    let mut dependencies: Vec<(String, Dependency)> = pkg
+        .dependencies
+        .iter()
+        .map(|(name, req)| (name.clone(), Dependency::Simple(req.clone())))
+        .collect();
+    let mut optional = BTreeSet::new();
+    let mut table = HashMap::new();
+    let mut defaults = Vec::new();
+
+    let mut feature_names: Vec<&String> = pkg.features.keys().collect();
+    feature_names.sort();
+    for feature in feature_names {
+        let definition = &pkg.features[feature];
+        let mut values = Vec::new();
+        let mut deps: Vec<(&String, &String)> = definition.dependencies.iter().collect();
+        deps.sort();
+        for (dep, req) in deps {
+            if !pkg.dependencies.contains_key(dep) && optional.insert(dep.clone()) {
+                dependencies.push((dep.clone(), Dependency::Simple(req.clone())));
+            }
+            if optional.contains(dep) {
+                values.push(format!("dep:{}", dep));
             }
-        } else {
-            self.resolved.insert(
-                name.to_string(),
-                ResolvedDependency {
-                    name: name.to_string(),
-                    version,
-                    source,
-                    features,
-                    transitive_deps,
-                },
-            );
         }
+        if definition.default {
+            defaults.push(feature.clone());
+        }
+        table.insert(feature.clone(), values);
+    }
+
+    if !defaults.is_empty() {
+        table.insert("default".to_string(), defaults);
+    }
+
+    dependencies.sort_by(|a, b| a.0.cmp(&b.0));
+    Manifest {
+        version: Version::parse(&pkg.version).ok(),
+        dependencies,
+        optional,
+        features: table,
+    }
+}
+
+/// Platforms a package is needed on
+///
+/// A package is only restricted when every requirement restricts it.
+fn requested_platforms(requirements: &[Requirement]) -> Option<Vec<String>> {
+    let mut platforms = Vec::new();
+    for req in requirements {
+        platforms.extend(req.platforms.as_ref()?.iter().cloned());
+    }
+    platforms.sort();
+    platforms.dedup();
+    Some(platforms)
+}
 
-        Ok(())
+/// Check whether a platform restriction includes the target
+fn applies_to(platforms: &Option<Vec<String>>, target: &str) -> bool {
+    platforms
+        .as_ref()
+        .is_none_or(|platforms| platform::check_platform_compatibility(platforms, target))
+}
+
+/// Strip the trailing slash and `.git` suffix so equivalent Git URLs compare equal
+fn normalize_url(url: &str) -> &str {
+    url.trim_end_matches('/').trim_end_matches(".git")
+}
+
+/// The parts of a dependency's porters.toml the resolver needs
+#[derive(Default)]
+struct Manifest {
+    version: Option<Version>,
+    dependencies: Vec<(String, Dependency)>,
+    optional: BTreeSet<String>,
+    features: HashMap<String, Vec<String>>,
+}
+
+/// Read the version, dependencies and features from a package's porters.toml
+fn read_manifest(dir: &Path) -> Manifest {
+    let manifest = dir.join("porters.toml");
+    if !manifest.exists() {
+        return Manifest::default();
     }
 
-    fn check_constraint(&self, constraint: &str) -> Result<bool> {
-        // Simple constraint checking - can be extended
-        // Format: "min_version >= 1.0.0", "platform == linux", etc.
+    match PortersConfig::load(&manifest) {
+        Ok(config) => {
+            let optional = features::optional_dependencies(&config);
+            let deps = config.platform_dependencies(false);
 
-        if constraint.contains(">=") {
-            // Version constraint (simplified)
-            Ok(true)
-        } else if constraint.contains("==") {
-            // Platform or other equality check
-            let parts: Vec<&str> = constraint.split("==").map(|s| s.trim()).collect();
-            if parts.len() == 2 && parts[0] == "platform" {
-                let current_platform = get_current_platform();
-                Ok(parts[1] == current_platform)
+            if !deps.is_empty() {
+                print_info(&format!("  Found {} nested dependencies", deps.len()));
+            }
+
+            Manifest {
+                version: Version::parse(&config.project.version).ok(),
+                dependencies: deps,
+                optional,
+                features: config.features,
+            }
+        }
+        Err(e) => {
+            print_warning(&format!(
+                "Ignoring unreadable {}: {}",
+                manifest.display(),
+                e
+            ));
+            Manifest::default()
+        }
+    }
+}
+
+fn describe_requirement(req: &Requirement) -> String {
+    let spec = match &req.spec {
+        Dependency::Simple(version) => version.clone(),
+        Dependency::Detailed {
+            version,
+            git,
+            branch,
+            tag,
+            rev,
+            path,
+            url,
+            registry,
+            ..
+        } => {
+            let source = if let Some(git_url) = git {
+                let reference = rev.as_ref().or(tag.as_ref()).or(branch.as_ref());
+                match reference {
+                    Some(r) => format!("git {}@{}", git_url, r),
+                    None => format!("git {}", git_url),
+                }
+            } else if let Some(url) = url.as_ref() {
+                format!("archive {}", url)
+            } else if let Some(path) = path {
+                format!("path {}", path)
+            } else if let Some(registry) = registry.as_ref() {
+                format!("registry {}", registry)
             } else {
-                Ok(true)
+                String::new()
+            };
+            match (version, source.is_empty()) {
+                (Some(v), true) => v.clone(),
+                (Some(v), false) => format!("{} ({})", v, source),
+                (None, _) => source,
             }
-        } else {
-            Ok(true)
         }
+    };
+
+    format!("{} requires {} {}", req.chain.join(" → "), req.name, spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::RegistryManager;
+    use tempfile::TempDir;
+
+    fn write_package(dir: &Path, name: &str, version: &str, deps: &str) {
+        std::fs::create_dir_all(dir.join("include")).unwrap();
+        std::fs::write(dir.join("include").join(format!("{}.h", name)), "").unwrap();
+        std::fs::write(
+            dir.join("porters.toml"),
+            format!(
+                "[project]\nname = \"{}\"\nversion = \"{}\"\n\n[dependencies]\n{}",
+                name, version, deps
+            ),
+        )
+        .unwrap();
     }
 
-    fn format_conflicts(&self) -> String {
-        self.conflicts
-            .iter()
-            .map(|c| {
-                format!(
-                    "  {} requires versions: {}",
-                    c.package,
-                    c.requested_versions.join(", ")
-                )
-            })
-            .collect::<Vec<_>>()
-            .join("\n")
+    fn write_registry_entry(registry: &Path, name: &str, version: &str, deps: serde_json::Value) {
+        let pkg = serde_json::json!({
+            "name": name,
+            "description": "A test package",
+            "repository": format!("https://example.com/{}", name),
+            "version": version,
+            "license": "MIT",
+            "build_system": "cmake",
+            "dependencies": deps
+        });
+        std::fs::write(registry.join(format!("{}.json", name)), pkg.to_string()).unwrap();
     }
-}
 
-/// Get current platform identifier (utility function)
-#[allow(dead_code)]
-pub fn get_current_platform() -> String {
-    #[cfg(target_os = "windows")]
-    return "windows".to_string();
+    fn test_registry(temp: &TempDir) -> RegistryManager {
+        let registry_path = temp.path().join("registry");
+        std::fs::create_dir_all(&registry_path).unwrap();
+        write_registry_entry(
+            &registry_path,
+            "a",
+            "1.0.0",
+            serde_json::json!({ "c": "^2" }),
+        );
+        write_registry_entry(
+            &registry_path,
+            "b",
+            "1.0.0",
+            serde_json::json!({ "c": "*" }),
+        );
+        write_registry_entry(&registry_path, "c", "1.5.0", serde_json::json!({}));
 
-    #[cfg(target_os = "macos")]
-    return "macos".to_string();
+        let manager = RegistryManager::new(registry_path, temp.path().join("cache"));
+        manager.init().unwrap();
+        manager
+    }
 
-    #[cfg(target_os = "linux")]
-    return "linux".to_string();
+    fn project(deps: &str) -> PortersConfig {
+        toml::from_str(&format!(
+            "[project]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\n{}",
+            deps
+        ))
+        .unwrap()
+    }
 
-    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
-    return "unknown".to_string();
-}
+    #[tokio::test]
+    async fn test_transitive_path_dependencies() {
+        let temp = TempDir::new().unwrap();
+        let a = temp.path().join("a");
+        let b = temp.path().join("b");
+        write_package(
+            &a,
+            "a",
+            "1.0.0",
+            "b = { path = \"../b\", constraints = { arch = [\"aarch64\"] } }\n",
+        );
+        write_package(&b, "b", "2.1.0", "");
+
+        let config = project(&format!("a = {{ path = {:?} }}\n", a.to_string_lossy()));
+        let mut resolver = DependencyResolver::new(temp.path().join("cache"));
+        let resolved = resolver.resolve(&config).await.unwrap();
+
+        let names: Vec<&str> = resolved.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["b", "a"]);
+        assert_eq!(resolved[1].dependencies, vec!["b".to_string()]);
+        // Constraints declared by a dependency's manifest carry over
+        assert_eq!(resolved[0].constraints.len(), 1);
+        assert_eq!(resolved[0].constraints[0].arch, vec!["aarch64"]);
+    }
+
+    #[tokio::test]
+    async fn test_nested_path_dependencies_round_trip_through_lockfile() {
+        let temp = TempDir::new().unwrap();
+        let a = temp.path().join("a");
+        write_package(&a, "a", "1.0.0", "b = { path = \"../b\" }\n");
+        write_package(
+            &temp.path().join("b"),
+            "b",
+            "1.0.0",
+            "c = { path = \"./deps/c\" }\n",
+        );
+        write_package(&temp.path().join("b/deps/c"), "c", "1.0.0", "");
+
+        // Recorded paths follow the declared ones instead of the canonical location
+        let root = temp.path().join(".").join("a");
+        let config = project(&format!("a = {{ path = {:?} }}\n", root.to_string_lossy()));
+        let resolved = DependencyResolver::new(temp.path().join("cache"))
+            .resolve(&config)
+            .await
+            .unwrap();
+
+        let lock_path = temp.path().join("porters.lock");
+        LockFile::from_resolved(&resolved).save(&lock_path).unwrap();
+        let lock = LockFile::load(&lock_path).unwrap();
+        let source = |name: &str| match &lock.get_dependency(name).unwrap().source {
+            DependencySource::Path { path } => path.clone(),
+            other => panic!("{} has source {:?}", name, other),
+        };
+        let dir = normalize_path(temp.path());
+        assert_eq!(source("a"), root.to_string_lossy());
+        assert_eq!(source("b"), format!("{}/b", dir));
+        assert_eq!(source("c"), format!("{}/b/deps/c", dir));
+
+        let mut resolver = DependencyResolver::new(temp.path().join("cache"))
+            .with_options(ResolveOptions::from_flags(true, false))
+            .with_lockfile(lock);
+        assert!(resolver.resolve(&config).await.is_ok());
 
-/// Check if current environment satisfies platform requirements
-#[allow(dead_code)]
-pub fn check_platform_compatibility(allowed_platforms: &[String]) -> bool {
-    let current = get_current_platform();
-    allowed_platforms.is_empty() || allowed_platforms.contains(&current)
+        assert_eq!(normalize_path(Path::new("../a/../b/./c")), "../b/c");
+        assert_eq!(normalize_path(Path::new("a/../../b")), "../b");
+        assert_eq!(normalize_path(Path::new("a/..")), ".");
+    }
+
+    #[tokio::test]
+    async fn test_path_version_requirement_conflict() {
+        let temp = TempDir::new().unwrap();
+        let a = temp.path().join("a");
+        let b = temp.path().join("b");
+        write_package(
+            &a,
+            "a",
+            "1.0.0",
+            "b = { path = \"../b\", version = \"^3\" }\n",
+        );
+        write_package(&b, "b", "2.1.0", "");
+
+        let config = project(&format!("a = {{ path = {:?} }}\n", a.to_string_lossy()));
+        let mut resolver = DependencyResolver::new(temp.path().join("cache"));
+        let err = resolver.resolve(&config).await.unwrap_err();
+
+        let conflict = err.downcast_ref::<VersionConflict>().unwrap();
+        assert_eq!(conflict.package, "b");
+        assert!(conflict.requirements[0].contains("app → a 1.0.0 requires b ^3"));
+    }
+
+    #[tokio::test]
+    async fn test_registry_selection() {
+        let temp = TempDir::new().unwrap();
+        let mut resolver = DependencyResolver::new(temp.path().join("cache"))
+            .with_registries(Registries::new(vec![test_registry(&temp)]));
+
+        let state = resolver
+            .solve_config(&project("b = \"^1\"\nc = \"^1.2\"\n"))
+            .await
+            .unwrap();
+
+        assert_eq!(state.selected.len(), 2);
+        assert_eq!(state.selected["c"].label, "1.5.0");
+        assert_eq!(state.requirements["c"].len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_registry_named_by_dependency() {
+        let temp = TempDir::new().unwrap();
+        let internal_path = temp.path().join("internal");
+        std::fs::create_dir_all(&internal_path).unwrap();
+        write_registry_entry(&internal_path, "c", "1.2.0", serde_json::json!({}));
+        let internal = RegistryConfig {
+            name: "internal".to_string(),
+            url: internal_path.to_string_lossy().to_string(),
+            auth_token: None,
+            enabled: true,
+        };
+        let registries = || {
+            let internal = RegistryManager::from_config(&internal, temp.path().join("cache"));
+            internal.init().unwrap();
+            Registries::new(vec![test_registry(&temp), internal])
+        };
+
+        // The first registry that has the package serves it
+        let mut resolver =
+            DependencyResolver::new(temp.path().join("cache")).with_registries(registries());
+        let state = resolver
+            .solve_config(&project("c = \"^1\"\n"))
+            .await
+            .unwrap();
+        assert_eq!(state.selected["c"].label, "1.5.0");
+
+        let mut resolver =
+            DependencyResolver::new(temp.path().join("cache")).with_registries(registries());
+        let state = resolver
+            .solve_config(&project(
+                "c = { version = \"^1\", registry = \"internal\" }\n",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(state.selected["c"].label, "1.2.0");
+        match &state.selected["c"].origin {
+            Origin::Registry(pkg) => assert_eq!(pkg.registry, "internal"),
+            Origin::Fetched { .. } => panic!("expected a registry candidate"),
+        }
+
+        let mut resolver =
+            DependencyResolver::new(temp.path().join("cache")).with_registries(registries());
+        let err = resolver
+            .solve_config(&project(
+                "c = { version = \"^1\", registry = \"missing\" }\n",
+            ))
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("Registry 'missing' is not configured"));
+    }
+
+    #[tokio::test]
+    async fn test_yanked_version_kept_only_when_locked() {
+        let temp = TempDir::new().unwrap();
+        let registry_path = temp.path().join("registry");
+        std::fs::create_dir_all(&registry_path).unwrap();
+        let pkg = serde_json::json!({
+            "name": "c",
+            "description": "A test package",
+            "repository": "https://example.com/c",
+            "version": "1.5.0",
+            "license": "MIT",
+            "build_system": "cmake",
+            "versions": [
+                { "version": "1.4.0" },
+                { "version": "1.5.0", "yanked": true }
+            ]
+        });
+        std::fs::write(registry_path.join("c.json"), pkg.to_string()).unwrap();
+        let registries = || {
+            let manager = RegistryManager::new(registry_path.clone(), temp.path().join("cache"));
+            manager.init().unwrap();
+            Registries::new(vec![manager])
+        };
+
+        let mut resolver =
+            DependencyResolver::new(temp.path().join("cache")).with_registries(registries());
+        let state = resolver
+            .solve_config(&project("c = \"^1\"\n"))
+            .await
+            .unwrap();
+        assert_eq!(state.selected["c"].label, "1.4.0");
+
+        let locked = ResolvedDependency {
+            name: "c".to_string(),
+            version: "1.5.0".to_string(),
+            source: DependencySource::Registry {
+                registry: "porters".to_string(),
+            },
+            checksum: None,
+            dependencies: vec![],
+            features: vec![],
+            platforms: None,
+            path: PathBuf::new(),
+            include_paths: vec![],
+            lib_paths: vec![],
+            build: None,
+            constraints: vec![],
+        };
+        let mut resolver = DependencyResolver::new(temp.path().join("cache"))
+            .with_registries(registries())
+            .with_lockfile(LockFile::from_resolved(&[locked]));
+        let state = resolver
+            .solve_config(&project("c = \"^1\"\n"))
+            .await
+            .unwrap();
+        assert_eq!(state.selected["c"].label, "1.5.0");
+    }
+
+    #[tokio::test]
+    async fn test_registry_conflict_reports_chain() {
+        let temp = TempDir::new().unwrap();
+        let mut resolver = DependencyResolver::new(temp.path().join("cache"))
+            .with_registries(Registries::new(vec![test_registry(&temp)]));
+
+        let err = resolver
+            .solve_config(&project("a = \"^1\"\nc = \"^1\"\n"))
+            .await
+            .unwrap_err();
+
+        let conflict = err.downcast_ref::<VersionConflict>().unwrap();
+        assert_eq!(conflict.package, "c");
+        let message = conflict.to_string();
+        assert!(message.contains("app requires c ^1"));
+        assert!(message.contains("app → a 1.0.0 requires c ^2"));
+        assert!(message.contains("available: 1.5.0"));
+    }
+
+    #[tokio::test]
+    async fn test_locked_resolution_detects_stale_lockfile() {
+        let temp = TempDir::new().unwrap();
+        let a = temp.path().join("a");
+        let b = temp.path().join("b");
+        write_package(&a, "a", "1.0.0", "");
+        write_package(&b, "b", "1.0.0", "");
+
+        let a_path = a.to_string_lossy().to_string();
+        let locked_config = project(&format!("a = {{ path = {:?} }}\n", a_path));
+        let options = ResolveOptions::from_flags(true, false);
+        let resolved = DependencyResolver::new(temp.path().join("cache"))
+            .resolve(&locked_config)
+            .await
+            .unwrap();
+        let lock = LockFile::from_resolved(&resolved);
+
+        let mut resolver = DependencyResolver::new(temp.path().join("cache"))
+            .with_options(options.clone())
+            .with_lockfile(lock.clone());
+        assert!(resolver.resolve(&locked_config).await.is_ok());
+
+        let changed = project(&format!(
+            "a = {{ path = {:?} }}\nb = {{ path = {:?} }}\n",
+            a_path,
+            b.to_string_lossy()
+        ));
+        let mut resolver = DependencyResolver::new(temp.path().join("cache"))
+            .with_options(options)
+            .with_lockfile(lock);
+        let err = resolver.resolve(&changed).await.unwrap_err().to_string();
+        assert!(err.contains("porters.lock is out of date"));
+        assert!(err.contains("b is not in porters.lock"));
+    }
+
+    #[tokio::test]
+    async fn test_features_enable_optional_dependencies() {
+        let temp = TempDir::new().unwrap();
+        let a = temp.path().join("a");
+        let b = temp.path().join("b");
+        write_package(&a, "a", "1.0.0", "\n[features]\nfast = []\n");
+        write_package(&b, "b", "1.0.0", "");
+
+        let config = project(&format!(
+            "a = {{ path = {:?} }}\nb = {{ path = {:?}, optional = true }}\n\n[features]\njson = [\"dep:b\", \"a/fast\"]\n",
+            a.to_string_lossy(),
+            b.to_string_lossy()
+        ));
+
+        let resolved = DependencyResolver::new(temp.path().join("cache"))
+            .resolve(&config)
+            .await
+            .unwrap();
+        assert_eq!(resolved.len(), 1);
+        assert!(resolved[0].features.is_empty());
+
+        let options = ResolveOptions {
+            features: features::FeatureSelection {
+                features: vec!["json".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let resolved = DependencyResolver::new(temp.path().join("cache"))
+            .with_options(options)
+            .resolve(&config)
+            .await
+            .unwrap();
+        assert_eq!(resolved.len(), 2);
+        let a = resolved.iter().find(|d| d.name == "a").unwrap();
+        assert_eq!(a.features, vec!["fast".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_platform_conditional_dependencies() {
+        let temp = TempDir::new().unwrap();
+        for name in ["b", "c", "d"] {
+            write_package(&temp.path().join(name), name, "1.0.0", "");
+        }
+        write_package(
+            &temp.path().join("a"),
+            "a",
+            "1.0.0",
+            "\n[target.linux-aarch64.dependencies]\nc = { path = \"../c\" }\n",
+        );
+
+        let path = |name: &str| temp.path().join(name).to_string_lossy().to_string();
+        let config = project(&format!(
+            "a = {{ path = {:?} }}\nb = {{ path = {:?}, platforms = [\"windows\"] }}\n\n[target.linux.dependencies]\nd = {{ path = {:?} }}\n",
+            path("a"),
+            path("b"),
+            path("d")
+        ));
+
+        let resolve_for = |target: &str| {
+            let options = ResolveOptions {
+                target: Some(target.to_string()),
+                ..Default::default()
+            };
+            let cache = temp.path().join("cache");
+            let config = config.clone();
+            async move {
+                DependencyResolver::new(cache)
+                    .with_options(options)
+                    .resolve(&config)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let resolved = resolve_for("linux-aarch64").await;
+        let mut names: Vec<&str> = resolved.iter().map(|d| d.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["a", "c", "d"]);
+        let c = resolved.iter().find(|d| d.name == "c").unwrap();
+        assert_eq!(c.platforms, Some(vec!["linux-aarch64".to_string()]));
+        let d = resolved.iter().find(|d| d.name == "d").unwrap();
+        assert_eq!(d.platforms, Some(vec!["linux".to_string()]));
+
+        let resolved = resolve_for("windows-x86_64").await;
+        let mut names: Vec<&str> = resolved.iter().map(|d| d.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_pinned_checksum_mismatch() {
+        let temp = TempDir::new().unwrap();
+        let a = temp.path().join("a");
+        write_package(&a, "a", "1.0.0", "");
+        let actual = crate::hash::calculate_directory_hash(&a).unwrap();
+
+        let config = project(&format!(
+            "a = {{ path = {:?}, checksum = \"sha256:{}\" }}\n",
+            a.to_string_lossy(),
+            actual
+        ));
+        DependencyResolver::new(temp.path().join("cache"))
+            .resolve(&config)
+            .await
+            .unwrap();
+
+        let config = project(&format!(
+            "a = {{ path = {:?}, checksum = \"deadbeef\" }}\n",
+            a.to_string_lossy()
+        ));
+        let err = DependencyResolver::new(temp.path().join("cache"))
+            .resolve(&config)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch for a"));
+    }
 }
@@ -137,6 +137,16 @@ impl BuildSystemExporter for CMakeExporter {
             cmake.push_str(")\n\n");
         }
 
+        // Dependencies Porters built from source (see `porters build`)
+        cmake.push_str(&format!(
+            "if(PORTERS_DEPENDENCY_TARGETS)\n    target_link_libraries({} {} ${{PORTERS_DEPENDENCY_TARGETS}})\nendif()\n\n",
+            executable_name,
+            match config.project.project_type {
+                ProjectType::Application => "PRIVATE",
+                ProjectType::Library => "PUBLIC",
+            }
+        ));
+
         // Link directories
         if !config.build.linking.library_paths.is_empty() {
             cmake.push_str("link_directories(\n");
@@ -103,7 +103,7 @@ impl Default for CacheConfig {
 /// Registry configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegistryConfig {
-    /// Registry URL (default: GitHub repository)
+    /// Registry URL (default: GitHub repository, `sparse+https://` for HTTP indexes)
     #[serde(default = "default_registry_url")]
     pub url: String,
 
@@ -9,6 +9,9 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
+/// File in the global directory that records installed packages
+const CONFIG_FILE: &str = "global.toml";
+
 /// Global Porters configuration and state
 ///
 /// Stores globally installed packages and system-wide settings.
@@ -16,6 +19,7 @@ use std::path::{Path, PathBuf};
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct GlobalConfig {
     /// Globally installed packages
+    #[serde(default)]
     pub packages: HashMap<String, GlobalPackage>,
 
     /// Global settings
@@ -75,18 +79,27 @@ impl GlobalConfig {
     }
 
     /// Get the global config file path
+    ///
+    /// Older versions used `config.toml`, which is now the user configuration;
+    /// its packages are carried over by [`GlobalConfig::load`].
+    #[allow(dead_code)]
     pub fn config_path() -> Result<PathBuf> {
-        Ok(Self::global_dir()?.join("config.toml"))
+        Ok(Self::global_dir()?.join(CONFIG_FILE))
     }
 
     /// Load global configuration
     pub fn load() -> Result<Self> {
-        let config_path = Self::config_path()?;
+        Self::load_from(&Self::global_dir()?)
+    }
+
+    /// Load the global configuration kept in `dir`
+    fn load_from(dir: &Path) -> Result<Self> {
+        let config_path = dir.join(CONFIG_FILE);
 
         if !config_path.exists() {
-            // Create default config
-            let config = Self::default();
-            config.save()?;
+            // Create default config, keeping packages recorded by older versions
+            let config = Self::migrate_legacy(dir)?.unwrap_or_default();
+            config.save_to(dir)?;
             return Ok(config);
         }
 
@@ -99,9 +112,43 @@ impl GlobalConfig {
         Ok(config)
     }
 
+    /// Packages and settings that older versions stored in `config.toml`
+    ///
+    /// That file now belongs to [`crate::global_config::GlobalPortersConfig`],
+    /// which drops these tables the next time it is saved, so they are read
+    /// once when `global.toml` is first created.
+    fn migrate_legacy(dir: &Path) -> Result<Option<Self>> {
+        let legacy_path = dir.join("config.toml");
+        if !legacy_path.exists() {
+            return Ok(None);
+        }
+
+        let content = std::fs::read_to_string(&legacy_path)
+            .with_context(|| format!("Failed to read {}", legacy_path.display()))?;
+        let mut legacy: toml::Table = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", legacy_path.display()))?;
+        legacy.retain(|key, _| key == "packages" || key == "settings");
+        if legacy.is_empty() {
+            return Ok(None);
+        }
+
+        let config = legacy.try_into().with_context(|| {
+            format!(
+                "Failed to read global packages from {}",
+                legacy_path.display()
+            )
+        })?;
+        Ok(Some(config))
+    }
+
     /// Save global configuration
     pub fn save(&self) -> Result<()> {
-        let config_path = Self::config_path()?;
+        self.save_to(&Self::global_dir()?)
+    }
+
+    /// Save the global configuration into `dir`
+    fn save_to(&self, dir: &Path) -> Result<()> {
+        let config_path = dir.join(CONFIG_FILE);
 
         // Ensure directory exists
         if let Some(parent) = config_path.parent() {
@@ -199,3 +246,54 @@ pub fn project_deps_dir<P: AsRef<Path>>(project_path: P) -> PathBuf {
 pub fn project_lock_file<P: AsRef<Path>>(project_path: P) -> PathBuf {
     project_path.as_ref().join("porters.lock")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_migrates_legacy_packages() {
+        let temp = TempDir::new().unwrap();
+        std::fs::write(
+            temp.path().join("config.toml"),
+            r#"
+auto_update_check = false
+
+[packages.fmt]
+name = "fmt"
+version = "10.2.1"
+source = "registry"
+install_path = "/home/user/.porters/packages/fmt"
+installed_at = "2025-01-01T00:00:00+00:00"
+
+[settings]
+parallel_jobs = 3
+"#,
+        )
+        .unwrap();
+
+        let config = GlobalConfig::load_from(temp.path()).unwrap();
+        assert_eq!(config.packages["fmt"].version, "10.2.1");
+        assert_eq!(config.settings.parallel_jobs, 3);
+        assert!(temp.path().join("global.toml").exists());
+
+        // Migration happens once; global.toml wins afterwards
+        std::fs::write(
+            temp.path().join("config.toml"),
+            "auto_update_check = false\n",
+        )
+        .unwrap();
+        let config = GlobalConfig::load_from(temp.path()).unwrap();
+        assert!(config.packages.contains_key("fmt"));
+
+        let empty = TempDir::new().unwrap();
+        std::fs::write(empty.path().join("config.toml"), "offline = true\n").unwrap();
+        assert!(
+            GlobalConfig::load_from(empty.path())
+                .unwrap()
+                .packages
+                .is_empty()
+        );
+    }
+}
@@ -560,3 +560,172 @@ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND.
         )
     }
 }
+
+/// SPDX identifiers of commonly used licenses, including deprecated forms
+/// such as `GPL-3.0` that are still widely written
+const SPDX_LICENSES: &[&str] = &[
+    "0BSD",
+    "AFL-3.0",
+    "AGPL-3.0",
+    "AGPL-3.0-only",
+    "AGPL-3.0-or-later",
+    "Apache-1.1",
+    "Apache-2.0",
+    "Artistic-2.0",
+    "BlueOak-1.0.0",
+    "BSD-1-Clause",
+    "BSD-2-Clause",
+    "BSD-2-Clause-Patent",
+    "BSD-3-Clause",
+    "BSD-3-Clause-Clear",
+    "BSD-4-Clause",
+    "BSL-1.0",
+    "CC-BY-4.0",
+    "CC-BY-SA-4.0",
+    "CC0-1.0",
+    "CDDL-1.0",
+    "CECILL-2.1",
+    "ECL-2.0",
+    "EPL-1.0",
+    "EPL-2.0",
+    "EUPL-1.2",
+    "FTL",
+    "GPL-2.0",
+    "GPL-2.0-only",
+    "GPL-2.0-or-later",
+    "GPL-3.0",
+    "GPL-3.0-only",
+    "GPL-3.0-or-later",
+    "HPND",
+    "ICU",
+    "IJG",
+    "ISC",
+    "LGPL-2.0",
+    "LGPL-2.0-only",
+    "LGPL-2.0-or-later",
+    "LGPL-2.1",
+    "LGPL-2.1-only",
+    "LGPL-2.1-or-later",
+    "LGPL-3.0",
+    "LGPL-3.0-only",
+    "LGPL-3.0-or-later",
+    "Libpng",
+    "libpng-2.0",
+    "libtiff",
+    "MIT",
+    "MIT-0",
+    "MPL-1.1",
+    "MPL-2.0",
+    "MS-PL",
+    "MS-RL",
+    "NCSA",
+    "OFL-1.1",
+    "OpenSSL",
+    "OSL-3.0",
+    "PostgreSQL",
+    "PSF-2.0",
+    "Python-2.0",
+    "Unicode-3.0",
+    "Unicode-DFS-2016",
+    "Unlicense",
+    "UPL-1.0",
+    "Vim",
+    "W3C",
+    "WTFPL",
+    "X11",
+    "Zlib",
+    "zlib-acknowledgement",
+    "ZPL-2.1",
+];
+
+fn is_spdx_license(id: &str) -> bool {
+    let id = id.strip_suffix('+').unwrap_or(id);
+    id.starts_with("LicenseRef-")
+        || id.starts_with("DocumentRef-")
+        || SPDX_LICENSES
+            .iter()
+            .any(|known| known.eq_ignore_ascii_case(id))
+}
+
+fn is_spdx_exception(id: &str) -> bool {
+    id.to_ascii_lowercase().contains("exception")
+}
+
+/// Check an SPDX license expression such as `MIT OR Apache-2.0` or
+/// `Apache-2.0 WITH LLVM-exception`
+pub fn is_spdx_expression(expression: &str) -> bool {
+    let expression = expression.replace('(', " ( ").replace(')', " ) ");
+    let mut depth = 0usize;
+    let mut expect_term = true;
+    let mut expect_exception = false;
+    let mut after_license = false;
+
+    for token in expression.split_whitespace() {
+        if expect_term {
+            match token {
+                _ if expect_exception => {
+                    if !is_spdx_exception(token) {
+                        return false;
+                    }
+                    expect_exception = false;
+                    after_license = false;
+                }
+                "(" => {
+                    depth += 1;
+                    continue;
+                }
+                _ if is_spdx_license(token) => after_license = true,
+                _ => return false,
+            }
+            expect_term = false;
+        } else {
+            match token {
+                ")" if depth > 0 => {
+                    depth -= 1;
+                    after_license = false;
+                }
+                "AND" | "OR" => expect_term = true,
+                "WITH" if after_license => {
+                    expect_term = true;
+                    expect_exception = true;
+                }
+                _ => return false,
+            }
+        }
+    }
+
+    !expect_term && depth == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spdx_expressions() {
+        for valid in [
+            "MIT",
+            "BSL-1.0",
+            "mit",
+            "GPL-2.0+",
+            "MIT OR Apache-2.0",
+            "Apache-2.0 WITH LLVM-exception",
+            "(MIT OR Apache-2.0) AND Zlib",
+            "LicenseRef-Proprietary",
+        ] {
+            assert!(is_spdx_expression(valid), "{}", valid);
+        }
+        for invalid in [
+            "",
+            "MIT License",
+            "Apache 2",
+            "MIT OR",
+            "(MIT",
+            "MIT)",
+            "MIT WITH GPL-3.0",
+            "(MIT OR Zlib) WITH LLVM-exception",
+        ] {
+            assert!(!is_spdx_expression(invalid), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! Linting of porters.toml
+//!
+//! `porters validate` reports keys Porters doesn't know (and so silently
+//! ignores), invalid version requirements and dependency specs whose fields
+//! don't fit together, such as `git` combined with `path`.
+
+use crate::config::PortersConfig;
+use crate::version::{Version, VersionReq};
+use toml::{Table, Value};
+
+/// Keys of a dependency table
+const DEPENDENCY_KEYS: &[&str] = &[
+    "version",
+    "git",
+    "branch",
+    "tag",
+    "rev",
+    "path",
+    "subdir",
+    "optional",
+    "features",
+    "platforms",
+    "constraints",
+    "checksum",
+    "url",
+    "sha256",
+    "strip-prefix",
+    "registry",
+    "options",
+];
+
+/// Problems found in porters.toml
+#[derive(Debug, Default)]
+pub struct Lint {
+    /// Problems that make the manifest invalid
+    pub errors: Vec<String>,
+    /// Settings that have no effect
+    pub warnings: Vec<String>,
+}
+
+/// Lint the contents of porters.toml
+pub fn lint(content: &str) -> Lint {
+    let mut lint = Lint::default();
+
+    let table: Table = match toml::from_str(content) {
+        Ok(table) => table,
+        Err(err) => {
+            lint.errors.push(format!("Invalid TOML: {}", err.message()));
+            return lint;
+        }
+    };
+
+    let mut unknown = Vec::new();
+    let config = toml::de::Deserializer::parse(content)
+        .map_err(|err| err.message().to_string())
+        .and_then(|deserializer| {
+            serde_ignored::deserialize::<_, _, PortersConfig>(deserializer, |path| {
+                unknown.push(path.to_string())
+            })
+            .map_err(|err| err.message().to_string())
+        });
+    match config {
+        Ok(config) => {
+            let version = config
+                .project
+                .version
+                .split(['-', '+'])
+                .next()
+                .unwrap_or("");
+            if Version::parse(version).is_err() {
+                lint.errors.push(format!(
+                    "project.version: \"{}\" is not a valid version",
+                    config.project.version
+                ));
+            }
+        }
+        Err(message) => lint.errors.push(message),
+    }
+    lint.warnings.extend(
+        unknown
+            .into_iter()
+            .map(|key| format!("Unknown key `{}`", key)),
+    );
+
+    for (label, deps) in dependency_tables(&table) {
+        for (name, spec) in deps {
+            let key = format!("{}.{}", label, name);
+            match spec {
+                Value::String(requirement) => check_requirement(&key, requirement, &mut lint),
+                Value::Table(spec) => check_dependency(&key, spec, &mut lint),
+                _ => lint.errors.push(format!(
+                    "{}: expected a version requirement or a table",
+                    key
+                )),
+            }
+        }
+    }
+
+    lint
+}
+
+/// Every dependency table with its dotted name
+fn dependency_tables(table: &Table) -> Vec<(String, &Table)> {
+    let mut tables = Vec::new();
+    for kind in ["dependencies", "dev-dependencies"] {
+        if let Some(deps) = table.get(kind).and_then(Value::as_table) {
+            tables.push((kind.to_string(), deps));
+        }
+    }
+    if let Some(targets) = table.get("target").and_then(Value::as_table) {
+        for (target, section) in targets {
+            for kind in ["dependencies", "dev-dependencies"] {
+                if let Some(deps) = section.get(kind).and_then(Value::as_table) {
+                    tables.push((format!("target.{}.{}", target, kind), deps));
+                }
+            }
+        }
+    }
+    tables
+}
+
+fn check_requirement(key: &str, requirement: &str, lint: &mut Lint) {
+    if VersionReq::parse(requirement).is_err() {
+        lint.errors.push(format!(
+            "{}: \"{}\" is not a valid version requirement",
+            key, requirement
+        ));
+    }
+}
+
+fn check_dependency(key: &str, spec: &Table, lint: &mut Lint) {
+    let has = |field: &str| spec.contains_key(field);
+
+    for field in spec.keys() {
+        if !DEPENDENCY_KEYS.contains(&field.as_str()) {
+            lint.warnings
+                .push(format!("Unknown key `{}.{}`", key, field));
+        }
+    }
+
+    if let Some(version) = spec.get("version").and_then(Value::as_str) {
+        check_requirement(key, version, lint);
+    }
+    if spec.get("constraints").is_some_and(Value::is_str) {
+        lint.warnings.push(format!(
+            "{}: `constraints` is a table such as `{{ min-cpp-standard = \"17\" }}`; the string form is ignored",
+            key
+        ));
+    }
+
+    let sources: Vec<&str> = ["git", "path", "url"]
+        .into_iter()
+        .filter(|field| has(field))
+        .collect();
+    if sources.len() > 1 {
+        lint.errors.push(format!(
+            "{}: `{}` can't be combined; use a single source",
+            key,
+            sources.join("` and `")
+        ));
+    }
+    if sources.is_empty() && !has("version") {
+        lint.errors.push(format!(
+            "{}: needs a `version`, `git`, `path` or `url`",
+            key
+        ));
+    }
+    if has("registry") && !sources.is_empty() {
+        lint.errors.push(format!(
+            "{}: `registry` only applies to registry dependencies, not `{}`",
+            key, sources[0]
+        ));
+    }
+    if has("options") && !sources.is_empty() {
+        lint.warnings.push(format!(
+            "{}: `options` only apply to registry dependencies, not `{}`",
+            key, sources[0]
+        ));
+    }
+
+    let references: Vec<&str> = ["branch", "tag", "rev"]
+        .into_iter()
+        .filter(|field| has(field))
+        .collect();
+    if references.len() > 1 {
+        lint.errors.push(format!(
+            "{}: only one of `branch`, `tag` and `rev` can be set",
+            key
+        ));
+    }
+    for field in references
+        .into_iter()
+        .chain(has("subdir").then_some("subdir"))
+    {
+        if !has("git") {
+            lint.errors
+                .push(format!("{}: `{}` requires `git`", key, field));
+        }
+    }
+    for field in ["sha256", "strip-prefix"] {
+        if has(field) && !has("url") {
+            lint.errors
+                .push(format!("{}: `{}` requires `url`", key, field));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint() {
+        let lint = lint(
+            r#"
+[project]
+name = "app"
+version = "0.1.0"
+licence = "MIT"
+
+[dependencies]
+fmt = "^10.2"
+zlib = "~>1.3"
+json = { git = "https://github.com/nlohmann/json", path = "../json" }
+spdlog = { git = "https://github.com/gabime/spdlog", branch = "v1.x", tag = "v1.14.1" }
+catch2 = { version = "^3.5", registry = "internal", feature = ["main"] }
+asio = { path = "../asio", rev = "abc123", options = { shared = true } }
+boost = { version = "^1.84", constraints = "c++17" }
+
+[target.'linux-x86_64'.dev-dependencies]
+gtest = { url = "https://example.com/gtest.tar.gz", sha256 = "00", registry = "internal" }
+"#,
+        );
+
+        assert_eq!(
+            lint.warnings,
+            vec![
+                "Unknown key `project.licence`",
+                "dependencies.asio: `options` only apply to registry dependencies, not `path`",
+                "dependencies.boost: `constraints` is a table such as `{ min-cpp-standard = \"17\" }`; the string form is ignored",
+                "Unknown key `dependencies.catch2.feature`",
+            ]
+        );
+        let mut errors = lint.errors;
+        errors.sort();
+        assert_eq!(
+            errors,
+            vec![
+                "dependencies.asio: `rev` requires `git`",
+                "dependencies.json: `git` and `path` can't be combined; use a single source",
+                "dependencies.spdlog: only one of `branch`, `tag` and `rev` can be set",
+                "dependencies.zlib: \"~>1.3\" is not a valid version requirement",
+                "target.linux-x86_64.dev-dependencies.gtest: `registry` only applies to registry dependencies, not `url`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_legacy_constraints_string() {
+        let config: PortersConfig = toml::from_str(
+            r#"
+[project]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+boost = { version = "^1.84", constraints = ">=c++17" }
+fmt = { version = "^10", constraints = { min-cpp-standard = "17" } }
+"#,
+        )
+        .unwrap();
+        let constraints = |name: &str| match &config.dependencies[name] {
+            crate::config::Dependency::Detailed { constraints, .. } => constraints.as_ref().clone(),
+            _ => panic!("{} isn't a detailed dependency", name),
+        };
+        assert!(constraints("boost").is_none());
+        assert_eq!(
+            constraints("fmt").unwrap().min_cpp_standard.as_deref(),
+            Some("17")
+        );
+    }
+}
@@ -1,68 +1,55 @@
 //! Lock file management for dependency resolution
 //!
 //! This module handles reading, writing, and managing the porters.lock file
-//! which tracks resolved dependency versions and checksums.
+//! which records the resolved dependency graph: exact versions, sources,
+//! checksums, transitive edges, enabled features and platform filters.
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::path::Path;
 
+use crate::deps::ResolvedDependency;
+use crate::util::pretty::print_info;
+
 /// Lock file to track resolved dependencies
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct LockFile {
     /// Version of the lock file format
     pub version: String,
 
-    /// Resolved dependencies
-    pub dependencies: HashMap<String, ResolvedDependency>,
-
     /// When the lock file was last updated
     pub updated_at: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ResolvedDependency {
-    pub name: String,
-    pub version: String,
-    pub source: DependencySource,
-    pub checksum: Option<String>,
-    pub dependencies: Vec<String>,
-}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "type", rename_all = "lowercase")]
-pub enum DependencySource {
-    Git {
-        url: String,
-        rev: String,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        branch: Option<String>,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        tag: Option<String>,
-    },
-    Path {
-        path: String,
-    },
-    Registry {
-        registry: String,
-        version: String,
-    },
+    /// Resolved dependency graph, keyed by package name
+    pub dependencies: BTreeMap<String, ResolvedDependency>,
 }
 
 impl LockFile {
-    pub const VERSION: &'static str = "1";
+    pub const VERSION: &'static str = "2";
 
     /// Create a new lock file
     pub fn new() -> Self {
         Self {
             version: Self::VERSION.to_string(),
-            dependencies: HashMap::new(),
+            dependencies: BTreeMap::new(),
             updated_at: chrono::Utc::now().to_rfc3339(),
         }
     }
 
+    /// Create a lock file from a resolved dependency graph
+    pub fn from_resolved(deps: &[ResolvedDependency]) -> Self {
+        let mut lock = Self::new();
+        for dep in deps {
+            lock.add_dependency(dep.name.clone(), dep.clone());
+        }
+        lock
+    }
+
     /// Load lock file from path
+    ///
+    /// Lock files written in an older format are migrated in memory; they
+    /// are rewritten in the current format the next time they are saved.
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
         if !path.as_ref().exists() {
             return Ok(Self::new());
@@ -71,10 +58,32 @@ impl LockFile {
         let content = std::fs::read_to_string(path.as_ref())
             .with_context(|| format!("Failed to read {}", path.as_ref().display()))?;
 
-        let lock: LockFile =
-            toml::from_str(&content).with_context(|| "Failed to parse lock file")?;
+        Self::parse(&content)
+    }
+
+    /// Parse lock file contents, migrating older formats
+    pub fn parse(content: &str) -> Result<Self> {
+        let raw: toml::Table = toml::from_str(content).context("Failed to parse lock file")?;
+        let version = raw
+            .get("version")
+            .and_then(|v| v.as_str())
+            .unwrap_or("1");
 
-        Ok(lock)
+        match version {
+            Self::VERSION => toml::from_str(content).context("Failed to parse lock file"),
+            "1" => {
+                let old: v1::LockFile =
+                    toml::from_str(content).context("Failed to parse version 1 lock file")?;
+                print_info("Migrating porters.lock from format version 1");
+                Ok(old.migrate())
+            }
+            other => anyhow::bail!(
+                "Unsupported lock file version '{}' (this porters supports up to {}). \
+                 Upgrade porters or regenerate porters.lock with 'porters lock'",
+                other,
+                Self::VERSION
+            ),
+        }
     }
 
     /// Save lock file to path
@@ -112,3 +121,197 @@ impl LockFile {
         self.updated_at = chrono::Utc::now().to_rfc3339();
     }
 }
+
+/// Version 1 lock file format
+///
+/// Version 1 kept its own copy of the dependency model and stored registry
+/// versions inside the source table.
+mod v1 {
+    use crate::deps;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Deserialize)]
+    pub struct LockFile {
+        pub dependencies: HashMap<String, ResolvedDependency>,
+        pub updated_at: String,
+    }
+
+    #[derive(Deserialize)]
+    pub struct ResolvedDependency {
+        pub name: String,
+        pub version: String,
+        pub source: DependencySource,
+        pub checksum: Option<String>,
+        #[serde(default)]
+        pub dependencies: Vec<String>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(tag = "type", rename_all = "lowercase")]
+    pub enum DependencySource {
+        Git {
+            url: String,
+            rev: String,
+            branch: Option<String>,
+            tag: Option<String>,
+        },
+        Path {
+            path: String,
+        },
+        Registry {
+            registry: String,
+            version: String,
+        },
+    }
+
+    impl LockFile {
+        pub fn migrate(self) -> super::LockFile {
+            let dependencies = self
+                .dependencies
+                .into_iter()
+                .map(|(key, dep)| {
+                    let mut version = dep.version;
+                    let source = match dep.source {
+                        DependencySource::Git {
+                            url,
+                            rev,
+                            branch,
+                            tag,
+                        } => deps::DependencySource::Git {
+                            url,
+                            rev,
+                            branch,
+                            tag,
+                            subdir: None,
+                        },
+                        DependencySource::Path { path } => deps::DependencySource::Path { path },
+                        DependencySource::Registry {
+                            registry,
+                            version: registry_version,
+                        } => {
+                            version = registry_version;
+                            deps::DependencySource::Registry { registry }
+                        }
+                    };
+
+                    let migrated = deps::ResolvedDependency {
+                        name: dep.name,
+                        version,
+                        source,
+                        checksum: dep.checksum,
+                        dependencies: dep.dependencies,
+                        features: vec![],
+                        platforms: None,
+                        path: Default::default(),
+                        include_paths: vec![],
+                        lib_paths: vec![],
+                        build: None,
+                        constraints: vec![],
+                    };
+                    (key, migrated)
+                })
+                .collect();
+
+            super::LockFile {
+                version: super::LockFile::VERSION.to_string(),
+                updated_at: self.updated_at,
+                dependencies,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deps::DependencySource;
+
+    #[test]
+    fn test_migrate_v1_lock_file() {
+        let content = r#"
+version = "1"
+updated_at = "2024-01-15T10:30:00Z"
+
+[dependencies.fmt]
+name = "fmt"
+version = "10.1.1"
+checksum = "abc123"
+dependencies = []
+
+[dependencies.fmt.source]
+type = "registry"
+registry = "porters"
+version = "10.1.1"
+
+[dependencies.spdlog]
+name = "spdlog"
+version = "a1b2c3d4"
+dependencies = ["fmt"]
+
+[dependencies.spdlog.source]
+type = "git"
+url = "https://github.com/gabime/spdlog"
+rev = "a1b2c3d4e5f6"
+branch = "v1.x"
+"#;
+
+        let lock = LockFile::parse(content).unwrap();
+        assert_eq!(lock.version, LockFile::VERSION);
+        assert_eq!(lock.updated_at, "2024-01-15T10:30:00Z");
+
+        let fmt = &lock.dependencies["fmt"];
+        assert_eq!(fmt.version, "10.1.1");
+        assert_eq!(fmt.checksum.as_deref(), Some("abc123"));
+        assert_eq!(
+            fmt.source,
+            DependencySource::Registry {
+                registry: "porters".to_string()
+            }
+        );
+
+        let spdlog = &lock.dependencies["spdlog"];
+        assert_eq!(spdlog.dependencies, vec!["fmt".to_string()]);
+        assert!(matches!(
+            &spdlog.source,
+            DependencySource::Git { rev, branch: Some(branch), .. }
+                if rev == "a1b2c3d4e5f6" && branch == "v1.x"
+        ));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dep = ResolvedDependency {
+            name: "zlib".to_string(),
+            version: "1.3.0".to_string(),
+            source: DependencySource::Registry {
+                registry: "porters".to_string(),
+            },
+            checksum: Some("deadbeef".to_string()),
+            dependencies: vec![],
+            features: vec!["shared".to_string()],
+            platforms: Some(vec!["linux".to_string()]),
+            path: "/tmp/zlib".into(),
+            include_paths: vec![],
+            lib_paths: vec![],
+            build: None,
+            constraints: vec![],
+        };
+
+        let lock = LockFile::from_resolved(&[dep]);
+        let content = toml::to_string_pretty(&lock).unwrap();
+        let parsed = LockFile::parse(&content).unwrap();
+
+        let zlib = &parsed.dependencies["zlib"];
+        assert_eq!(parsed.version, "2");
+        assert_eq!(zlib.features, vec!["shared".to_string()]);
+        assert_eq!(zlib.platforms, Some(vec!["linux".to_string()]));
+        assert!(zlib.path.as_os_str().is_empty());
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let err = LockFile::parse("version = \"99\"\nupdated_at = \"\"\n").unwrap_err();
+        assert!(err.to_string().contains("Unsupported lock file version"));
+    }
+}
@@ -25,6 +25,7 @@ mod global_config;
 mod global_packages;
 mod hash;
 mod license;
+mod lint;
 mod lockfile;
 mod pkg_managers;
 mod publish;
@@ -156,6 +157,38 @@ enum Commands {
         #[arg(long)]
         macos: bool,
 
+        /// Comma-separated list of features to activate
+        #[arg(long, value_delimiter = ',')]
+        features: Vec<String>,
+
+        /// Activate all available features
+        #[arg(long)]
+        all_features: bool,
+
+        /// Do not activate the `default` feature
+        #[arg(long)]
+        no_default_features: bool,
+
+        /// Require porters.lock to be up to date with porters.toml
+        #[arg(long)]
+        locked: bool,
+
+        /// Like --locked, and additionally forbid any network access
+        #[arg(long)]
+        frozen: bool,
+
+        /// Build with the release profile
+        #[arg(short, long, conflicts_with = "profile")]
+        release: bool,
+
+        /// Build with the named profile from porters.toml
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+
+        /// How to report build output and compiler diagnostics
+        #[arg(long, value_enum, default_value = "human")]
+        message_format: build::diagnostics::MessageFormat,
+
         /// Additional build arguments
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
@@ -164,6 +197,14 @@ enum Commands {
     /// ▶️ Run the project
     #[command(visible_alias = "r")]
     Run {
+        /// Build with the release profile
+        #[arg(long, conflicts_with = "profile")]
+        release: bool,
+
+        /// Build with the named profile from porters.toml
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+
         /// Additional run arguments
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
@@ -194,7 +235,39 @@ enum Commands {
 
     /// 🧪 Run tests
     #[command(visible_alias = "t")]
-    Test,
+    Test {
+        /// Comma-separated list of features to activate
+        #[arg(long, value_delimiter = ',')]
+        features: Vec<String>,
+
+        /// Activate all available features
+        #[arg(long)]
+        all_features: bool,
+
+        /// Do not activate the `default` feature
+        #[arg(long)]
+        no_default_features: bool,
+
+        /// Require porters.lock to be up to date with porters.toml
+        #[arg(long)]
+        locked: bool,
+
+        /// Like --locked, and additionally forbid any network access
+        #[arg(long)]
+        frozen: bool,
+
+        /// Build with the release profile
+        #[arg(long, conflicts_with = "profile")]
+        release: bool,
+
+        /// Build with the named profile from porters.toml
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+
+        /// How to report build output and compiler diagnostics
+        #[arg(long, value_enum, default_value = "human")]
+        message_format: build::diagnostics::MessageFormat,
+    },
 
     /// ✅ Check compilation without creating executables (syntax check)
     #[command(visible_alias = "ch")]
@@ -207,6 +280,13 @@ enum Commands {
         verbose: bool,
     },
 
+    /// 🩺 Lint porters.toml for unknown keys and invalid dependency specs
+    Validate {
+        /// Manifest to check
+        #[arg(default_value = "porters.toml")]
+        manifest: String,
+    },
+
     /// 🔄 Update dependencies
     #[command(visible_alias = "u")]
     Update,
@@ -273,6 +353,14 @@ enum Commands {
         /// Disable cache (force re-download)
         #[arg(long)]
         no_cache: bool,
+
+        /// Require porters.lock to be up to date with porters.toml
+        #[arg(long)]
+        locked: bool,
+
+        /// Like --locked, and additionally forbid any network access
+        #[arg(long)]
+        frozen: bool,
     },
 
     /// 🔌 Manage extensions
@@ -319,8 +407,15 @@ enum Commands {
         /// Update to absolute latest (ignore constraints)
         #[arg(long)]
         latest: bool,
+
+        /// Show the updates without changing porters.toml or porters.lock
+        #[arg(long)]
+        dry_run: bool,
     },
 
+    /// 📋 Show dependencies that have newer versions
+    Outdated,
+
     /// 🎯 Cross-compile for specific platform(s)
     Compile {
         /// Compile for all supported platforms
@@ -343,9 +438,21 @@ enum Commands {
         #[arg(long)]
         baremetal: bool,
 
-        /// Specific target triple
+        /// Specific target (e.g. linux-aarch64 or aarch64-unknown-linux-gnu)
         #[arg(long)]
         target: Option<String>,
+
+        /// Build with the release profile
+        #[arg(short, long, conflicts_with = "profile")]
+        release: bool,
+
+        /// Build with the named profile from porters.toml
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+
+        /// How to report build output and compiler diagnostics
+        #[arg(long, value_enum, default_value = "human")]
+        message_format: build::diagnostics::MessageFormat,
     },
 
     /// 🔧 Execute a custom command (dynamically matched from config)
@@ -470,8 +577,32 @@ enum ExtensionAction {
 enum RegistryAction {
     /// 🔍 Search for packages in the registry
     Search {
-        /// Search query (name, description, or tag)
-        query: String,
+        /// Search query (name, description, or tag); every word must match
+        query: Option<String>,
+
+        /// Only packages with this tag (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+
+        /// Only packages supporting this platform (e.g. linux, windows-x86_64)
+        #[arg(long)]
+        platform: Option<String>,
+
+        /// Only packages under this license (SPDX identifier)
+        #[arg(long)]
+        license: Option<String>,
+
+        /// Only header-only packages
+        #[arg(long)]
+        header_only: bool,
+
+        /// Only packages usable with this C++ standard (e.g. 17)
+        #[arg(long, value_name = "STANDARD")]
+        min_cpp: Option<String>,
+
+        /// Print the results as JSON
+        #[arg(long)]
+        json: bool,
     },
 
     /// ➕ Add a package from the registry
@@ -482,6 +613,18 @@ enum RegistryAction {
         /// Install globally (default: local to project)
         #[arg(long, short = 'g')]
         global: bool,
+
+        /// Registry to take the package from (default: first registry that has it)
+        #[arg(long)]
+        registry: Option<String>,
+
+        /// Version requirement (default: latest published version)
+        #[arg(long)]
+        version: Option<String>,
+
+        /// Add as a dev dependency
+        #[arg(long)]
+        dev: bool,
     },
 
     /// 📋 List all packages in the registry
@@ -490,10 +633,60 @@ enum RegistryAction {
     /// 🔄 Update local registry index
     Update,
 
+    /// 📤 Publish this package to a registry
+    Publish {
+        /// Registry to publish to
+        #[arg(long, default_value = "porters")]
+        registry: String,
+
+        /// Registry directory for new packages (default: first project category or keyword)
+        #[arg(long)]
+        category: Option<String>,
+
+        /// Show the package definition without publishing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// ✔️ Check package definitions against the registry schema
+    Validate {
+        /// Package definition file or registry directory
+        #[arg(default_value = "registry")]
+        path: String,
+    },
+
+    /// 🗂️ Write index.json so a registry directory can be served over HTTP
+    Index {
+        /// Registry directory
+        #[arg(default_value = "registry")]
+        dir: String,
+    },
+
+    /// 💾 Copy packages and their sources into a directory for offline use
+    Mirror {
+        /// Mirror directory
+        dir: String,
+
+        /// Packages as `name` or `name@requirement` (default: the registry packages in porters.lock)
+        packages: Vec<String>,
+
+        /// Registry to take the packages from (default: first registry that has them)
+        #[arg(long)]
+        registry: Option<String>,
+
+        /// Mirror every matching version instead of only the latest
+        #[arg(long)]
+        all_versions: bool,
+    },
+
     /// ℹ️ Show detailed information about a package
     Info {
         /// Package name
         package: String,
+
+        /// Registry to look the package up in (default: first registry that has it)
+        #[arg(long)]
+        registry: Option<String>,
     },
 }
 
@@ -574,9 +767,46 @@ async fn main() -> Result<()> {
             linux,
             windows,
             macos,
+            features,
+            all_features,
+            no_default_features,
+            locked,
+            frozen,
+            release,
+            profile,
+            message_format,
             args,
-        } => build_project(all_platforms, linux, windows, macos, args).await,
-        Commands::Run { args } => run_project(args).await,
+        } => {
+            use_stderr(message_format == build::diagnostics::MessageFormat::Json);
+            let options = deps::ResolveOptions {
+                features: deps::features::FeatureSelection {
+                    features,
+                    all_features,
+                    no_default_features,
+                },
+                ..deps::ResolveOptions::from_flags(locked, frozen)
+            };
+            let profile = build::profile::Profile::selected(release, profile.as_deref());
+            build_project(
+                all_platforms,
+                linux,
+                windows,
+                macos,
+                args,
+                &options,
+                profile,
+                message_format,
+            )
+            .await
+        }
+        Commands::Run {
+            release,
+            profile,
+            args,
+        } => {
+            let profile = build::profile::Profile::selected(release, profile.as_deref());
+            run_project(args, profile).await
+        }
         Commands::Execute {
             file,
             args,
@@ -584,11 +814,33 @@ async fn main() -> Result<()> {
             no_console,
             output,
         } => execute_single_file(&file, args, external, no_console, output.as_deref()).await,
-        Commands::Test => test_project().await,
+        Commands::Test {
+            features,
+            all_features,
+            no_default_features,
+            locked,
+            frozen,
+            release,
+            profile,
+            message_format,
+        } => {
+            use_stderr(message_format == build::diagnostics::MessageFormat::Json);
+            let options = deps::ResolveOptions {
+                features: deps::features::FeatureSelection {
+                    features,
+                    all_features,
+                    no_default_features,
+                },
+                ..deps::ResolveOptions::from_flags(locked, frozen)
+            };
+            let profile = build::profile::Profile::selected(release, profile.as_deref());
+            test_project(&options, profile, message_format).await
+        }
         Commands::Check { file, verbose } => check_compilation(file.as_deref(), verbose).await,
+        Commands::Validate { manifest } => validate_manifest(&manifest),
         Commands::Update => update_dependencies().await,
         Commands::Clean => clean_project().await,
-        Commands::Lock => generate_lockfile().await,
+        Commands::Lock => generate_lockfile(&deps::ResolveOptions::default()).await,
         Commands::Vendor => vendor_dependencies().await,
         Commands::Graph => show_dependency_graph().await,
         Commands::Publish { token, dry_run } => publish_package(token, dry_run).await,
@@ -603,7 +855,12 @@ async fn main() -> Result<()> {
             dev,
             optional,
             no_cache,
-        } => sync_dependencies(dev, optional, !no_cache).await,
+            locked,
+            frozen,
+        } => {
+            let options = deps::ResolveOptions::from_flags(locked, frozen);
+            sync_dependencies(dev, optional, !no_cache, &options).await
+        }
         Commands::Extension { action } => handle_extension(action).await,
         Commands::RunScript { name } => run_script(&name).await,
         Commands::List { tree } => list_dependencies(tree).await,
@@ -615,7 +872,8 @@ async fn main() -> Result<()> {
         Commands::GlobalList => global_list_packages().await,
         Commands::CleanCache { force } => clean_cache(force).await,
         Commands::SelfUpdate => self_update().await,
-        Commands::UpdateDeps { latest } => update_deps(latest).await,
+        Commands::UpdateDeps { latest, dry_run } => update_deps(latest, dry_run).await,
+        Commands::Outdated => show_outdated().await,
         Commands::Compile {
             all_platforms,
             linux,
@@ -623,7 +881,25 @@ async fn main() -> Result<()> {
             macos,
             baremetal,
             target,
-        } => compile_cross(all_platforms, linux, windows, macos, baremetal, target).await,
+            release,
+            profile,
+            message_format,
+        } => {
+            use_stderr(message_format == build::diagnostics::MessageFormat::Json);
+            let profile = build::profile::Profile::selected(release, profile.as_deref());
+            compile_cross(
+                all_platforms,
+                linux,
+                windows,
+                macos,
+                baremetal,
+                target,
+                &deps::ResolveOptions::default(),
+                profile,
+                message_format,
+            )
+            .await
+        }
         Commands::Custom(args) => execute_custom_command(args).await,
         Commands::AddToPath { overwrite } => add_to_path(overwrite),
         Commands::RemoveFromPath => remove_from_path(),
@@ -2057,8 +2333,13 @@ install(TARGETS {}
     RUNTIME DESTINATION bin
 )
 install(DIRECTORY include/ DESTINATION include)
+
+# Link the dependencies Porters builds from source
+if(PORTERS_DEPENDENCY_TARGETS)
+    target_link_libraries({} PUBLIC ${{PORTERS_DEPENDENCY_TARGETS}})
+endif()
 "#,
-            project_name, project_name, project_name, project_name
+            project_name, project_name, project_name, project_name, project_name
         )
     } else {
         format!(
@@ -2076,8 +2357,13 @@ add_executable({} ${{SOURCES}})
 
 # Include directories
 target_include_directories({} PRIVATE include)
+
+# Link the dependencies Porters builds from source
+if(PORTERS_DEPENDENCY_TARGETS)
+    target_link_libraries({} PRIVATE ${{PORTERS_DEPENDENCY_TARGETS}})
+endif()
 "#,
-            project_name, project_name, project_name
+            project_name, project_name, project_name, project_name
         )
     };
 
@@ -2402,7 +2688,7 @@ fn check_tool_requirements(config: &PortersConfig) -> Result<()> {
 }
 
 /// Execute a build script (pre-build, post-build, etc.)
-fn execute_build_script(script: &str, script_type: &str) -> Result<()> {
+fn execute_build_script(script: &str, script_type: &str, config: &PortersConfig) -> Result<()> {
     let mut cmd = if cfg!(target_os = "windows") {
         let mut c = std::process::Command::new("cmd");
         c.args(["/C", script]);
@@ -2415,7 +2701,7 @@ fn execute_build_script(script: &str, script_type: &str) -> Result<()> {
 
     // Set environment variables for the script
     let project_dir = std::env::current_dir()?;
-    let build_dir = project_dir.join("build");
+    let build_dir = project_dir.join(config.get_build_dir());
 
     cmd.env("PROJECT_DIR", &project_dir);
     cmd.env("BUILD_DIR", &build_dir);
@@ -2438,6 +2724,28 @@ fn execute_build_script(script: &str, script_type: &str) -> Result<()> {
     Ok(())
 }
 
+/// Build options for the project's activated features and the `profile`
+fn project_build_options(
+    config: &PortersConfig,
+    options: &deps::ResolveOptions,
+    resolved_deps: &[deps::ResolvedDependency],
+    profile: &str,
+) -> Result<build::BuildOptions> {
+    let profile = build::profile::Profile::resolve(profile, &config.profile)?;
+    let activation = deps::features::activate_project(config, &options.features)?;
+    let features: Vec<String> = activation.features.into_iter().collect();
+
+    if !features.is_empty() {
+        print_info(&format!("✨ Features: {}", features.join(", ")));
+    }
+
+    if profile.name != build::profile::DEFAULT_PROFILE {
+        print_info(&format!("🎛️  Profile: {}", profile.name));
+    }
+
+    Ok(build::BuildOptions::with_features(features, resolved_deps).with_profile(profile))
+}
+
 /// Verify checksums of resolved dependencies against lockfile
 fn verify_dependency_checksums(resolved_deps: &[deps::ResolvedDependency]) -> Result<()> {
     let lock_file_path = global_packages::project_lock_file(".");
@@ -2469,8 +2777,8 @@ fn verify_dependency_checksums(resolved_deps: &[deps::ResolvedDependency]) -> Re
             continue;
         };
 
-        // Get dependency directory
-        let dep_path = global_packages::project_deps_dir(".").join(&dep.name);
+        // Get dependency directory (transitive dependencies live outside ports/)
+        let dep_path = &dep.path;
         if !dep_path.exists() {
             anyhow::bail!(
                 "Dependency {} not found at {}",
@@ -2480,8 +2788,8 @@ fn verify_dependency_checksums(resolved_deps: &[deps::ResolvedDependency]) -> Re
         }
 
         // Verify checksum using hash module
-        if !hash::verify_directory_hash(&dep_path, expected_checksum)? {
-            let actual_checksum = hash::calculate_directory_hash(&dep_path)?;
+        if !hash::verify_directory_hash(dep_path, expected_checksum)? {
+            let actual_checksum = hash::calculate_directory_hash(dep_path)?;
             anyhow::bail!(
                 "❌ Checksum mismatch for dependency '{}'!\n\
                  Expected: {}\n\
@@ -2514,21 +2822,39 @@ fn verify_dependency_checksums(resolved_deps: &[deps::ResolvedDependency]) -> Re
 /// * `windows` - Build for Windows  
 /// * `macos` - Build for macOS
 /// * `args` - Additional arguments to pass to the build system
+/// * `options` - How porters.lock and the network are used during resolution
+/// * `profile` - Name of the build profile (`debug`, `release`, ...)
+/// * `message_format` - How build output and diagnostics are reported
 ///
 /// # Returns
 /// * `Result<()>` - Success or error
+#[allow(clippy::too_many_arguments)]
 async fn build_project(
     all_platforms: bool,
     linux: bool,
     windows: bool,
     macos: bool,
     args: Vec<String>,
+    options: &deps::ResolveOptions,
+    profile: &str,
+    message_format: build::diagnostics::MessageFormat,
 ) -> Result<()> {
     print_step("🔨 Building project");
 
     // If any platform flags are set, delegate to cross-compile
     if all_platforms || linux || windows || macos {
-        return compile_cross(all_platforms, linux, windows, macos, false, None).await;
+        return compile_cross(
+            all_platforms,
+            linux,
+            windows,
+            macos,
+            false,
+            None,
+            options,
+            profile,
+            message_format,
+        )
+        .await;
     }
 
     // Default: build for current platform
@@ -2558,7 +2884,7 @@ async fn build_project(
     // Execute pre-build script if defined
     if let Some(pre_build_script) = &config.build.scripts.pre_build {
         print_info("🔧 Executing pre-build script...");
-        execute_build_script(pre_build_script, "pre-build")?;
+        execute_build_script(pre_build_script, "pre-build", &config)?;
         print_success("✅ Pre-build script completed");
     }
 
@@ -2583,7 +2909,7 @@ async fn build_project(
 
     // Resolve dependencies
     print_info("📦 Resolving dependencies...");
-    let resolved_deps = deps::resolve_dependencies(&config).await?;
+    let mut resolved_deps = deps::resolve_dependencies_with(&config, options).await?;
     print_success(&format!("✅ Resolved {} dependencies", resolved_deps.len()));
 
     // Check binary cache for dependencies
@@ -2613,24 +2939,43 @@ async fn build_project(
     verify_dependency_checksums(&resolved_deps)?;
     print_success("✅ All checksums verified");
 
+    deps::constraints::check_dependency_constraints(&config, &resolved_deps)?;
+    let diagnostics = build::diagnostics::Diagnostics::new(message_format);
+    deps::build::build_dependencies(
+        &mut resolved_deps,
+        &deps::get_cache_dir()?,
+        &build::profile::Profile::resolve(profile, &config.profile)?,
+        &diagnostics,
+    )?;
+
     // Detect and run build system
+    let build_options = project_build_options(&config, options, &resolved_deps, profile)?
+        .with_diagnostics(diagnostics.clone());
+    let profile_dir = std::env::current_dir()?
+        .join(config.get_build_dir())
+        .join(&build_options.profile.name);
+
     print_info("🔍 Detecting build system...");
-    let build_system = build::detect_build_system(".", &config)?;
+    let build_system = build::detect_build_system_with(".", &config, build_options)?;
     print_success(&format!("🔨 Using build system: {}", build_system.name()));
 
     print_info("⚙️  Building...");
-    build_system.build(&sources, &resolved_deps, &args)?;
+    let result = build_system.build(&sources, &resolved_deps, &args);
+    diagnostics.finish(result.is_ok());
+    result?;
 
     // Store build in binary cache
     if bin_cache.is_enabled() {
         print_info("💾 Storing build artifacts in binary cache...");
-        let build_dir = std::env::current_dir()?.join("build");
-        if build_dir.exists() {
-            // For the main project, use project name as cache key
+        if profile_dir.exists() {
+            // For the main project, use project name as cache key, one entry per profile
             let project_name = &config.project.name;
             let project_version = &config.project.version;
-            let build_hash =
-                hash::calculate_file_hash(&std::env::current_dir()?.join("porters.toml"))?;
+            let build_hash = format!(
+                "{}-{}",
+                hash::calculate_file_hash(&std::env::current_dir()?.join("porters.toml"))?,
+                profile
+            );
             let source_hash = hash::calculate_directory_hash(&std::env::current_dir()?)?;
 
             bin_cache.store(
@@ -2638,7 +2983,7 @@ async fn build_project(
                 project_version,
                 &source_hash,
                 &build_hash,
-                &build_dir,
+                &profile_dir,
             )?;
         }
     }
@@ -2649,7 +2994,7 @@ async fn build_project(
     // Execute post-build script if defined
     if let Some(post_build_script) = &config.build.scripts.post_build {
         print_info("🔧 Executing post-build script...");
-        execute_build_script(post_build_script, "post-build")?;
+        execute_build_script(post_build_script, "post-build", &config)?;
         print_success("✅ Post-build script completed");
     }
 
@@ -2662,17 +3007,33 @@ async fn build_project(
 ///
 /// # Arguments
 /// * `args` - Arguments to pass to the executable
+/// * `profile` - Name of the build profile to build and run
 ///
 /// # Returns
 /// * `Result<()>` - Success or error
-async fn run_project(args: Vec<String>) -> Result<()> {
+async fn run_project(args: Vec<String>, profile: &str) -> Result<()> {
     print_step("▶️  Running project");
 
     // Build first (current platform only)
-    build_project(false, false, false, false, vec![]).await?;
+    build_project(
+        false,
+        false,
+        false,
+        false,
+        vec![],
+        &deps::ResolveOptions::default(),
+        profile,
+        build::diagnostics::MessageFormat::Human,
+    )
+    .await?;
 
     let config = PortersConfig::load("porters.toml")?;
-    let build_system = build::detect_build_system(".", &config)?;
+    let profile = build::profile::Profile::resolve(profile, &config.profile)?;
+    let build_system = build::detect_build_system_with(
+        ".",
+        &config,
+        build::BuildOptions::default().with_profile(profile),
+    )?;
 
     print_info("🚀 Running executable...");
     build_system.run(&args)?;
@@ -2680,15 +3041,31 @@ async fn run_project(args: Vec<String>) -> Result<()> {
     Ok(())
 }
 
-async fn test_project() -> Result<()> {
+async fn test_project(
+    options: &deps::ResolveOptions,
+    profile: &str,
+    message_format: build::diagnostics::MessageFormat,
+) -> Result<()> {
     print_step("🧪 Running tests");
 
     let config = PortersConfig::load("porters.toml")?;
     let sources = scan::scan_project(".")?;
-    let resolved_deps = deps::resolve_dependencies(&config).await?;
-    let build_system = build::detect_build_system(".", &config)?;
+    let mut resolved_deps = deps::resolve_dependencies_with(&config, options).await?;
+    deps::constraints::check_dependency_constraints(&config, &resolved_deps)?;
+    let diagnostics = build::diagnostics::Diagnostics::new(message_format);
+    deps::build::build_dependencies(
+        &mut resolved_deps,
+        &deps::get_cache_dir()?,
+        &build::profile::Profile::resolve(profile, &config.profile)?,
+        &diagnostics,
+    )?;
+    let build_options = project_build_options(&config, options, &resolved_deps, profile)?
+        .with_diagnostics(diagnostics.clone());
+    let build_system = build::detect_build_system_with(".", &config, build_options)?;
 
-    build_system.test(&sources, &resolved_deps)?;
+    let result = build_system.test(&sources, &resolved_deps);
+    diagnostics.finish(result.is_ok());
+    result?;
 
     print_success("✅ Tests complete!");
 
@@ -3035,7 +3412,9 @@ async fn update_dependencies() -> Result<()> {
     print_step("🔄 Updating dependencies");
 
     let config = PortersConfig::load("porters.toml")?;
-    deps::update_dependencies(&config).await?;
+    let options = deps::ResolveOptions::default();
+    let resolved_deps = deps::update_dependencies(&config, &options).await?;
+    write_lockfile(&resolved_deps, &options)?;
 
     print_success("✅ Dependencies updated!");
 
@@ -3055,52 +3434,70 @@ async fn clean_project() -> Result<()> {
     Ok(())
 }
 
-async fn generate_lockfile() -> Result<()> {
-    print_step("🔒 Generating lockfile");
+fn validate_manifest(manifest: &str) -> Result<()> {
+    print_step(&format!("🩺 Validating {}", manifest));
 
-    let config = PortersConfig::load("porters.toml")?;
-    let resolved_deps = deps::resolve_dependencies(&config).await?;
+    let content = std::fs::read_to_string(manifest)
+        .with_context(|| format!("Failed to read {}", manifest))?;
+    let lint = lint::lint(&content);
 
-    // Use the lockfile module to create and save lockfile
-    let mut lockfile = lockfile::LockFile::new();
+    for warning in &lint.warnings {
+        print_warning(warning);
+    }
+    for error in &lint.errors {
+        print_error(error);
+    }
 
-    for dep in &resolved_deps {
-        // Create lockfile::DependencySource from deps::DependencySource
-        let source = match &dep.source {
-            deps::DependencySource::Git { url, rev } => lockfile::DependencySource::Git {
-                url: url.clone(),
-                rev: rev.clone(),
-                branch: None,
-                tag: None,
-            },
-            deps::DependencySource::Path { path } => {
-                lockfile::DependencySource::Path { path: path.clone() }
-            }
-            deps::DependencySource::Registry { registry } => lockfile::DependencySource::Registry {
-                registry: registry.clone(),
-                version: dep.version.clone(),
-            },
-        };
+    if !lint.errors.is_empty() {
+        anyhow::bail!("{} has {} error(s)", manifest, lint.errors.len());
+    }
+    print_success(&format!("{} is valid", manifest));
+    Ok(())
+}
 
-        let resolved_dep = lockfile::ResolvedDependency {
-            name: dep.name.clone(),
-            version: dep.version.clone(),
-            source,
-            checksum: dep.checksum.clone(),
-            dependencies: dep.dependencies.clone(),
-        };
+async fn generate_lockfile(options: &deps::ResolveOptions) -> Result<()> {
+    print_step("🔒 Generating lockfile");
+
+    let config = PortersConfig::load("porters.toml")?;
+    let resolved_deps = deps::resolve_dependencies_with(&config, options).await?;
 
-        lockfile.add_dependency(dep.name.clone(), resolved_dep);
+    // The resolution was just verified against porters.lock, so leave it untouched
+    if options.locked {
+        print_success("porters.lock is up to date 🔒");
+        return Ok(());
     }
 
-    // Save lockfile
-    lockfile.save("porters.lock")?;
+    write_lockfile(&resolved_deps, options)?;
 
     print_success("Generated porters.lock 🔒");
 
     Ok(())
 }
 
+/// Write porters.lock for freshly resolved dependencies
+fn write_lockfile(
+    resolved_deps: &[deps::ResolvedDependency],
+    options: &deps::ResolveOptions,
+) -> Result<()> {
+    let mut lockfile = lockfile::LockFile::from_resolved(resolved_deps);
+
+    // Keep entries resolved for other platforms so one lock file serves every target
+    let target = options.target();
+    let existing = lockfile::LockFile::load("porters.lock")?;
+    for (name, dep) in existing.dependencies {
+        let other_platform = dep
+            .platforms
+            .as_ref()
+            .is_some_and(|p| !deps::platform::check_platform_compatibility(p, &target));
+        if other_platform && !lockfile.dependencies.contains_key(&name) {
+            lockfile.add_dependency(name, dep);
+        }
+    }
+
+    // Save lockfile
+    lockfile.save("porters.lock")
+}
+
 async fn vendor_dependencies() -> Result<()> {
     print_step("📦 Vendoring dependencies");
 
@@ -3167,7 +3564,7 @@ async fn install_package(
         && let Some(pre_install_script) = &cfg.build.scripts.pre_install
     {
         print_info("🔧 Executing pre-install script...");
-        execute_build_script(pre_install_script, "pre-install")?;
+        execute_build_script(pre_install_script, "pre-install", cfg)?;
     }
 
     // Load extensions and execute pre-install hooks
@@ -3241,7 +3638,7 @@ async fn install_package(
         && let Some(post_install_script) = &cfg.build.scripts.post_install
     {
         print_info("Executing post-install script...");
-        execute_build_script(post_install_script, "post-install")?;
+        execute_build_script(post_install_script, "post-install", cfg)?;
     }
 
     Ok(())
@@ -3451,6 +3848,7 @@ async fn sync_dependencies(
     include_dev: bool,
     include_optional: bool,
     use_cache: bool,
+    options: &deps::ResolveOptions,
 ) -> Result<()> {
     print_step("🔄 Syncing dependencies from porters.toml");
 
@@ -3476,7 +3874,7 @@ async fn sync_dependencies(
     // Execute pre-install script if configured
     if let Some(pre_install_script) = &config.build.scripts.pre_install {
         print_info("🔧 Executing pre-install script...");
-        execute_build_script(pre_install_script, "pre-install")?;
+        execute_build_script(pre_install_script, "pre-install", &config)?;
     }
 
     // Auto-install extensions listed in config
@@ -3577,8 +3975,17 @@ async fn sync_dependencies(
                     dep_cache.retrieve(name, version, &dep_path)?;
                 } else if dep_path.exists() {
                     print_warning(&format!("⚠️  {} already exists, skipping", name));
+                } else if options.offline {
+                    anyhow::bail!(
+                        "🔒 {} is not available locally and --frozen forbids downloading it",
+                        name
+                    );
                 } else {
-                    let _checksum = deps::clone_git_repo(url, &dep_path).await?;
+                    let checksum = deps::clone_git_repo(url, &dep_path).await?;
+                    if let Err(e) = deps::verify_checksum(name, dep, &checksum) {
+                        std::fs::remove_dir_all(&dep_path)?;
+                        return Err(e);
+                    }
                     print_success(&format!("✅ Installed {}", name));
 
                     // Store in cache
@@ -3587,14 +3994,25 @@ async fn sync_dependencies(
                     }
                 }
             }
+            config::Dependency::Detailed { url, .. } if url.is_some() => {
+                print_info(&format!(
+                    "📦 {} will be downloaded from {}",
+                    name,
+                    url.as_ref().as_deref().unwrap_or_default()
+                ));
+            }
             config::Dependency::Detailed {
                 path: Some(path), ..
             } => {
                 print_info(&format!("📁 {} is a path dependency at {}", name, path));
             }
-            config::Dependency::Simple(spec) => {
-                print_warning(&format!(
-                    "⚠️  {}: Simple version spec not yet supported ({})",
+            config::Dependency::Simple(spec)
+            | config::Dependency::Detailed {
+                version: Some(spec),
+                ..
+            } => {
+                print_info(&format!(
+                    "📚 {} {} will be fetched from the registry",
                     name, spec
                 ));
             }
@@ -3610,12 +4028,12 @@ async fn sync_dependencies(
     // Execute post-install script if configured
     if let Some(post_install_script) = &config.build.scripts.post_install {
         print_info("🔧 Executing post-install script...");
-        execute_build_script(post_install_script, "post-install")?;
+        execute_build_script(post_install_script, "post-install", &config)?;
     }
 
     // Generate/update lockfile
     print_step("🔒 Updating lockfile");
-    generate_lockfile().await?;
+    generate_lockfile(options).await?;
 
     print_success("✅ Dependencies synced successfully!");
     Ok(())
@@ -3727,6 +4145,16 @@ fn print_dependency(name: &str, dep: &config::Dependency, tree: bool, indent: us
                 println!("{}    Source: {}", prefix, url);
             }
         }
+        config::Dependency::Detailed { url, .. } if url.is_some() => {
+            println!("{}  {} (archive)", prefix, name);
+            if tree {
+                println!(
+                    "{}    Source: {}",
+                    prefix,
+                    url.as_ref().as_deref().unwrap_or_default()
+                );
+            }
+        }
         config::Dependency::Detailed {
             path: Some(path), ..
         } => {
@@ -3833,7 +4261,11 @@ async fn self_update() -> Result<()> {
 }
 
 /// Update all dependencies to latest versions
-async fn update_deps(latest: bool) -> Result<()> {
+///
+/// Git dependencies move to newer release tags and registry dependencies to
+/// newer versions. porters.toml is edited in place, keeping its formatting
+/// and comments, and porters.lock is regenerated.
+async fn update_deps(latest: bool, dry_run: bool) -> Result<()> {
     if latest {
         print_step("Updating all dependencies to absolute latest versions");
         print_warning("This ignores version constraints!");
@@ -3841,46 +4273,67 @@ async fn update_deps(latest: bool) -> Result<()> {
         print_step("Updating dependencies to latest compatible versions");
     }
 
-    let mut config = PortersConfig::load("porters.toml")?;
-
-    let mut updated = 0;
-
-    // Update regular dependencies
-    for (name, dep) in &mut config.dependencies {
-        if let config::Dependency::Detailed {
-            git: Some(_url),
-            tag,
-            branch,
-            ..
-        } = dep
-        {
-            print_info(&format!("Checking {} for updates...", name));
-
-            // For now, just update to latest tag/branch
-            // In production, we'd query git remote for latest tags
-            if latest {
-                *tag = None;
-                *branch = Some("main".to_string());
-                updated += 1;
-                print_success(&format!("Updated {} to latest", name));
-            } else {
-                print_info(&format!("{} update check (respecting constraints)", name));
-            }
+    let config = PortersConfig::load("porters.toml")?;
+    let lock = lockfile::LockFile::load("porters.lock")?;
+    let report = deps::outdated::check_outdated(&config, &lock)?;
+    deps::outdated::print_outdated(&report);
+
+    let updates: Vec<_> = report
+        .iter()
+        .filter_map(|dep| Some((dep, dep.update_to(latest)?)))
+        .collect();
+    if updates.is_empty() {
+        if report.iter().any(|dep| dep.is_outdated()) {
+            print_info("Run 'porters update-deps --latest' to update past version constraints");
         }
+        return Ok(());
     }
 
-    if updated > 0 {
-        config.save("porters.toml")?;
-        print_success(&format!("Updated {} dependencies", updated));
-        print_info("Run 'porters sync' to apply updates");
-    } else {
-        print_info("All dependencies are up to date");
+    for (dep, release) in &updates {
+        print_info(&format!(
+            "{} {} → {}",
+            dep.name, dep.current.label, release.label
+        ));
+    }
+
+    if dry_run {
+        print_info("Dry run: porters.toml and porters.lock were not changed");
+        return Ok(());
     }
 
+    let content = std::fs::read_to_string("porters.toml").context("Failed to read porters.toml")?;
+    let updated = deps::outdated::update_manifest(&content, &report, latest)?;
+    std::fs::write("porters.toml", updated).context("Failed to write porters.toml")?;
+
+    // Re-resolve from scratch so the lockfile picks up the new versions
+    generate_lockfile(&deps::ResolveOptions {
+        ignore_lockfile: true,
+        ..Default::default()
+    })
+    .await?;
+
+    print_success(&format!("Updated {} dependencies", updates.len()));
+
+    Ok(())
+}
+
+/// Show dependencies that have newer versions available
+async fn show_outdated() -> Result<()> {
+    print_step("📋 Checking for outdated dependencies");
+
+    let config = PortersConfig::load("porters.toml")?;
+    let lock = lockfile::LockFile::load("porters.lock")?;
+    let report = deps::outdated::check_outdated(&config, &lock)?;
+    deps::outdated::print_outdated(&report);
+
     Ok(())
 }
 
 /// Cross-compile for specified platforms
+///
+/// Each target is built with porters.toml's build settings and `profile`,
+/// like a host build, against the dependencies resolved for it.
+#[allow(clippy::too_many_arguments)]
 async fn compile_cross(
     all_platforms: bool,
     linux: bool,
@@ -3888,6 +4341,9 @@ async fn compile_cross(
     macos: bool,
     baremetal: bool,
     target: Option<String>,
+    options: &deps::ResolveOptions,
+    profile: &str,
+    message_format: build::diagnostics::MessageFormat,
 ) -> Result<()> {
     print_step("Cross-compilation");
 
@@ -3930,7 +4386,7 @@ async fn compile_cross(
         default_target: config.cross_compile.default_target.clone(),
     };
 
-    let compiler = cross_compile::CrossCompiler::new(cross_config, project_root);
+    let compiler = cross_compile::CrossCompiler::new(cross_config, project_root.clone());
 
     // Collect targets to compile
     let mut targets = Vec::new();
@@ -3951,10 +4407,18 @@ async fn compile_cross(
             targets.extend(cross_compile::Target::for_platform("baremetal"));
         }
         if let Some(target_str) = target {
-            print_warning(&format!(
-                "Custom target triple not yet fully supported: {}",
-                target_str
-            ));
+            let Some(parsed) = cross_compile::Target::parse(&target_str) else {
+                let names: Vec<&str> = cross_compile::Target::all()
+                    .iter()
+                    .map(|t| t.name())
+                    .collect();
+                anyhow::bail!(
+                    "Unknown target '{}'. Supported targets: {}",
+                    target_str,
+                    names.join(", ")
+                );
+            };
+            targets.push(parsed);
         }
     }
 
@@ -3970,7 +4434,27 @@ async fn compile_cross(
         build_system_str
     ));
 
-    let build_dirs = compiler.compile_all(&targets, &build_system_str)?;
+    // Each target gets the dependencies declared for its platform
+    let diagnostics = build::diagnostics::Diagnostics::new(message_format);
+    let mut target_deps = Vec::new();
+    for target in &targets {
+        print_info(&format!(
+            "Resolving dependencies for {}",
+            target.display_name()
+        ));
+        let options = deps::ResolveOptions {
+            target: Some(target.name().to_string()),
+            ..options.clone()
+        };
+        let resolved = deps::resolve_dependencies_with(&config, &options).await?;
+        let build_options = project_build_options(&config, &options, &resolved, profile)?
+            .with_build_config(&project_root, &config.build)
+            .with_diagnostics(diagnostics.clone());
+        target_deps.push((target.clone(), resolved, build_options));
+    }
+
+    let build_dirs = compiler.compile_all(&target_deps, &build_system_str)?;
+    diagnostics.finish(build_dirs.len() == targets.len());
 
     print_success(&format!(
         "Successfully compiled for {} targets! 🎉",
@@ -3978,9 +4462,13 @@ async fn compile_cross(
     ));
 
     // Show build artifact locations
-    println!("\n📦  Build Artifacts:");
+    print_info("📦  Build Artifacts:");
     for (i, dir) in build_dirs.iter().enumerate() {
-        println!("  {} {}", targets[i].display_name(), dir.display());
+        print_info(&format!(
+            "  {} {}",
+            targets[i].display_name(),
+            dir.display()
+        ));
     }
 
     Ok(())
@@ -4932,70 +5420,350 @@ async fn handle_xmake_action(action: PackageManagerAction) -> Result<()> {
     Ok(())
 }
 
-async fn handle_registry_action(action: RegistryAction) -> Result<()> {
-    use crate::registry::RegistryManager;
-    use colored::Colorize;
-    use std::env;
+/// Record a registry package in porters.toml, fetch it and update porters.lock
+///
+/// Returns the project name and the include directories of the package.
+async fn add_registry_dependency(
+    pkg: &registry::PackageDefinition,
+    version: Option<String>,
+    pin_registry: bool,
+    dev: bool,
+) -> Result<(String, Vec<std::path::PathBuf>)> {
+    if !std::path::Path::new("porters.toml").exists() {
+        anyhow::bail!(
+            "No porters.toml found in the current directory. Run 'porters init' first or use --global"
+        );
+    }
+
+    let table = if dev {
+        "dev-dependencies"
+    } else {
+        "dependencies"
+    };
+    let requirement = version.unwrap_or_else(|| format!("^{}", pkg.version));
+    let registry = (pin_registry || pkg.registry != registry::DEFAULT_REGISTRY)
+        .then_some(pkg.registry.as_str());
+
+    let content = std::fs::read_to_string("porters.toml").context("Failed to read porters.toml")?;
+    let updated =
+        registry::install::add_to_manifest(&content, table, &pkg.name, &requirement, registry)?;
+    std::fs::write("porters.toml", updated).context("Failed to write porters.toml")?;
+    print_success(&format!(
+        "Added {} {} to [{}]",
+        pkg.name, requirement, table
+    ));
+
+    let config = PortersConfig::load("porters.toml")?;
+    let options = deps::ResolveOptions::default();
+    let resolved_deps = deps::resolve_dependencies_with(&config, &options).await?;
+    write_lockfile(&resolved_deps, &options)?;
+    print_success("Updated porters.lock 🔒");
+
+    // Dependencies and dev-dependencies are both resolved into the lock
+    let include_paths = resolved_deps
+        .iter()
+        .find(|dep| dep.name == pkg.name)
+        .map(|dep| dep.include_paths.clone())
+        .with_context(|| {
+            format!(
+                "{} was added to porters.toml but not resolved; check that it isn't optional or limited to another platform",
+                pkg.name
+            )
+        })?;
+
+    Ok((config.project.name, include_paths))
+}
 
-    // Get paths
-    let registry_path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("registry");
-    let home_dir =
-        dirs::home_dir().ok_or_else(|| anyhow::anyhow!("Could not find home directory"))?;
-    let cache_path = home_dir.join(".porters");
+/// Publish the current project to a registry
+fn publish_to_registry(registry: &str, category: Option<&str>, dry_run: bool) -> Result<()> {
+    use crate::registry::publish::{self, Published};
 
-    let manager = RegistryManager::new(registry_path, cache_path);
-    manager.init()?;
+    print_step(&format!("📤 Publishing to registry '{}'", registry));
+
+    let config = PortersConfig::load("porters.toml")?;
+    let release = publish::package_definition(&config, std::path::Path::new("."))?;
+    let category = category
+        .map(str::to_string)
+        .or_else(|| config.project.categories.first().cloned())
+        .or_else(|| config.project.keywords.first().cloned())
+        .unwrap_or_else(|| "misc".to_string());
+
+    print_info(&format!("Package: {} v{}", release.name, release.version));
+    if let Some(rev) = &release.rev {
+        print_info(&format!("Commit: {}", rev));
+    }
+
+    if dry_run {
+        let definition = publish::merge_release(None, &release)?;
+        println!("{}", serde_json::to_string_pretty(&definition)?);
+        print_warning("Dry run mode - nothing was published");
+        return Ok(());
+    }
+
+    let registries = registry::Registries::for_current_project()?;
+    match registries.get(registry)?.publish(&release, &category)? {
+        Published::Written(path) => {
+            print_success(&format!(
+                "Published {} v{} to {}",
+                release.name,
+                release.version,
+                path.display()
+            ));
+        }
+        Published::Submitted {
+            branch,
+            pull_request,
+        } => {
+            print_success(&format!(
+                "Pushed {} v{} to branch {}",
+                release.name, release.version, branch
+            ));
+            match pull_request {
+                Some(url) => print_info(&format!("Pull request: {}", url)),
+                None => print_info(&format!(
+                    "Open a merge request from {} to submit the package",
+                    branch
+                )),
+            }
+        }
+    }
+    print_info("Make sure the published commit is pushed to the package repository");
+
+    Ok(())
+}
+
+/// Snapshot registry packages and their sources into a mirror directory
+async fn mirror_registry(
+    registries: &registry::Registries,
+    dir: &str,
+    packages: &[String],
+    registry: Option<&str>,
+    all_versions: bool,
+) -> Result<()> {
+    use crate::registry::mirror::{self, MirrorRequest};
+
+    let requests: Vec<MirrorRequest> = if packages.is_empty() {
+        // Default to the registry versions pinned by porters.lock
+        let lock_path = crate::global_packages::project_lock_file(".");
+        if !lock_path.exists() {
+            anyhow::bail!(
+                "Name the packages to mirror, or run 'porters registry mirror' in a project with porters.lock"
+            );
+        }
+        let lock = lockfile::LockFile::load(&lock_path)?;
+        lock.dependencies
+            .values()
+            .filter_map(|dep| match &dep.source {
+                deps::DependencySource::Registry { registry } => Some(MirrorRequest {
+                    name: dep.name.clone(),
+                    requirement: Some(dep.version.clone()),
+                    registry: Some(registry.clone()),
+                }),
+                _ => None,
+            })
+            .collect()
+    } else {
+        packages
+            .iter()
+            .map(|spec| MirrorRequest::parse(spec, registry))
+            .collect::<Result<_>>()?
+    };
+    if requests.is_empty() {
+        print_info("porters.lock has no registry dependencies to mirror");
+        return Ok(());
+    }
+
+    let selection = mirror::select(registries, &requests, all_versions)?;
+    let dir = std::path::Path::new(dir);
+    let summary = mirror::mirror(dir, &selection, &deps::get_cache_dir()?).await?;
+
+    print_success(&format!(
+        "Mirrored {} packages into {} ({} versions added, {} already present)",
+        summary.packages,
+        dir.display(),
+        summary.stored,
+        summary.kept
+    ));
+    if let Some(url) = dir
+        .canonicalize()
+        .ok()
+        .and_then(|path| reqwest::Url::from_directory_path(path).ok())
+    {
+        print_info(&format!(
+            "Use it with url = \"{}\" in [[registries]] of porters.toml, or [registry] of ~/.porters/config.toml",
+            url.as_str().trim_end_matches('/')
+        ));
+    }
+
+    Ok(())
+}
+
+/// The current project's registries, with their indexes up to date
+fn initialized_registries() -> Result<crate::registry::Registries> {
+    let registries = crate::registry::Registries::for_current_project()?;
+    registries.init()?;
+    Ok(registries)
+}
+
+async fn handle_registry_action(action: RegistryAction) -> Result<()> {
+    use colored::Colorize;
 
     match action {
-        RegistryAction::Search { query } => {
-            let results = manager.search(&query)?;
+        // Indexing a directory doesn't involve the configured registries
+        RegistryAction::Index { dir } => {
+            let dir = std::path::Path::new(&dir);
+            let count = crate::registry::sparse::write_index(dir)?;
+            print_success(&format!(
+                "Wrote {} with {} packages",
+                dir.join(crate::registry::sparse::INDEX_FILE).display(),
+                count
+            ));
+        }
+        RegistryAction::Validate { path } => {
+            let (checked, invalid) =
+                crate::registry::schema::check_path(std::path::Path::new(&path))?;
+            for (file, errors) in &invalid {
+                print_error(&format!("{}", file.display()));
+                for error in errors {
+                    println!("    {}", error);
+                }
+            }
+            if !invalid.is_empty() {
+                anyhow::bail!(
+                    "{} of {} package definitions are invalid",
+                    invalid.len(),
+                    checked
+                );
+            }
+            print_success(&format!("All {} package definitions are valid", checked));
+        }
+        // Publishing writes to the registry source, not the local index
+        RegistryAction::Publish {
+            registry,
+            category,
+            dry_run,
+        } => publish_to_registry(&registry, category.as_deref(), dry_run)?,
+        RegistryAction::Search {
+            query,
+            tags,
+            platform,
+            license,
+            header_only,
+            min_cpp,
+            json,
+        } => {
+            let registries = initialized_registries()?;
+            let cpp_standard = match &min_cpp {
+                Some(standard) => Some(
+                    crate::deps::constraints::parse_standard(standard).ok_or_else(|| {
+                        anyhow::anyhow!("'{}' is not a known C++ standard", standard)
+                    })?,
+                ),
+                None => None,
+            };
+            let filters = crate::registry::search::SearchFilters {
+                tags,
+                platform,
+                license,
+                header_only,
+                cpp_standard,
+            };
+            let query = query.unwrap_or_default();
+            let results = registries.search_with(&query, &filters)?;
+
+            if json {
+                let results: Vec<_> = results
+                    .iter()
+                    .map(|pkg| crate::registry::search::SearchResult::new(pkg, &query))
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&results)?);
+                return Ok(());
+            }
+
+            let described = if query.is_empty() {
+                String::new()
+            } else {
+                format!(" for '{}'", query)
+            };
             if results.is_empty() {
-                println!("{}", format!(" No packages found for '{}'", query).yellow());
+                println!("{}", format!(" No packages found{}", described).yellow());
             } else {
                 println!(
                     "{}",
-                    format!(" Found {} package(s) for '{}':", results.len(), query).cyan()
+                    format!(" Found {} package(s){}:", results.len(), described).cyan()
                 );
                 println!();
                 for pkg in results {
-                    manager.display_package(&pkg);
+                    registries.get(&pkg.registry)?.display_package(&pkg);
                     println!();
                 }
             }
         }
-        RegistryAction::Add { package, global } => {
-            let pkg = manager.load_package(&package)?;
+        RegistryAction::Add {
+            package,
+            global,
+            registry,
+            version,
+            dev,
+        } => {
+            let registries = initialized_registries()?;
+            let manager = registries.registry_of(&package, registry.as_deref())?;
+            let pkg = registries.select_version(
+                &package,
+                version.as_deref().unwrap_or("*"),
+                Some(manager.name()),
+            )?;
 
             println!(
                 "{}",
                 format!(" Adding {} v{}", pkg.name, pkg.version).cyan()
             );
             println!("{}", format!("   Repository: {}", pkg.repository).dimmed());
+            if pkg.registry != crate::registry::DEFAULT_REGISTRY {
+                println!("{}", format!("   Registry: {}", pkg.registry).dimmed());
+            }
+
+            if !pkg.options.is_empty() {
+                let mut options: Vec<_> = pkg.options.iter().collect();
+                options.sort_by(|a, b| a.0.cmp(b.0));
+                println!("{}", "   Build options:".dimmed());
+                for (name, value) in options {
+                    println!("{}", format!("     {} = {}", name, value).dimmed());
+                }
+            }
+            println!();
 
-            // Resolve dependencies
-            let resolved = manager.resolve_dependencies(&package)?;
+            let (target, include_paths) = if global {
+                let installed = crate::registry::install::install_global(&registries, &pkg).await?;
+                for install in &installed {
+                    print_success(&format!(
+                        "Installed {} v{} to {}",
+                        install.package.name,
+                        install.package.version,
+                        install.dependency.path.display()
+                    ));
+                }
+                let include_paths = installed
+                    .first()
+                    .map(|install| install.dependency.include_paths.clone())
+                    .unwrap_or_default();
+                ("<target>".to_string(), include_paths)
+            } else {
+                add_registry_dependency(&pkg, version, registry.is_some(), dev).await?
+            };
 
-            if !resolved.is_empty() {
+            let snippet = crate::registry::install::usage_snippet(&pkg, &target, &include_paths);
+            if !snippet.is_empty() {
                 println!();
-                println!("{}", "Dependencies:".cyan());
-                for dep in &resolved {
-                    println!("   {} v{} ({})", dep.name, dep.version, dep.source);
+                print_info(&format!("Use {} in your build:", pkg.name));
+                for line in snippet {
+                    println!("    {}", line);
                 }
             }
-
-            let scope = if global { "global" } else { "project" };
-            println!();
-            println!(
-                "{}",
-                format!(" Package {} would be installed to {}", pkg.name, scope).green()
-            );
-            println!(
-                "{}",
-                "  (Implementation of actual installation coming in next phase)".dimmed()
-            );
         }
         RegistryAction::List => {
-            let packages = manager.list_all()?;
+            let registries = initialized_registries()?;
+            let packages = registries.list_all()?;
             if packages.is_empty() {
                 println!("{}", " No packages in registry".yellow());
             } else {
@@ -5004,24 +5772,75 @@ async fn handle_registry_action(action: RegistryAction) -> Result<()> {
                     format!(" Registry contains {} packages:", packages.len()).cyan()
                 );
                 println!();
+                let show_registry = registries.managers().len() > 1;
                 for pkg in packages {
+                    let registry = if show_registry {
+                        format!(" ({})", pkg.registry)
+                    } else {
+                        String::new()
+                    };
                     println!(
-                        "  {}  {} v{}",
+                        "  {}  {} v{}{}",
                         "".cyan(),
                         pkg.name.bright_white(),
-                        pkg.version.dimmed()
+                        pkg.version.dimmed(),
+                        registry.magenta()
                     );
                     println!("     {}", pkg.description.dimmed());
                 }
             }
         }
         RegistryAction::Update => {
-            manager.update_index()?;
+            // Opening the registries refreshes every index
+            initialized_registries()?;
             println!("{}", " Registry index updated successfully".green());
         }
-        RegistryAction::Info { package } => {
+        RegistryAction::Mirror {
+            dir,
+            packages,
+            registry,
+            all_versions,
+        } => {
+            let registries = initialized_registries()?;
+            mirror_registry(
+                &registries,
+                &dir,
+                &packages,
+                registry.as_deref(),
+                all_versions,
+            )
+            .await?;
+        }
+        RegistryAction::Info { package, registry } => {
+            let registries = initialized_registries()?;
+            let manager = registries.registry_of(&package, registry.as_deref())?;
             let pkg = manager.load_package(&package)?;
             manager.display_package(&pkg);
+            if let Some(notice) = pkg.deprecation_notice() {
+                println!();
+                print_warning(&notice);
+            }
+
+            // Show every published version
+            let releases = pkg.releases();
+            if releases.len() > 1 || releases.iter().any(|r| r.yanked) {
+                println!();
+                println!("{}", "Versions:".cyan());
+                for release in &releases {
+                    let source = match (&release.url, &release.rev, &release.tag) {
+                        (Some(url), _, _) => url.clone(),
+                        (None, Some(rev), _) => format!("rev {}", &rev[..rev.len().min(12)]),
+                        (None, None, Some(tag)) => format!("tag {}", tag),
+                        (None, None, None) => String::new(),
+                    };
+                    let line = format!("   {:<10} {}", release.version, source.dimmed());
+                    if release.yanked {
+                        println!("{} {}", line, "(yanked)".red());
+                    } else {
+                        println!("{}", line);
+                    }
+                }
+            }
 
             // Show dependencies
             if !pkg.dependencies.is_empty() {
@@ -85,7 +85,7 @@ pub fn publish_package(config: &PortersConfig, token: &str, dry_run: bool) -> Re
 }
 
 /// Parse GitHub repository URL to extract owner and repo name
-fn parse_github_repo(url: &str) -> Result<(String, String)> {
+pub(crate) fn parse_github_repo(url: &str) -> Result<(String, String)> {
     // Handle various URL formats:
     // https://github.com/owner/repo
     // https://github.com/owner/repo.git
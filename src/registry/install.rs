@@ -0,0 +1,245 @@
+//! Installing registry packages
+//!
+//! `porters registry add` records a registry package in porters.toml, or
+//! with `-g` installs it and its registry dependencies into
+//! `~/.porters/packages`, and prints how to use it from a project.
+
+use super::{PackageDefinition, Registries};
+use crate::deps::{self, ResolvedDependency};
+use crate::global_packages::GlobalConfig;
+use crate::util::pretty::*;
+use anyhow::{Context, Result, anyhow};
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, InlineTable, Item, Value};
+
+/// A registry package installed into `~/.porters/packages`
+pub struct InstalledPackage {
+    pub package: PackageDefinition,
+    /// The installed copy, with paths pointing into the install directory
+    pub dependency: ResolvedDependency,
+}
+
+/// Add or replace a registry dependency in the contents of porters.toml
+///
+/// The entry is a requirement string, or an inline table when the package
+/// must come from a specific registry. The rest of the file keeps its
+/// formatting and comments.
+pub fn add_to_manifest(
+    content: &str,
+    table: &str,
+    name: &str,
+    requirement: &str,
+    registry: Option<&str>,
+) -> Result<String> {
+    let mut doc: DocumentMut = content.parse().context("Failed to parse porters.toml")?;
+    let deps = doc
+        .entry(table)
+        .or_insert(toml_edit::table())
+        .as_table_like_mut()
+        .ok_or_else(|| anyhow!("[{}] in porters.toml is not a table", table))?;
+
+    let value = match registry {
+        Some(registry) => {
+            let mut entry = InlineTable::new();
+            entry.insert("version", requirement.into());
+            entry.insert("registry", registry.into());
+            Value::InlineTable(entry)
+        }
+        None => Value::from(requirement),
+    };
+    deps.insert(name, Item::Value(value));
+
+    Ok(doc.to_string())
+}
+
+/// Install a registry package and its registry dependencies globally
+///
+/// Every package is fetched like a project dependency, copied to
+/// `~/.porters/packages/<name>` (replacing an earlier install) and recorded
+/// in the global packages config.
+pub async fn install_global(
+    registries: &Registries,
+    pkg: &PackageDefinition,
+) -> Result<Vec<InstalledPackage>> {
+    GlobalConfig::initialize()?;
+    let cache_dir = deps::get_cache_dir()?;
+    let packages_dir = GlobalConfig::packages_dir()?;
+    let mut global = GlobalConfig::load()?;
+
+    let mut queue = VecDeque::from([pkg.clone()]);
+    let mut seen = HashSet::from([pkg.name.clone()]);
+    let mut installed = Vec::new();
+
+    while let Some(pkg) = queue.pop_front() {
+        let mut requirements: Vec<_> = pkg.dependencies.iter().collect();
+        requirements.sort();
+        for (name, requirement) in requirements {
+            if seen.insert(name.clone()) {
+                let dep = registries
+                    .select_version(name, requirement, None)
+                    .with_context(|| {
+                        format!("Failed to resolve {} (required by {})", name, pkg.name)
+                    })?;
+                queue.push_back(dep);
+            }
+        }
+
+        print_package(&format!("Installing {} v{}...", pkg.name, pkg.version));
+        let mut dependency =
+            deps::fetch_registry_package(&pkg.name, &pkg, &cache_dir, false).await?;
+
+        let install_path = packages_dir.join(&pkg.name);
+        if install_path.exists() {
+            fs::remove_dir_all(&install_path)
+                .with_context(|| format!("Failed to remove {}", install_path.display()))?;
+        }
+        deps::copy_dir_all(&dependency.path, &install_path)
+            .with_context(|| format!("Failed to install {}", pkg.name))?;
+        relocate(&mut dependency, &install_path);
+
+        global.add_package(
+            pkg.name.clone(),
+            pkg.version.clone(),
+            format!("registry+{}", pkg.registry),
+            install_path,
+        )?;
+        installed.push(InstalledPackage {
+            package: pkg,
+            dependency,
+        });
+    }
+
+    Ok(installed)
+}
+
+/// Point the paths of a fetched dependency at its installed copy
+fn relocate(dependency: &mut ResolvedDependency, install_path: &Path) {
+    let moved = |path: &PathBuf| match path.strip_prefix(&dependency.path) {
+        Ok(relative) => install_path.join(relative),
+        Err(_) => path.clone(),
+    };
+    dependency.include_paths = dependency.include_paths.iter().map(moved).collect();
+    dependency.lib_paths = dependency.lib_paths.iter().map(moved).collect();
+    dependency.path = install_path.to_path_buf();
+}
+
+/// Lines showing how to use a package from `target`
+///
+/// Uses the package's `install.cmake` and `install.pkg_config` metadata,
+/// followed by the include directories of the fetched sources.
+pub fn usage_snippet(
+    pkg: &PackageDefinition,
+    target: &str,
+    include_paths: &[PathBuf],
+) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    if let Some(cmake) = pkg.cmake_install() {
+        let find_package = cmake.find_package.as_deref().unwrap_or(&pkg.name);
+        let components = if cmake.components.is_empty() {
+            String::new()
+        } else {
+            format!(" COMPONENTS {}", cmake.components.join(" "))
+        };
+        lines.push("# CMakeLists.txt".to_string());
+        lines.push(format!(
+            "find_package({} CONFIG REQUIRED{})",
+            find_package, components
+        ));
+        if let Some((first, others)) = cmake.targets.split_first() {
+            lines.push(format!(
+                "target_link_libraries({} PRIVATE {})",
+                target, first
+            ));
+            if !others.is_empty() {
+                lines.push(format!("# Also available: {}", others.join(", ")));
+            }
+        }
+    }
+
+    if let Some(name) = pkg.pkg_config_name() {
+        lines.push("# pkg-config".to_string());
+        lines.push(format!("pkg-config --cflags --libs {}", name));
+    }
+
+    if !include_paths.is_empty() {
+        lines.push("# Include directories".to_string());
+        lines.extend(
+            include_paths
+                .iter()
+                .map(|path| format!("-I{}", path.display())),
+        );
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_to_manifest() {
+        let content = r#"[project]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+zlib = "^1.3"   # compression
+"#;
+        let updated = add_to_manifest(content, "dependencies", "fmt", "^10.2.1", None).unwrap();
+        let updated = add_to_manifest(
+            &updated,
+            "dev-dependencies",
+            "acme-test",
+            "^2.0.0",
+            Some("internal"),
+        )
+        .unwrap();
+        assert_eq!(
+            updated,
+            r#"[project]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+zlib = "^1.3"   # compression
+fmt = "^10.2.1"
+
+[dev-dependencies]
+acme-test = { version = "^2.0.0", registry = "internal" }
+"#
+        );
+    }
+
+    #[test]
+    fn test_usage_snippet() {
+        let pkg: PackageDefinition = serde_json::from_value(serde_json::json!({
+            "name": "fmt",
+            "description": "Formatting library",
+            "repository": "https://github.com/fmtlib/fmt",
+            "version": "10.2.1",
+            "license": "MIT",
+            "build_system": "cmake",
+            "install": {
+                "cmake": { "find_package": "fmt", "targets": ["fmt::fmt", "fmt::fmt-header-only"] }
+            }
+        }))
+        .unwrap();
+
+        let lines = usage_snippet(&pkg, "app", &[PathBuf::from("/deps/fmt/include")]);
+        assert_eq!(
+            lines,
+            vec![
+                "# CMakeLists.txt",
+                "find_package(fmt CONFIG REQUIRED)",
+                "target_link_libraries(app PRIVATE fmt::fmt)",
+                "# Also available: fmt::fmt-header-only",
+                "# Include directories",
+                "-I/deps/fmt/include",
+            ]
+        );
+    }
+}
@@ -0,0 +1,513 @@
+//! Offline registry mirrors
+//!
+//! `porters registry mirror <dir>` snapshots chosen packages into a
+//! directory that can be carried into an air-gapped network:
+//!
+//! ```text
+//! <dir>/index.json                               package listing
+//! <dir>/<name>.json                              mirrored definitions
+//! <dir>/sources/<name>/<name>-<version>.tar.gz   sources of each version
+//! ```
+//!
+//! Versions published as source archives are copied as they are. Versions
+//! published from a Git repository are packed into an archive of their
+//! exported sources, so using the mirror needs neither network nor Git
+//! access. Archive URLs in the mirror are relative to its root and resolved
+//! against wherever the mirror is read from: a registry directory, a
+//! `file://` registry or a `sparse+http(s)://` URL.
+
+use super::{PackageDefinition, PublishedVersion, Registries, publish, schema, sparse};
+use crate::deps::{self, archive::ArchiveFormat};
+use crate::util::pretty::*;
+use crate::version::{Version, VersionReq};
+use anyhow::{Context, Result, anyhow, bail};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Directory of a mirror holding the source archives
+pub const SOURCES_DIR: &str = "sources";
+
+/// A package to mirror, written `name` or `name@requirement`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MirrorRequest {
+    pub name: String,
+    /// Versions to mirror (default: the latest)
+    pub requirement: Option<String>,
+    /// Registry to take the package from (default: first registry that has it)
+    pub registry: Option<String>,
+}
+
+impl MirrorRequest {
+    pub fn parse(spec: &str, registry: Option<&str>) -> Result<Self> {
+        let (name, requirement) = match spec.split_once('@') {
+            Some((name, requirement)) => {
+                VersionReq::parse(requirement)
+                    .with_context(|| format!("Invalid version requirement in '{}'", spec))?;
+                (name, Some(requirement.to_string()))
+            }
+            None => (spec, None),
+        };
+        if name.is_empty() {
+            bail!("Missing package name in '{}'", spec);
+        }
+        Ok(Self {
+            name: name.to_string(),
+            requirement,
+            registry: registry.map(String::from),
+        })
+    }
+}
+
+/// Versions chosen for a mirror, newest first, by package name
+pub type Selection = BTreeMap<String, Vec<PackageDefinition>>;
+
+/// Choose the versions to mirror, with the registry dependencies they need
+///
+/// Each request gets its highest matching version, or every matching
+/// version with `all_versions`; dependencies get their highest matching
+/// version. Yanked versions are only mirrored when asked for by their
+/// exact version, as porters.lock does.
+pub fn select(
+    registries: &Registries,
+    requests: &[MirrorRequest],
+    all_versions: bool,
+) -> Result<Selection> {
+    let mut selection = Selection::new();
+    let mut seen = HashSet::new();
+    let mut queue: VecDeque<(MirrorRequest, Option<String>)> = requests
+        .iter()
+        .map(|request| (request.clone(), None))
+        .collect();
+
+    while let Some((request, required_by)) = queue.pop_front() {
+        if !seen.insert(request.clone()) {
+            continue;
+        }
+
+        let context = || match &required_by {
+            Some(parent) => format!("Failed to mirror {} (required by {})", request.name, parent),
+            None => format!("Failed to mirror {}", request.name),
+        };
+        let releases = registries
+            .load_versions(&request.name, request.registry.as_deref())
+            .with_context(context)?;
+        let chosen = choose(
+            &releases,
+            request.requirement.as_deref(),
+            all_versions && required_by.is_none(),
+        )
+        .with_context(context)?;
+
+        let versions = selection.entry(request.name.clone()).or_default();
+        for release in chosen {
+            if versions.iter().any(|v| v.version == release.version) {
+                continue;
+            }
+            let mut requirements: Vec<_> = release.dependencies.iter().collect();
+            requirements.sort();
+            for (name, requirement) in requirements {
+                let dependency = MirrorRequest {
+                    name: name.clone(),
+                    requirement: Some(requirement.clone()),
+                    registry: None,
+                };
+                queue.push_back((dependency, Some(release.name.clone())));
+            }
+            versions.push(release);
+        }
+        versions.sort_by(|a, b| {
+            Version::parse(&b.version)
+                .ok()
+                .cmp(&Version::parse(&a.version).ok())
+        });
+    }
+
+    Ok(selection)
+}
+
+/// Releases matching a requirement, newest first
+fn choose(
+    releases: &[PackageDefinition],
+    requirement: Option<&str>,
+    all: bool,
+) -> Result<Vec<PackageDefinition>> {
+    let req = requirement.map(VersionReq::parse).transpose()?;
+    let exact = matches!(req, Some(VersionReq::Exact(_)));
+
+    let mut matching = releases
+        .iter()
+        .filter(|pkg| {
+            (!pkg.yanked || exact)
+                && req.as_ref().is_none_or(|req| {
+                    Version::parse(&pkg.version).is_ok_and(|version| req.matches(&version))
+                })
+        })
+        .cloned()
+        .peekable();
+
+    if matching.peek().is_none() {
+        let available: Vec<&str> = releases
+            .iter()
+            .filter(|pkg| !pkg.yanked)
+            .map(|pkg| pkg.version.as_str())
+            .collect();
+        bail!(
+            "No version satisfies {} (registry has {})",
+            requirement.unwrap_or("*"),
+            available.join(", ")
+        );
+    }
+
+    Ok(if all {
+        matching.collect()
+    } else {
+        matching.take(1).collect()
+    })
+}
+
+/// Outcome of writing a mirror
+#[derive(Debug, Default)]
+pub struct MirrorSummary {
+    pub packages: usize,
+    /// Versions whose sources were stored
+    pub stored: usize,
+    /// Versions that were already in the mirror
+    pub kept: usize,
+}
+
+/// Write the selected versions into the mirror directory `dir`
+///
+/// Versions already in the mirror are kept, so running the command again
+/// extends a mirror; their sources are only fetched again when the archive
+/// is missing. `index.json` is rewritten afterwards.
+pub async fn mirror(dir: &Path, selection: &Selection, cache_dir: &Path) -> Result<MirrorSummary> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let mut summary = MirrorSummary::default();
+
+    for (name, releases) in selection {
+        let Some(latest) = releases.first() else {
+            continue;
+        };
+        let path = dir.join(format!("{}.json", name));
+        let mut mirrored = if path.exists() {
+            load_definition(&path)?.releases()
+        } else {
+            Vec::new()
+        };
+
+        for release in releases {
+            let existing = mirrored.iter().position(|m| m.version == release.version);
+            if let Some(i) = existing
+                && mirrored[i]
+                    .url
+                    .as_deref()
+                    .is_some_and(|url| dir.join(url).is_file())
+            {
+                summary.kept += 1;
+                continue;
+            }
+
+            print_package(&format!("Mirroring {} v{}...", name, release.version));
+            let (url, sha256) = store_sources(dir, release, cache_dir)
+                .await
+                .with_context(|| format!("Failed to mirror {} v{}", name, release.version))?;
+            let release = PackageDefinition {
+                url: Some(url),
+                sha256: Some(sha256),
+                ..release.clone()
+            };
+            match existing {
+                Some(i) => mirrored[i] = release,
+                None => mirrored.push(release),
+            }
+            summary.stored += 1;
+        }
+
+        let definition = mirror_definition(latest, mirrored)?;
+        fs::write(&path, serde_json::to_string_pretty(&definition)? + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        summary.packages += 1;
+    }
+
+    sparse::write_index(dir)?;
+    Ok(summary)
+}
+
+fn load_definition(path: &Path) -> Result<PackageDefinition> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Definition listing every mirrored version, the newest at the top level
+///
+/// Package metadata (description, options, install, ...) comes from `base`.
+fn mirror_definition(
+    base: &PackageDefinition,
+    mut releases: Vec<PackageDefinition>,
+) -> Result<serde_json::Value> {
+    releases.sort_by(|a, b| {
+        Version::parse(&b.version)
+            .ok()
+            .cmp(&Version::parse(&a.version).ok())
+    });
+    let latest = releases
+        .first()
+        .ok_or_else(|| anyhow!("No versions of {} to mirror", base.name))?;
+
+    let versions = releases
+        .iter()
+        .rev()
+        .map(|r| PublishedVersion {
+            version: r.version.clone(),
+            tag: r.tag.clone(),
+            rev: r.rev.clone(),
+            url: r.url.clone(),
+            sha256: r.sha256.clone(),
+            checksum: r.checksum.clone(),
+            dependencies: (r.dependencies != latest.dependencies).then(|| r.dependencies.clone()),
+            yanked: r.yanked,
+        })
+        .collect();
+
+    let pkg = PackageDefinition {
+        version: latest.version.clone(),
+        tag: latest.tag.clone(),
+        rev: latest.rev.clone(),
+        url: latest.url.clone(),
+        sha256: latest.sha256.clone(),
+        checksum: latest.checksum.clone(),
+        dependencies: latest.dependencies.clone(),
+        yanked: latest.yanked,
+        versions,
+        ..base.clone()
+    };
+
+    let definition = publish::definition_json(&pkg)?;
+    schema::validate(&pkg.name, &definition)?;
+    Ok(definition)
+}
+
+/// Copy or pack the sources of a version into the mirror
+///
+/// Returns the archive's path relative to the mirror and its SHA-256 hash.
+async fn store_sources(
+    dir: &Path,
+    release: &PackageDefinition,
+    cache_dir: &Path,
+) -> Result<(String, String)> {
+    let (data, format) = match &release.url {
+        Some(url) => {
+            let data = deps::archive::download(url)
+                .await
+                .with_context(|| format!("Failed to download {}", url))?;
+            let format = ArchiveFormat::from_url(url)
+                .or_else(|| ArchiveFormat::sniff(&data))
+                .ok_or_else(|| anyhow!("Unsupported archive format: {}", url))?;
+            (data, format)
+        }
+        None => {
+            let fetched =
+                deps::fetch_registry_package(&release.name, release, cache_dir, false).await?;
+            (pack(&fetched.path)?, ArchiveFormat::TarGz)
+        }
+    };
+
+    let sha256 = hex::encode(Sha256::digest(&data));
+    if let Some(expected) = &release.sha256
+        && !expected.eq_ignore_ascii_case(&sha256)
+    {
+        bail!(
+            "Checksum mismatch for the source archive:\n  expected: {}\n  actual:   {}",
+            expected,
+            sha256
+        );
+    }
+
+    let relative = format!(
+        "{}/{}/{}-{}.{}",
+        SOURCES_DIR,
+        release.name,
+        release.name,
+        release.version,
+        format.extension()
+    );
+    let path = dir.join(&relative);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, &data).with_context(|| format!("Failed to write {}", path.display()))?;
+
+    Ok((relative, sha256))
+}
+
+/// Pack a source tree into a `.tar.gz` that is the same every time
+fn pack(dir: &Path) -> Result<Vec<u8>> {
+    let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    builder.mode(tar::HeaderMode::Deterministic);
+    builder.follow_symlinks(false);
+
+    for entry in WalkDir::new(dir).follow_links(false).sort_by_file_name() {
+        let entry = entry?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(dir)?;
+        builder
+            .append_path_with_name(entry.path(), relative)
+            .with_context(|| format!("Failed to pack {}", entry.path().display()))?;
+    }
+
+    Ok(builder.into_inner()?.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::RegistryManager;
+    use tempfile::TempDir;
+
+    fn write_entry(registry: &Path, name: &str, fields: serde_json::Value) {
+        let mut definition = serde_json::json!({
+            "name": name,
+            "description": "A test package",
+            "repository": format!("https://example.com/{}", name),
+            "license": "MIT",
+            "build_system": "cmake"
+        });
+        for (key, value) in fields.as_object().unwrap() {
+            definition[key] = value.clone();
+        }
+        fs::write(
+            registry.join(format!("{}.json", name)),
+            definition.to_string(),
+        )
+        .unwrap();
+    }
+
+    fn registries(temp: &TempDir, registry: &Path) -> Registries {
+        let manager = RegistryManager::new(registry.to_path_buf(), temp.path().join("cache"));
+        manager.init().unwrap();
+        Registries::new(vec![manager])
+    }
+
+    #[test]
+    fn test_select() {
+        let temp = TempDir::new().unwrap();
+        let registry = temp.path().join("registry");
+        fs::create_dir_all(&registry).unwrap();
+        write_entry(
+            &registry,
+            "app-kit",
+            serde_json::json!({
+                "version": "2.1.0",
+                "dependencies": { "zlib": "^1.2" },
+                "versions": [
+                    { "version": "1.0.0", "dependencies": {} },
+                    { "version": "2.0.0" },
+                    { "version": "2.1.0" }
+                ]
+            }),
+        );
+        write_entry(
+            &registry,
+            "zlib",
+            serde_json::json!({
+                "version": "1.3.1",
+                "versions": [
+                    { "version": "1.2.13" },
+                    { "version": "1.3.0", "yanked": true },
+                    { "version": "1.3.1" }
+                ]
+            }),
+        );
+        let registries = registries(&temp, &registry);
+        let versions = |selection: &Selection, name: &str| -> Vec<String> {
+            selection[name].iter().map(|r| r.version.clone()).collect()
+        };
+
+        let requests = [MirrorRequest::parse("app-kit@^2", None).unwrap()];
+        let selection = select(&registries, &requests, false).unwrap();
+        assert_eq!(versions(&selection, "app-kit"), vec!["2.1.0"]);
+        assert_eq!(versions(&selection, "zlib"), vec!["1.3.1"]);
+
+        let selection = select(&registries, &requests, true).unwrap();
+        assert_eq!(versions(&selection, "app-kit"), vec!["2.1.0", "2.0.0"]);
+        assert_eq!(versions(&selection, "zlib"), vec!["1.3.1"]);
+
+        // Yanked versions only when asked for exactly
+        let requests = [MirrorRequest::parse("zlib", None).unwrap()];
+        let selection = select(&registries, &requests, true).unwrap();
+        assert_eq!(versions(&selection, "zlib"), vec!["1.3.1", "1.2.13"]);
+        let requests = [MirrorRequest::parse("zlib@1.3.0", None).unwrap()];
+        let selection = select(&registries, &requests, false).unwrap();
+        assert_eq!(versions(&selection, "zlib"), vec!["1.3.0"]);
+
+        let requests = [MirrorRequest::parse("zlib@^2", None).unwrap()];
+        assert!(select(&registries, &requests, false).is_err());
+        assert!(MirrorRequest::parse("@1.0.0", None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mirror() {
+        let temp = TempDir::new().unwrap();
+        let sources = temp.path().join("sources");
+        fs::create_dir_all(sources.join("zlib-1.3.1")).unwrap();
+        fs::write(sources.join("zlib-1.3.1").join("zlib.h"), "header").unwrap();
+        let data = pack(&sources).unwrap();
+        let archive = temp.path().join("zlib-1.3.1.tar.gz");
+        fs::write(&archive, &data).unwrap();
+        let archive_url = reqwest::Url::from_file_path(&archive).unwrap().to_string();
+
+        let registry = temp.path().join("registry");
+        fs::create_dir_all(&registry).unwrap();
+        write_entry(
+            &registry,
+            "zlib",
+            serde_json::json!({
+                "version": "1.3.1",
+                "url": archive_url,
+                "sha256": hex::encode(Sha256::digest(&data)),
+                "options": { "shared": false }
+            }),
+        );
+        let registries = registries(&temp, &registry);
+
+        let dir = temp.path().join("mirror");
+        let requests = [MirrorRequest::parse("zlib", None).unwrap()];
+        let selection = select(&registries, &requests, false).unwrap();
+        let cache = temp.path().join("cache");
+        let summary = mirror(&dir, &selection, &cache).await.unwrap();
+        assert_eq!((summary.packages, summary.stored), (1, 1));
+        assert_eq!(
+            fs::read(dir.join("sources/zlib/zlib-1.3.1.tar.gz")).unwrap(),
+            data
+        );
+        assert!(dir.join(sparse::INDEX_FILE).exists());
+
+        // Mirroring again keeps the stored sources
+        let summary = mirror(&dir, &selection, &cache).await.unwrap();
+        assert_eq!((summary.stored, summary.kept), (0, 1));
+
+        // The mirror is a registry of its own, with URLs relative to it
+        let definition = load_definition(&dir.join("zlib.json")).unwrap();
+        assert_eq!(
+            definition.url.as_deref(),
+            Some("sources/zlib/zlib-1.3.1.tar.gz")
+        );
+        assert_eq!(definition.options["shared"], serde_json::json!(false));
+
+        let manager = RegistryManager::new(dir.clone(), temp.path().join("mirror-cache"));
+        let pkg = manager.select_version("zlib", "^1.3").unwrap();
+        let url = pkg.url.unwrap();
+        assert!(url.starts_with("file://"), "{}", url);
+        assert_eq!(
+            deps::archive::download(&url).await.unwrap(),
+            fs::read(dir.join("sources/zlib/zlib-1.3.1.tar.gz")).unwrap()
+        );
+    }
+}
@@ -0,0 +1,589 @@
+//! Publishing packages to a registry
+//!
+//! `porters registry publish` turns porters.toml into a package definition
+//! pinned to the current commit, checks it against the registry schema and
+//! the versions already published, then writes it into a local registry
+//! directory or pushes it as a branch to a Git-backed registry.
+
+use super::sparse::{self, INDEX_FILE};
+use super::{PackageDefinition, PublishedVersion, RegistryManager, authenticated_url, schema};
+use crate::config::{Dependency, PortersConfig};
+use crate::deps::git::{GitFetcher, GitSource};
+use crate::util::pretty::*;
+use crate::version::Version;
+use anyhow::{Context, Result, anyhow, bail};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Build systems package definitions can declare
+const BUILD_SYSTEMS: &[&str] = &["cmake", "meson", "xmake", "autotools", "bazel"];
+
+/// Where a release ended up
+pub enum Published {
+    /// Written into the registry directory
+    Written(PathBuf),
+    /// Pushed as a branch of the registry repository
+    Submitted {
+        branch: String,
+        pull_request: Option<String>,
+    },
+}
+
+/// Build the registry definition of the project in `root`
+///
+/// The release is pinned to the commit checked out in `root`, with the
+/// checksum consumers will compute for its sources.
+pub fn package_definition(config: &PortersConfig, root: &Path) -> Result<PackageDefinition> {
+    let project = &config.project;
+
+    let mut missing = Vec::new();
+    if project.description.is_none() {
+        missing.push("description");
+    }
+    if project.license.is_none() {
+        missing.push("license");
+    }
+    if project.repository.is_none() {
+        missing.push("repository");
+    }
+    if !missing.is_empty() {
+        bail!(
+            "porters.toml is missing fields required to publish: {}\n\
+             Add them to the [project] section",
+            missing.join(", ")
+        );
+    }
+
+    Version::parse(&project.version)
+        .with_context(|| format!("Version '{}' is not a valid version", project.version))?;
+
+    let build_system = crate::build::detect_build_system(&root.to_string_lossy(), config)?
+        .name()
+        .to_lowercase();
+    let build_system = if BUILD_SYSTEMS.contains(&build_system.as_str()) {
+        build_system
+    } else {
+        "custom".to_string()
+    };
+
+    let (rev, tag) = source_revision(root, &project.version)?;
+    print_info("Calculating checksum...");
+    let checksum = source_checksum(root, &project.name, &rev)?;
+
+    Ok(PackageDefinition {
+        name: project.name.clone(),
+        description: project.description.clone().unwrap_or_default(),
+        repository: project.repository.clone().unwrap_or_default(),
+        version: project.version.clone(),
+        tag,
+        license: project.license.clone().unwrap_or_default(),
+        build_system,
+        dependencies: registry_requirements(&config.dependencies, "dependencies")?,
+        dev_dependencies: registry_requirements(&config.dev_dependencies, "dev-dependencies")
+            .unwrap_or_else(|err| {
+                print_warning(&format!("{}; leaving dev dependencies out", err));
+                HashMap::new()
+            }),
+        options: HashMap::new(),
+        install: HashMap::new(),
+        tags: project.keywords.clone(),
+        homepage: project.homepage.clone(),
+        documentation: None,
+        platforms: project.platforms.clone(),
+        constraints: None,
+        features: HashMap::new(),
+        rev: Some(rev),
+        url: None,
+        sha256: None,
+        checksum: Some(format!("sha256:{}", checksum)),
+        yanked: false,
+        deprecated: None,
+        replaced_by: None,
+        versions: vec![],
+        registry: super::default_registry_name(),
+    })
+}
+
+/// Version requirements of registry dependencies
+///
+/// Registry packages can only depend on other registry packages.
+fn registry_requirements(
+    deps: &HashMap<String, Dependency>,
+    table: &str,
+) -> Result<HashMap<String, String>> {
+    let mut requirements = HashMap::new();
+    for (name, dep) in deps {
+        let requirement = match dep {
+            Dependency::Simple(version) => version,
+            Dependency::Detailed {
+                version: Some(version),
+                git: None,
+                path: None,
+                url,
+                ..
+            } if url.is_none() => version,
+            _ => bail!(
+                "[{}] {} is not a registry dependency; published packages can only depend on registry packages",
+                table,
+                name
+            ),
+        };
+        requirements.insert(name.clone(), requirement.clone());
+    }
+    Ok(requirements)
+}
+
+/// Commit checked out in `root` and the release tag pointing at it
+fn source_revision(root: &Path, version: &str) -> Result<(String, Option<String>)> {
+    let repo = git2::Repository::open(root).with_context(|| {
+        format!(
+            "{} is not the root of a Git repository; registry packages are published from their repository",
+            root.display()
+        )
+    })?;
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .context("The repository has no commits")?
+        .id();
+
+    let mut options = git2::StatusOptions::new();
+    options.include_untracked(false);
+    if !repo.statuses(Some(&mut options))?.is_empty() {
+        print_warning("Uncommitted changes are not part of the published sources");
+    }
+
+    let mut tag = None;
+    for candidate in [format!("v{}", version), version.to_string()] {
+        let Ok(object) = repo.revparse_single(&format!("refs/tags/{}", candidate)) else {
+            continue;
+        };
+        if object.peel_to_commit()?.id() == head {
+            tag = Some(candidate);
+            break;
+        }
+        print_warning(&format!(
+            "Tag {} doesn't point at the current commit; publishing the commit instead",
+            candidate
+        ));
+    }
+
+    Ok((head.to_string(), tag))
+}
+
+/// Checksum of the sources of `rev`, computed the way consumers do
+fn source_checksum(root: &Path, name: &str, rev: &str) -> Result<String> {
+    let scratch =
+        std::env::temp_dir().join(format!("porters-publish-{}-{}", name, std::process::id()));
+    let url = root
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {}", root.display()))?;
+    let url = url.to_string_lossy();
+
+    let checksum = GitFetcher::new(&scratch, false)
+        .checkout(
+            name,
+            &GitSource {
+                rev: Some(rev),
+                ..GitSource::new(&url)
+            },
+        )
+        .and_then(|checkout| crate::hash::calculate_directory_hash(&checkout.path));
+    fs::remove_dir_all(&scratch).ok();
+    checksum
+}
+
+/// The definition as registry JSON, without empty fields
+pub(super) fn definition_json(release: &PackageDefinition) -> Result<serde_json::Value> {
+    let mut value = serde_json::to_value(release)?;
+    if let Some(fields) = value.as_object_mut() {
+        fields.retain(|_, field| match field {
+            serde_json::Value::Null => false,
+            serde_json::Value::Array(items) => !items.is_empty(),
+            serde_json::Value::Object(entries) => !entries.is_empty(),
+            _ => true,
+        });
+    }
+    Ok(value)
+}
+
+fn same_repository(a: &str, b: &str) -> bool {
+    let normalize = |url: &str| {
+        url.trim_end_matches('/')
+            .trim_end_matches(".git")
+            .to_lowercase()
+    };
+    normalize(a) == normalize(b)
+}
+
+/// Add a release to the existing registry entry of the package, if any
+///
+/// Every version is listed in `versions`; fields maintained by the registry
+/// (install metadata, options, ...) are kept unless the release sets them.
+pub fn merge_release(
+    existing: Option<serde_json::Value>,
+    release: &PackageDefinition,
+) -> Result<serde_json::Value> {
+    let new = definition_json(release)?;
+    let Some(entry) = existing else {
+        schema::validate(&release.name, &new)?;
+        return Ok(new);
+    };
+
+    let current: PackageDefinition = serde_json::from_value(entry)
+        .with_context(|| format!("Invalid registry entry for '{}'", release.name))?;
+    if current.name != release.name {
+        bail!(
+            "The registry file for '{}' defines package '{}'",
+            release.name,
+            current.name
+        );
+    }
+    if !same_repository(&current.repository, &release.repository) {
+        bail!(
+            "Package '{}' is already published from {}; choose another name",
+            release.name,
+            current.repository
+        );
+    }
+
+    let version = Version::parse(&release.version)?;
+    let releases = current.releases();
+    if releases.iter().any(|r| r.version == release.version) {
+        bail!("{} v{} is already published", release.name, release.version);
+    }
+    if let Some(latest) = releases
+        .iter()
+        .filter_map(|r| Version::parse(&r.version).ok())
+        .max()
+        .filter(|latest| *latest > version)
+    {
+        bail!(
+            "{} v{} is older than the latest published version v{}",
+            release.name,
+            release.version,
+            latest
+        );
+    }
+
+    let versions: Vec<PublishedVersion> = releases
+        .iter()
+        .rev()
+        .chain(std::iter::once(release))
+        .map(|r| PublishedVersion {
+            version: r.version.clone(),
+            tag: r.tag.clone(),
+            rev: r.rev.clone(),
+            url: r.url.clone(),
+            sha256: r.sha256.clone(),
+            checksum: r.checksum.clone(),
+            dependencies: (r.dependencies != release.dependencies).then(|| r.dependencies.clone()),
+            yanked: r.yanked,
+        })
+        .collect();
+
+    // Fields the release leaves empty keep their registry-maintained values
+    let merged = PackageDefinition {
+        options: if release.options.is_empty() {
+            current.options
+        } else {
+            release.options.clone()
+        },
+        install: if release.install.is_empty() {
+            current.install
+        } else {
+            release.install.clone()
+        },
+        tags: if release.tags.is_empty() {
+            current.tags
+        } else {
+            release.tags.clone()
+        },
+        homepage: release.homepage.clone().or(current.homepage),
+        documentation: release.documentation.clone().or(current.documentation),
+        platforms: if release.platforms.is_empty() {
+            current.platforms
+        } else {
+            release.platforms.clone()
+        },
+        constraints: release.constraints.clone().or(current.constraints),
+        features: if release.features.is_empty() {
+            current.features
+        } else {
+            release.features.clone()
+        },
+        deprecated: release.deprecated.clone().or(current.deprecated),
+        replaced_by: release.replaced_by.clone().or(current.replaced_by),
+        versions,
+        ..release.clone()
+    };
+
+    let entry = definition_json(&merged)?;
+    schema::validate(&release.name, &entry)?;
+    Ok(entry)
+}
+
+/// Write a release into the registry directory `dir`
+///
+/// An existing definition of the package is updated where it is; new
+/// packages go to `<category>/<name>.json`. `index.json` is regenerated if
+/// the directory has one.
+pub fn publish_to_directory(
+    dir: &Path,
+    category: &str,
+    release: &PackageDefinition,
+) -> Result<PathBuf> {
+    let index = sparse::scan_directory(dir)?;
+    let (path, existing) = match index.packages.get(&release.name) {
+        Some(relative) => {
+            let path = dir.join(relative);
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let existing = serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse {}", path.display()))?;
+            (path, Some(existing))
+        }
+        None => (
+            dir.join(category).join(format!("{}.json", release.name)),
+            None,
+        ),
+    };
+
+    let definition = merge_release(existing, release)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(&definition)? + "\n")
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    if dir.join(INDEX_FILE).exists() {
+        sparse::write_index(dir)?;
+    }
+    Ok(path)
+}
+
+impl RegistryManager {
+    /// Publish a release to this registry
+    ///
+    /// Local registries are written directly. Git registries get a
+    /// `publish/<name>-<version>` branch, and a pull request when they are
+    /// hosted on GitHub and a token is configured.
+    pub fn publish(&self, release: &PackageDefinition, category: &str) -> Result<Published> {
+        if let Some(dir) = self.local_registry() {
+            return publish_to_directory(dir, category, release).map(Published::Written);
+        }
+
+        match self.url.as_deref() {
+            Some(url) if url.starts_with(sparse::SPARSE_PREFIX) => bail!(
+                "Registry '{}' is served over HTTP; publish to the directory or repository it is served from",
+                self.name
+            ),
+            Some(url) => self.submit(url, release, category),
+            None => bail!("Registry '{}' has no location to publish to", self.name),
+        }
+    }
+
+    fn submit(&self, url: &str, release: &PackageDefinition, category: &str) -> Result<Published> {
+        let clone_url = authenticated_url(url, self.auth_token.as_deref());
+        let work_dir = std::env::temp_dir().join(format!(
+            "porters-publish-{}-{}",
+            self.name,
+            std::process::id()
+        ));
+        if work_dir.exists() {
+            fs::remove_dir_all(&work_dir)?;
+        }
+
+        let result = (|| {
+            let git = |args: &[&str]| -> Result<String> {
+                let output = Command::new("git")
+                    .arg("-C")
+                    .arg(&work_dir)
+                    .args(args)
+                    .output()
+                    .context("Failed to execute git. Make sure git is installed.")?;
+                if !output.status.success() {
+                    bail!(
+                        "git {} failed: {}",
+                        args.first().copied().unwrap_or_default(),
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    );
+                }
+                Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+            };
+
+            print_info(&format!("Cloning registry '{}' from {}...", self.name, url));
+            let status = Command::new("git")
+                .args(["clone", "--depth", "1", &clone_url])
+                .arg(&work_dir)
+                .status()
+                .context("Failed to execute git clone. Make sure git is installed.")?;
+            if !status.success() {
+                bail!(
+                    "Failed to clone registry '{}' from {}. Check the repository URL and auth token.",
+                    self.name,
+                    url
+                );
+            }
+
+            let base_branch = git(&["rev-parse", "--abbrev-ref", "HEAD"])?;
+            let packages_dir = if work_dir.join("registry").is_dir() {
+                work_dir.join("registry")
+            } else {
+                work_dir.clone()
+            };
+            publish_to_directory(&packages_dir, category, release)?;
+
+            let branch = format!("publish/{}-{}", release.name, release.version);
+            let message = format!("Publish {} v{}", release.name, release.version);
+            git(&["checkout", "-b", &branch])?;
+            git(&["add", "-A"])?;
+            git(&["commit", "-m", &message])?;
+            print_info(&format!("Pushing {}...", branch));
+            git(&["push", "origin", &branch]).with_context(|| {
+                format!(
+                    "Failed to push to registry '{}'. Publishing needs write access to {}",
+                    self.name, url
+                )
+            })?;
+
+            let pull_request = match (crate::publish::parse_github_repo(url), &self.auth_token) {
+                (Ok((owner, repo)), Some(token)) => Some(open_pull_request(
+                    &owner,
+                    &repo,
+                    token,
+                    &branch,
+                    &base_branch,
+                    &message,
+                )?),
+                _ => None,
+            };
+
+            Ok(Published::Submitted {
+                branch,
+                pull_request,
+            })
+        })();
+
+        fs::remove_dir_all(&work_dir).ok();
+        result
+    }
+}
+
+/// Open a pull request for a pushed branch, returning its URL
+fn open_pull_request(
+    owner: &str,
+    repo: &str,
+    token: &str,
+    branch: &str,
+    base: &str,
+    title: &str,
+) -> Result<String> {
+    #[derive(serde::Deserialize)]
+    struct PullRequest {
+        html_url: String,
+    }
+
+    let url = format!("https://api.github.com/repos/{}/{}/pulls", owner, repo);
+    let body = serde_json::json!({
+        "title": title,
+        "head": branch,
+        "base": base,
+        "body": "Submitted with `porters registry publish`.",
+    });
+
+    // The blocking client can't run on the async runtime's threads
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                let response = reqwest::blocking::Client::new()
+                    .post(&url)
+                    .header("Authorization", format!("Bearer {}", token))
+                    .header("Accept", "application/vnd.github.v3+json")
+                    .header("User-Agent", "porters")
+                    .json(&body)
+                    .send()
+                    .context("Failed to open pull request")?;
+                if !response.status().is_success() {
+                    bail!("GitHub API error: {}", response.text()?);
+                }
+                Ok(response.json::<PullRequest>()?.html_url)
+            })
+            .join()
+            .map_err(|_| anyhow!("Opening the pull request panicked"))?
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn release(version: &str, rev: &str) -> PackageDefinition {
+        serde_json::from_value(serde_json::json!({
+            "name": "acme-log",
+            "description": "Logging for Acme services",
+            "repository": "https://git.acme.dev/acme/log",
+            "version": version,
+            "license": "MIT",
+            "build_system": "cmake",
+            "dependencies": { "fmt": "^10.0.0" },
+            "rev": rev,
+            "checksum": format!("sha256:{}", "ab".repeat(32)),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_publish_to_directory() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path();
+        fs::write(dir.join(INDEX_FILE), "{}").unwrap();
+
+        let path = publish_to_directory(dir, "logging", &release("1.0.0", "1111111")).unwrap();
+        assert_eq!(path, dir.join("logging/acme-log.json"));
+
+        // Registry-maintained fields survive later releases
+        let mut entry: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        entry["install"] = serde_json::json!({ "cmake": { "targets": ["acme::log"] } });
+        fs::write(&path, entry.to_string()).unwrap();
+
+        let mut next = release("1.1.0", "2222222");
+        next.dependencies.clear();
+        publish_to_directory(dir, "other", &next).unwrap();
+
+        let entry: PackageDefinition =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(entry.version, "1.1.0");
+        assert_eq!(entry.rev.as_deref(), Some("2222222"));
+        assert!(entry.cmake_install().is_some());
+        let releases = entry.releases();
+        assert_eq!(releases.len(), 2);
+        assert_eq!(releases[1].rev.as_deref(), Some("1111111"));
+        assert_eq!(releases[1].dependencies.len(), 1);
+        assert!(releases[0].dependencies.is_empty());
+
+        let index = sparse::load_index(&dir.join(INDEX_FILE)).unwrap();
+        assert_eq!(index.path_of("acme-log"), "logging/acme-log.json");
+
+        // Versions only move forward
+        let err = publish_to_directory(dir, "logging", &release("1.1.0", "3333333")).unwrap_err();
+        assert!(err.to_string().contains("already published"));
+        let err = publish_to_directory(dir, "logging", &release("1.0.5", "3333333")).unwrap_err();
+        assert!(err.to_string().contains("older than"));
+
+        // The name belongs to the original repository
+        let mut other = release("2.0.0", "4444444");
+        other.repository = "https://github.com/someone/else".to_string();
+        let err = publish_to_directory(dir, "logging", &other).unwrap_err();
+        assert!(err.to_string().contains("already published from"));
+
+        // Definitions must match the schema
+        let mut invalid = release("2.0.0", "4444444");
+        invalid.description = "Too short".to_string();
+        assert!(publish_to_directory(dir, "logging", &invalid).is_err());
+    }
+}
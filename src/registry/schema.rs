@@ -0,0 +1,201 @@
+//! Package definition schema
+//!
+//! Registry package definitions are checked against `registry/schema.json`,
+//! which is compiled into the binary so validation works offline.
+
+use super::sparse::INDEX_FILE;
+use crate::version::VersionReq;
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use walkdir::WalkDir;
+
+/// The JSON schema of registry package definitions
+pub const SCHEMA: &str = include_str!("../../registry/schema.json");
+
+fn validator() -> &'static jsonschema::Validator {
+    static VALIDATOR: OnceLock<jsonschema::Validator> = OnceLock::new();
+    VALIDATOR.get_or_init(|| {
+        let schema: serde_json::Value =
+            serde_json::from_str(SCHEMA).expect("registry/schema.json is valid JSON");
+        jsonschema::validator_for(&schema).expect("registry/schema.json is a valid schema")
+    })
+}
+
+/// Problems with a package definition, one `path: message` per entry
+///
+/// Besides the schema itself, checks what JSON schema can't express: the
+/// license must be an SPDX expression and dependencies must be valid
+/// version requirements.
+pub fn check(definition: &serde_json::Value) -> Vec<String> {
+    let mut errors: Vec<String> = validator()
+        .iter_errors(definition)
+        .map(|error| {
+            let path = error.instance_path.to_string();
+            if path.is_empty() {
+                error.to_string()
+            } else {
+                format!("{}: {}", path, error)
+            }
+        })
+        .collect();
+
+    if let Some(license) = definition.get("license").and_then(|v| v.as_str())
+        && !crate::license::is_spdx_expression(license)
+    {
+        errors.push(format!(
+            "/license: \"{}\" is not an SPDX license expression",
+            license
+        ));
+    }
+
+    check_requirements(definition, "/dependencies", &mut errors);
+    check_requirements(definition, "/dev_dependencies", &mut errors);
+    let versions = definition.get("versions").and_then(|v| v.as_array());
+    for i in 0..versions.map_or(0, Vec::len) {
+        check_requirements(
+            definition,
+            &format!("/versions/{}/dependencies", i),
+            &mut errors,
+        );
+    }
+
+    errors
+}
+
+fn check_requirements(definition: &serde_json::Value, pointer: &str, errors: &mut Vec<String>) {
+    let Some(requirements) = definition.pointer(pointer).and_then(|v| v.as_object()) else {
+        return;
+    };
+    for (name, requirement) in requirements {
+        if let Some(requirement) = requirement.as_str()
+            && VersionReq::parse(requirement).is_err()
+        {
+            errors.push(format!(
+                "{}/{}: \"{}\" is not a valid version requirement",
+                pointer, name, requirement
+            ));
+        }
+    }
+}
+
+/// Fail with every schema violation of the definition of `name`
+pub fn validate(name: &str, definition: &serde_json::Value) -> Result<()> {
+    let errors = check(definition);
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(anyhow::anyhow!(
+        "Package definition of '{}' does not match the registry schema:\n  {}",
+        name,
+        errors.join("\n  ")
+    ))
+}
+
+/// A package definition file with its problems
+pub type InvalidFile = (PathBuf, Vec<String>);
+
+/// Check a package definition file, or every definition in a registry
+/// directory
+///
+/// Returns the number of files checked and the problems of each invalid one.
+pub fn check_path(path: &Path) -> Result<(usize, Vec<InvalidFile>)> {
+    let files: Vec<PathBuf> = if path.is_file() {
+        vec![path.to_path_buf()]
+    } else if path.is_dir() {
+        WalkDir::new(path)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| {
+                entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.')
+            })
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.into_path())
+            .filter(|path| {
+                let file_name = path.file_name().unwrap_or_default();
+                path.extension().is_some_and(|ext| ext == "json")
+                    && file_name != "schema.json"
+                    && file_name != INDEX_FILE
+            })
+            .collect()
+    } else {
+        bail!("{} does not exist", path.display());
+    };
+
+    let mut invalid = Vec::new();
+    for file in &files {
+        let content = fs::read_to_string(file)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        let errors = match serde_json::from_str::<serde_json::Value>(&content) {
+            Ok(definition) => {
+                let mut errors = check(&definition);
+                let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+                if let Some(name) = definition.get("name").and_then(|v| v.as_str())
+                    && name != stem
+                {
+                    errors.push(format!(
+                        "/name: \"{}\" should be defined in {}.json",
+                        name, name
+                    ));
+                }
+                errors
+            }
+            Err(err) => vec![format!("Invalid JSON: {}", err)],
+        };
+        if !errors.is_empty() {
+            invalid.push((file.clone(), errors));
+        }
+    }
+
+    Ok((files.len(), invalid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_registry_is_valid() {
+        let registry = Path::new(env!("CARGO_MANIFEST_DIR")).join("registry");
+        let (checked, invalid) = check_path(&registry).unwrap();
+        assert!(checked > 0);
+        assert!(invalid.is_empty(), "{:?}", invalid);
+    }
+
+    #[test]
+    fn test_check() {
+        let definition = serde_json::json!({
+            "name": "acme-net",
+            "description": "Networking for Acme services",
+            "repository": "git@git.acme.dev:acme/net.git",
+            "version": "2.0.0",
+            "license": "Apache-2.0 WITH LLVM-exception",
+            "build_system": "cmake",
+            "dependencies": { "asio": "^1.28" },
+            "versions": [{ "version": "1.0.0", "dependencies": { "asio": "1.x" } }],
+            "deprecated": "Merged into acme-core",
+            "replaced_by": "acme-core"
+        });
+        assert_eq!(
+            check(&definition),
+            vec![
+                "/versions/0/dependencies/asio: \"1.x\" is not a valid version requirement"
+                    .to_string()
+            ]
+        );
+
+        let definition = serde_json::json!({
+            "name": "acme-net",
+            "description": "Networking for Acme services",
+            "repository": "https://git.acme.dev/acme/net",
+            "version": "2.0",
+            "license": "Apache 2",
+            "build_system": "cmake"
+        });
+        let errors = check(&definition);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("/version:"));
+        assert!(errors[1].starts_with("/license:"));
+    }
+}
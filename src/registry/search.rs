@@ -0,0 +1,304 @@
+//! Ranked, faceted registry search
+//!
+//! Every word of a query must match a package's name, tags or description.
+//! Matches in the name weigh most, then tags, then the description, so
+//! `porters registry search json` lists `nlohmann-json` before packages that
+//! merely mention JSON. Filters narrow the results down by tag, platform,
+//! license, header-only packages and the C++ standard they need.
+
+use super::PackageDefinition;
+use crate::deps::{constraints, platform};
+use serde::Serialize;
+
+/// Filters of `porters registry search`
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilters {
+    /// Tags the package must all have
+    pub tags: Vec<String>,
+    /// Platform the package must support (`linux`, `windows-x86_64`, ...)
+    pub platform: Option<String>,
+    /// License the package's SPDX expression must mention
+    pub license: Option<String>,
+    /// Only header-only packages
+    pub header_only: bool,
+    /// C++ standard (year) the package must be usable with
+    pub cpp_standard: Option<u32>,
+}
+
+impl SearchFilters {
+    /// Check whether a package passes every filter
+    pub fn matches(&self, pkg: &PackageDefinition) -> bool {
+        let has_tag = |tag: &str| pkg.tags.iter().any(|t| t.eq_ignore_ascii_case(tag));
+
+        if !self.tags.iter().all(|tag| has_tag(tag)) {
+            return false;
+        }
+
+        if let Some(wanted) = &self.platform
+            && !pkg.platforms.is_empty()
+            && !pkg
+                .platforms
+                .iter()
+                .any(|p| platform::matches(p, wanted) || platform::matches(wanted, p))
+        {
+            return false;
+        }
+
+        if let Some(license) = &self.license
+            && !license_ids(&pkg.license).any(|id| id.eq_ignore_ascii_case(license))
+        {
+            return false;
+        }
+
+        if self.header_only && !is_header_only(pkg) {
+            return false;
+        }
+
+        if let Some(standard) = self.cpp_standard
+            && let Some(constraints) = &pkg.constraints
+        {
+            let bound =
+                |value: &Option<String>| value.as_deref().and_then(constraints::parse_standard);
+            if bound(&constraints.min_cpp_standard).is_some_and(|min| min > standard)
+                || bound(&constraints.max_cpp_standard).is_some_and(|max| max < standard)
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Whether a package is header-only: tagged `header-only` or built that
+/// way by default (`options.header_only`)
+pub fn is_header_only(pkg: &PackageDefinition) -> bool {
+    pkg.tags
+        .iter()
+        .any(|t| t.eq_ignore_ascii_case("header-only"))
+        || pkg.options.get("header_only").and_then(|v| v.as_bool()) == Some(true)
+}
+
+/// License identifiers in an SPDX expression
+fn license_ids(expression: &str) -> impl Iterator<Item = &str> {
+    expression
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .filter(|token| !token.is_empty() && !matches!(*token, "AND" | "OR" | "WITH"))
+}
+
+/// Relevance of a package for a query, `None` if it doesn't match
+///
+/// An empty query matches every package with the same score.
+pub fn score(pkg: &PackageDefinition, query: &str) -> Option<u32> {
+    let query = query.trim().to_lowercase();
+    let name = pkg.name.to_lowercase();
+    let description = pkg.description.to_lowercase();
+    let tags: Vec<String> = pkg.tags.iter().map(|t| t.to_lowercase()).collect();
+
+    let mut total = if !query.is_empty() && name == query {
+        200
+    } else {
+        0
+    };
+
+    for term in query.split_whitespace() {
+        let mut score = if name == term {
+            100
+        } else if name.starts_with(term) {
+            60
+        } else if name.contains(term) {
+            40
+        } else {
+            0
+        };
+
+        if tags.iter().any(|tag| tag == term) {
+            score += 30;
+        } else if tags.iter().any(|tag| tag.contains(term)) {
+            score += 15;
+        }
+
+        if description
+            .split(|c: char| !c.is_alphanumeric() && c != '+' && c != '-')
+            .any(|word| word == term)
+        {
+            score += 10;
+        } else if description.contains(term) {
+            score += 5;
+        }
+
+        if score == 0 {
+            return None;
+        }
+        total += score;
+    }
+
+    Some(total)
+}
+
+/// Sort packages by relevance for a query, then by name
+///
+/// The sort is stable, so packages of the same name keep the order of
+/// their registries.
+pub fn rank(packages: &mut [PackageDefinition], query: &str) {
+    packages.sort_by_cached_key(|pkg| {
+        (
+            std::cmp::Reverse(score(pkg, query).unwrap_or(0)),
+            pkg.name.to_lowercase(),
+        )
+    });
+}
+
+/// A search result as printed by `porters registry search --json`
+#[derive(Debug, Serialize)]
+pub struct SearchResult<'a> {
+    pub name: &'a str,
+    pub version: &'a str,
+    pub description: &'a str,
+    pub license: &'a str,
+    pub repository: &'a str,
+    pub registry: &'a str,
+    pub tags: &'a [String],
+    pub platforms: &'a [String],
+    pub header_only: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_cpp_standard: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<String>,
+    pub score: u32,
+}
+
+impl<'a> SearchResult<'a> {
+    pub fn new(pkg: &'a PackageDefinition, query: &str) -> Self {
+        Self {
+            name: &pkg.name,
+            version: &pkg.version,
+            description: &pkg.description,
+            license: &pkg.license,
+            repository: &pkg.repository,
+            registry: &pkg.registry,
+            tags: &pkg.tags,
+            platforms: &pkg.platforms,
+            header_only: is_header_only(pkg),
+            min_cpp_standard: pkg
+                .constraints
+                .as_ref()
+                .and_then(|c| c.min_cpp_standard.as_deref()),
+            deprecated: pkg.deprecation_notice(),
+            score: score(pkg, query).unwrap_or(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, description: &str, extra: serde_json::Value) -> PackageDefinition {
+        let mut definition = serde_json::json!({
+            "name": name,
+            "description": description,
+            "repository": format!("https://example.com/{}", name),
+            "version": "1.0.0",
+            "license": "MIT",
+            "build_system": "cmake"
+        });
+        for (key, value) in extra.as_object().unwrap() {
+            definition[key] = value.clone();
+        }
+        serde_json::from_value(definition).unwrap()
+    }
+
+    #[test]
+    fn test_ranking() {
+        let mut packages = vec![
+            package(
+                "simdjson",
+                "Parsing gigabytes of JSON per second",
+                serde_json::json!({ "tags": ["parsing"] }),
+            ),
+            package(
+                "yaml-cpp",
+                "A YAML parser and emitter",
+                serde_json::json!({}),
+            ),
+            package(
+                "rapidxml",
+                "Fast XML parser, with optional json export",
+                serde_json::json!({}),
+            ),
+            package(
+                "json",
+                "JSON for Modern C++",
+                serde_json::json!({ "tags": ["json"] }),
+            ),
+        ];
+        packages.retain(|pkg| score(pkg, "json").is_some());
+        rank(&mut packages, "json");
+
+        let names: Vec<&str> = packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["json", "simdjson", "rapidxml"]);
+
+        // Every word has to match
+        assert!(score(&packages[1], "json parsing").is_some());
+        assert!(score(&packages[1], "json networking").is_none());
+    }
+
+    #[test]
+    fn test_filters() {
+        let asio = package(
+            "asio",
+            "Asynchronous networking and I/O",
+            serde_json::json!({
+                "license": "BSL-1.0",
+                "tags": ["networking", "header-only"],
+                "platforms": ["linux", "macos-aarch64"],
+                "constraints": { "min_cpp_standard": "11" }
+            }),
+        );
+        let acme = package(
+            "acme-net",
+            "Networking for Acme services",
+            serde_json::json!({
+                "license": "MIT OR Apache-2.0",
+                "tags": ["networking"],
+                "constraints": { "min_cpp_standard": "c++20" }
+            }),
+        );
+
+        let filters = SearchFilters {
+            tags: vec!["Networking".to_string()],
+            ..Default::default()
+        };
+        assert!(filters.matches(&asio) && filters.matches(&acme));
+
+        let filters = SearchFilters {
+            platform: Some("macos".to_string()),
+            ..Default::default()
+        };
+        assert!(filters.matches(&asio) && filters.matches(&acme));
+        let filters = SearchFilters {
+            platform: Some("windows".to_string()),
+            ..Default::default()
+        };
+        assert!(!filters.matches(&asio) && filters.matches(&acme));
+
+        let filters = SearchFilters {
+            license: Some("apache-2.0".to_string()),
+            ..Default::default()
+        };
+        assert!(!filters.matches(&asio) && filters.matches(&acme));
+
+        let filters = SearchFilters {
+            header_only: true,
+            ..Default::default()
+        };
+        assert!(filters.matches(&asio) && !filters.matches(&acme));
+
+        let filters = SearchFilters {
+            cpp_standard: constraints::parse_standard("17"),
+            ..Default::default()
+        };
+        assert!(filters.matches(&asio) && !filters.matches(&acme));
+    }
+}
@@ -0,0 +1,407 @@
+//! HTTP ("sparse") registry index protocol
+//!
+//! A sparse registry is a static file tree served over HTTP. `index.json`
+//! maps package names to their definition files, relative to the registry
+//! URL, and each definition is downloaded only when it is looked up.
+//! Responses are cached in the registry's index directory next to their
+//! `ETag` and `Last-Modified` headers, so later lookups are conditional
+//! requests that usually come back as `304 Not Modified`.
+//!
+//! Registries select the protocol with a `sparse+http://` or
+//! `sparse+https://` URL.
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// URL prefix selecting the sparse protocol
+pub const SPARSE_PREFIX: &str = "sparse+";
+
+/// Listing of the packages of a sparse registry
+pub const INDEX_FILE: &str = "index.json";
+
+/// Contents of `index.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SparseIndex {
+    /// Package name → definition file relative to the registry URL
+    #[serde(default)]
+    pub packages: BTreeMap<String, String>,
+}
+
+impl SparseIndex {
+    /// Definition file of a package (default: `<name>.json`)
+    pub fn path_of(&self, name: &str) -> String {
+        self.packages
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| format!("{}.json", name))
+    }
+}
+
+/// Validators of a cached response
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheHeaders {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_modified: Option<String>,
+}
+
+/// Outcome of a conditional request
+struct Response {
+    status: u16,
+    headers: CacheHeaders,
+    body: Vec<u8>,
+}
+
+/// Downloads registry files into a local cache directory
+pub struct SparseClient<'a> {
+    /// Registry URL without the `sparse+` prefix or a trailing slash
+    base: &'a str,
+    cache_dir: &'a Path,
+    auth_token: Option<&'a str>,
+}
+
+impl<'a> SparseClient<'a> {
+    pub fn new(url: &'a str, cache_dir: &'a Path, auth_token: Option<&'a str>) -> Self {
+        let base = url.strip_prefix(SPARSE_PREFIX).unwrap_or(url);
+        Self {
+            base: base.trim_end_matches('/'),
+            cache_dir,
+            auth_token,
+        }
+    }
+
+    /// Cached copy of a registry file, if there is one
+    pub fn cached(&self, relative: &str) -> Result<Option<PathBuf>> {
+        let path = self.cache_path(relative)?;
+        Ok(path.exists().then_some(path))
+    }
+
+    /// Download a registry file, revalidating the cached copy
+    ///
+    /// Returns the cached file, or `None` if the registry doesn't have it.
+    pub fn fetch(&self, relative: &str) -> Result<Option<PathBuf>> {
+        let path = self.cache_path(relative)?;
+        let headers_path = headers_path(&path);
+        let cached_headers = if path.exists() {
+            fs::read_to_string(&headers_path)
+                .ok()
+                .and_then(|content| serde_json::from_str(&content).ok())
+                .unwrap_or_default()
+        } else {
+            CacheHeaders::default()
+        };
+
+        let url = format!("{}/{}", self.base, relative);
+        let response = self
+            .get(&url, &cached_headers)
+            .with_context(|| format!("Failed to fetch {}", url))?;
+
+        match response.status {
+            304 => Ok(Some(path)),
+            404 | 410 => {
+                // The file is gone from the registry
+                fs::remove_file(&path).ok();
+                fs::remove_file(&headers_path).ok();
+                Ok(None)
+            }
+            200 => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let tmp = path.with_extension("json.tmp");
+                fs::write(&tmp, &response.body)
+                    .with_context(|| format!("Failed to write {}", tmp.display()))?;
+                fs::rename(&tmp, &path)?;
+                fs::write(&headers_path, serde_json::to_string(&response.headers)?)?;
+                Ok(Some(path))
+            }
+            401 | 403 => bail!(
+                "Access to {} was denied (HTTP {}). Check the registry's auth token",
+                url,
+                response.status
+            ),
+            status => bail!("Failed to fetch {}: HTTP {}", url, status),
+        }
+    }
+
+    /// Where a registry file is cached
+    fn cache_path(&self, relative: &str) -> Result<PathBuf> {
+        let relative = Path::new(relative);
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            bail!(
+                "Invalid registry path '{}' (must be relative and stay inside the registry)",
+                relative.display()
+            );
+        }
+        Ok(self.cache_dir.join(relative))
+    }
+
+    /// Perform a conditional GET
+    ///
+    /// The blocking client runs on its own thread, because lookups also
+    /// happen inside the async dependency resolver.
+    fn get(&self, url: &str, cached: &CacheHeaders) -> Result<Response> {
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let client = reqwest::blocking::Client::builder()
+                        .timeout(std::time::Duration::from_secs(30))
+                        .build()?;
+                    let mut request = client.get(url).header("User-Agent", "porters");
+                    if let Some(etag) = &cached.etag {
+                        request = request.header("If-None-Match", etag);
+                    }
+                    if let Some(last_modified) = &cached.last_modified {
+                        request = request.header("If-Modified-Since", last_modified);
+                    }
+                    if let Some(token) = self.auth_token {
+                        request = request.header("Authorization", authorization(token));
+                    }
+
+                    let response = request.send()?;
+                    let header = |name: &str| {
+                        response
+                            .headers()
+                            .get(name)
+                            .and_then(|value| value.to_str().ok())
+                            .map(String::from)
+                    };
+                    let headers = CacheHeaders {
+                        etag: header("ETag"),
+                        last_modified: header("Last-Modified"),
+                    };
+                    let status = response.status().as_u16();
+                    let body = response.bytes()?.to_vec();
+                    Ok(Response {
+                        status,
+                        headers,
+                        body,
+                    })
+                })
+                .join()
+                .map_err(|_| anyhow!("HTTP request thread panicked"))?
+        })
+    }
+}
+
+/// Load a cached `index.json`
+pub fn load_index(path: &Path) -> Result<SparseIndex> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Write `index.json` for serving a registry directory over HTTP
+///
+/// Returns the number of packages listed.
+pub fn write_index(dir: &Path) -> Result<usize> {
+    let index = scan_directory(dir)?;
+
+    let path = dir.join(INDEX_FILE);
+    fs::write(&path, serde_json::to_string_pretty(&index)? + "\n")
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(index.packages.len())
+}
+
+/// Find every package definition in a registry directory
+pub fn scan_directory(dir: &Path) -> Result<SparseIndex> {
+    let mut index = SparseIndex::default();
+    collect_packages(dir, dir, &mut index)?;
+    Ok(index)
+}
+
+fn collect_packages(root: &Path, dir: &Path, index: &mut SparseIndex) -> Result<()> {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .collect::<std::io::Result<_>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+        if file_name.starts_with('.') {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_packages(root, &path, index)?;
+        } else if path.extension().and_then(|s| s.to_str()) == Some("json")
+            && file_name != "schema.json"
+            && file_name != INDEX_FILE
+        {
+            #[derive(Deserialize)]
+            struct Named {
+                name: String,
+            }
+
+            let content = fs::read_to_string(&path)?;
+            let Ok(Named { name }) = serde_json::from_str(&content) else {
+                continue;
+            };
+            let relative = path
+                .strip_prefix(root)?
+                .to_string_lossy()
+                .replace('\\', "/");
+            if let Some(previous) = index.packages.insert(name.clone(), relative) {
+                bail!(
+                    "Package '{}' is defined twice ({} and {})",
+                    name,
+                    previous,
+                    index.packages[&name]
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `Authorization` header value for a token
+///
+/// Tokens are sent as bearer tokens unless they name their own scheme
+/// (e.g. `Basic dXNlcjpwYXNz`).
+fn authorization(token: &str) -> String {
+    if token.contains(' ') {
+        token.to_string()
+    } else {
+        format!("Bearer {}", token)
+    }
+}
+
+fn headers_path(path: &Path) -> PathBuf {
+    path.with_extension("json.headers")
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+
+    /// Requests a test server received
+    #[derive(Default)]
+    pub struct ServerLog {
+        pub not_modified: AtomicUsize,
+        pub authorization: Mutex<Vec<String>>,
+    }
+
+    /// Serve `files` over HTTP with ETags, returning the base URL
+    pub fn serve(files: HashMap<String, String>) -> (String, Arc<ServerLog>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let log = Arc::new(ServerLog::default());
+
+        let server_log = log.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+
+                let mut if_none_match = None;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    let (name, value) = line.split_once(": ").unwrap_or((line, ""));
+                    match name.to_ascii_lowercase().as_str() {
+                        "if-none-match" => if_none_match = Some(value.to_string()),
+                        "authorization" => server_log
+                            .authorization
+                            .lock()
+                            .unwrap()
+                            .push(value.to_string()),
+                        _ => {}
+                    }
+                }
+
+                let response = match files.get(path.trim_start_matches('/')) {
+                    Some(body) => {
+                        let etag = format!("\"{}\"", body.len());
+                        if if_none_match.as_deref() == Some(etag.as_str()) {
+                            server_log.not_modified.fetch_add(1, Ordering::SeqCst);
+                            "HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n".to_string()
+                        } else {
+                            format!(
+                                "HTTP/1.1 200 OK\r\nETag: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                                etag,
+                                body.len(),
+                                body
+                            )
+                        }
+                    }
+                    None => {
+                        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            .to_string()
+                    }
+                };
+                stream.write_all(response.as_bytes()).ok();
+            }
+        });
+
+        (url, log)
+    }
+
+    #[test]
+    fn test_fetch_revalidates_cached_files() {
+        let temp = TempDir::new().unwrap();
+        let files = HashMap::from([(
+            "reg/index.json".to_string(),
+            r#"{"packages":{"zlib":"compression/zlib.json"}}"#.to_string(),
+        )]);
+        let (url, log) = serve(files);
+        let url = format!("sparse+{}/reg/", url);
+
+        let client = SparseClient::new(&url, temp.path(), Some("secret"));
+        let path = client.fetch(INDEX_FILE).unwrap().unwrap();
+        let index = load_index(&path).unwrap();
+        assert_eq!(index.path_of("zlib"), "compression/zlib.json");
+        assert_eq!(index.path_of("fmt"), "fmt.json");
+
+        // The second request is answered from the cache
+        assert_eq!(client.fetch(INDEX_FILE).unwrap(), Some(path));
+        assert_eq!(log.not_modified.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            log.authorization.lock().unwrap().as_slice(),
+            ["Bearer secret", "Bearer secret"]
+        );
+
+        assert_eq!(client.fetch("missing.json").unwrap(), None);
+        assert!(client.fetch("../outside.json").is_err());
+    }
+
+    #[test]
+    fn test_write_index() {
+        let temp = TempDir::new().unwrap();
+        fs::create_dir_all(temp.path().join("compression")).unwrap();
+        fs::write(
+            temp.path().join("compression/zlib.json"),
+            r#"{"name": "zlib"}"#,
+        )
+        .unwrap();
+        fs::write(temp.path().join("fmt.json"), r#"{"name": "fmt"}"#).unwrap();
+        fs::write(temp.path().join("schema.json"), r#"{"name": "schema"}"#).unwrap();
+
+        assert_eq!(write_index(temp.path()).unwrap(), 2);
+        let index = load_index(&temp.path().join(INDEX_FILE)).unwrap();
+        assert_eq!(index.packages["zlib"], "compression/zlib.json");
+        assert_eq!(index.packages["fmt"], "fmt.json");
+    }
+}
@@ -27,34 +27,6 @@ pub struct Dependency {
     pub features: Vec<String>,
 }
 
-/// Resolved dependency with its version
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct ResolvedDependency {
-    pub name: String,
-    pub version: Version,
-    pub source: DependencySource,
-}
-
-/// Source of a dependency
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum DependencySource {
-    Conan,
-    Vcpkg,
-    XMake,
-    Registry,
-}
-
-impl std::fmt::Display for DependencySource {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            DependencySource::Conan => write!(f, "Conan"),
-            DependencySource::Vcpkg => write!(f, "vcpkg"),
-            DependencySource::XMake => write!(f, "XMake"),
-            DependencySource::Registry => write!(f, "Registry"),
-        }
-    }
-}
-
 /// Platform constraints
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlatformConstraints {
@@ -73,13 +45,16 @@ pub struct PlatformConstraints {
 }
 
 /// Package metadata for resolution
+///
+/// This resolver only checks registry metadata (versions and constraints)
+/// without fetching anything; projects are resolved into
+/// [`crate::deps::ResolvedDependency`] by [`crate::deps::resolver`].
 #[derive(Debug, Clone)]
 pub struct PackageMetadata {
     pub name: String,
     pub version: Version,
     pub dependencies: Vec<Dependency>,
     pub constraints: Option<PlatformConstraints>,
-    pub source: DependencySource,
 }
 
 /// Dependency conflict
@@ -92,7 +67,7 @@ pub struct DependencyConflict {
 /// Dependency graph node
 #[derive(Debug, Clone)]
 struct DependencyNode {
-    package: ResolvedDependency,
+    package: PackageMetadata,
     dependencies: Vec<String>,
     #[allow(dead_code)] // Used for debugging and future features
     depth: usize,
@@ -100,8 +75,8 @@ struct DependencyNode {
 
 /// Dependency resolver
 pub struct DependencyResolver {
-    /// Resolved dependencies
-    resolved: HashMap<String, ResolvedDependency>,
+    /// Resolved packages
+    resolved: HashMap<String, PackageMetadata>,
     /// Dependency graph for circular detection
     graph: HashMap<String, DependencyNode>,
     /// Conflicts detected
@@ -126,7 +101,7 @@ impl DependencyResolver {
         &mut self,
         root_deps: Vec<Dependency>,
         fetch_metadata: F,
-    ) -> Result<Vec<ResolvedDependency>>
+    ) -> Result<Vec<PackageMetadata>>
     where
         F: Fn(&str, &str) -> Result<PackageMetadata>,
     {
@@ -169,11 +144,7 @@ impl DependencyResolver {
             self.validate_constraints(&metadata)?;
 
             // Add to resolved set
-            let resolved_dep = ResolvedDependency {
-                name: metadata.name.clone(),
-                version: metadata.version.clone(),
-                source: metadata.source.clone(),
-            };
+            let resolved_dep = metadata.clone();
 
             // Add to graph for circular dependency detection
             let dep_names: Vec<String> = metadata
@@ -325,7 +296,7 @@ impl DependencyResolver {
     }
 
     /// Topological sort of dependencies
-    fn topological_sort(&self) -> Vec<ResolvedDependency> {
+    fn topological_sort(&self) -> Vec<PackageMetadata> {
         let mut sorted = Vec::new();
         let mut visited = HashSet::new();
         let mut temp_mark = HashSet::new();
@@ -344,7 +315,7 @@ impl DependencyResolver {
         package: &str,
         visited: &mut HashSet<String>,
         temp_mark: &mut HashSet<String>,
-        sorted: &mut Vec<ResolvedDependency>,
+        sorted: &mut Vec<PackageMetadata>,
     ) {
         if visited.contains(package) {
             return;
@@ -437,14 +408,6 @@ impl Default for DependencyResolver {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_dependency_source_display() {
-        assert_eq!(DependencySource::Conan.to_string(), "Conan");
-        assert_eq!(DependencySource::Vcpkg.to_string(), "vcpkg");
-        assert_eq!(DependencySource::XMake.to_string(), "XMake");
-        assert_eq!(DependencySource::Registry.to_string(), "Registry");
-    }
-
     #[test]
     fn test_resolver_creation() {
         let resolver = DependencyResolver::new();
@@ -482,7 +445,6 @@ mod tests {
                 version: Version::new(1, 0, 0),
                 dependencies: vec![],
                 constraints: None,
-                source: DependencySource::Registry,
             })
         };
 
@@ -519,7 +481,6 @@ mod tests {
                 version: Version::new(1, 0, 0),
                 dependencies,
                 constraints: None,
-                source: DependencySource::Registry,
             })
         };
 
@@ -551,7 +512,6 @@ mod tests {
                 compilers: HashMap::new(),
                 environment: HashMap::new(),
             }),
-            source: DependencySource::Registry,
         };
 
         let result = resolver.validate_constraints(&metadata);
@@ -580,7 +540,6 @@ mod tests {
                 compilers: HashMap::new(),
                 environment: HashMap::new(),
             }),
-            source: DependencySource::Registry,
         };
 
         let result = resolver.validate_constraints(&metadata);
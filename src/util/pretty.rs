@@ -4,25 +4,43 @@
 //! for different message types: steps, success, info, warnings, and errors.
 
 use colored::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Send messages to stderr, keeping stdout for machine-readable output
+pub fn use_stderr(enabled: bool) {
+    TO_STDERR.store(enabled, Ordering::Relaxed);
+}
+
+macro_rules! message {
+    ($($arg:tt)*) => {
+        if TO_STDERR.load(Ordering::Relaxed) {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
 
 /// Print a step header with arrow emoji
 pub fn print_step(msg: &str) {
-    println!("{}  {}", "➡️".bright_blue(), msg.bold().bright_white());
+    message!("{}  {}", "➡️".bright_blue(), msg.bold().bright_white());
 }
 
 /// Print a success message with checkmark emoji
 pub fn print_success(msg: &str) {
-    println!("{}  {}", "✅".green(), msg.green());
+    message!("{}  {}", "✅".green(), msg.green());
 }
 
 /// Print an info message
 pub fn print_info(msg: &str) {
-    println!("{}  {}", "ℹ️".cyan(), msg.cyan());
+    message!("{}  {}", "ℹ️".cyan(), msg.cyan());
 }
 
 /// Print a warning message
 pub fn print_warning(msg: &str) {
-    println!("{}  {}", "⚠️".yellow(), msg.yellow());
+    message!("{}  {}", "⚠️".yellow(), msg.yellow());
 }
 
 /// Print an error message with fire emoji
@@ -32,12 +50,12 @@ pub fn print_error(msg: &str) {
 
 /// Print a build message
 pub fn print_build(msg: &str) {
-    println!("{}  {}", "🔨".bright_yellow(), msg);
+    message!("{}  {}", "🔨".bright_yellow(), msg);
 }
 
 /// Print a package message
 pub fn print_package(msg: &str) {
-    println!("{}  {}", "📦".bright_magenta(), msg);
+    message!("{}  {}", "📦".bright_magenta(), msg);
 }
 
 /// Print a graph node
@@ -38,7 +38,6 @@ pub struct Version {
 
 impl Version {
     /// Create a new version
-    #[allow(dead_code)]
     pub fn new(major: u32, minor: u32, patch: u32) -> Self {
         Version {
             major,